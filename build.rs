@@ -0,0 +1,16 @@
+//! Embed the icon in the executable on Windows, where Explorer, the taskbar and the
+//! betty files associated with the IDE show it. The window icon is set at runtime from
+//! `images/coding.png` on every system.
+
+fn main() {
+    println!("cargo:rerun-if-changed=images/coding.ico");
+    #[cfg(windows)]
+    {
+        let mut resource = winres::WindowsResource::new();
+        resource.set_icon("images/coding.ico");
+        // Without the icon, the executable still works
+        if let Err(err) = resource.compile() {
+            println!("cargo:warning=The icon could not be embedded: {}", err);
+        }
+    }
+}
@@ -0,0 +1,890 @@
+//! The [`Highligher`] takes in the source code and returns a stream of [`Token`]s.
+//! Each token has a type, and the type determines the color it will have in the IDE.
+//!
+//! The highlighter is the one of the colors IDE, but it does not depend on the GUI,
+//! so the betty toolchain can use it as well:
+//!
+//! ```
+//! use colors_highlight::{Config, Highligher, TokenType};
+//!
+//! let config = Config {
+//!     name: "betty".to_string(),
+//!     betty: true,
+//!     line_comment: Some("|".to_string()),
+//!     string_delimiters: vec!['"'],
+//!     ..Default::default()
+//! };
+//! let source = "x = 0x1F | comment";
+//! let (tokens, balance) = Highligher::new(source, &config).make_tokens();
+//!
+//! assert!(tokens.iter().any(|token| token.0 == TokenType::Num));
+//! assert!(balance.is_balanced());
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod structure;
+
+use std::ops::Range;
+
+use unicode_xid::UnicodeXID;
+
+/// Same naming conventions used in betty
+type Int = i64;
+
+/// Same naming conventions used in betty
+type Float = f64;
+
+/// A [`Token`] is composed of a type, of the span of its literal value in the
+/// source, expressed in bytes, and of its position in the source
+#[derive(Clone)]
+pub struct Token(
+    /// What the token is
+    pub TokenType,
+    /// Bytes of the source
+    pub Range<usize>,
+    /// Line and column of its first character
+    pub Position,
+);
+
+/// Position of the first character of a [`Token`], where both the line and the
+/// column (expressed in characters) start from 0
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Line, from 0
+    pub line: usize,
+    /// Column in characters, from 0
+    pub col: usize,
+}
+
+/// All the different [`Token`] types that a text can be divided into. Each token has
+/// a color that is used when drawing text in the code editor. Each color can be
+/// modified by the used in the `settings.json` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+    /// Identifier
+    Ident,
+    /// Number, in any notation
+    Num,
+    /// String, with its delimiters
+    Str,
+    /// Symbol
+    Sym,
+    /// Multi-character operator
+    Op,
+    /// Keyword
+    Kw,
+    /// Builtin function
+    BuiltinFun,
+    /// Identifier followed by '(', i.e. a function
+    Fun,
+    /// Line or block comment, with its delimiters
+    Comment,
+    /// Builtin error
+    Error,
+    /// Identifier that is never defined. Never made by the [`Highligher`], which
+    /// only sees one token at a time.
+    Undefined,
+    /// Anything else, including whitespace
+    Other,
+}
+
+/// State of the analysis at the end of a line. Strings and multi-line comments may
+/// continue on the following lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineState {
+    /// Nothing is left open
+    #[default]
+    Code,
+    /// Inside a string, with its delimiter
+    Str(char),
+    /// Inside a block comment
+    BlockComment,
+}
+
+/// Count of the opened and not yet closed brackets and blocks. A positive number
+/// means that some are left open, a negative one that there are too many closing.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Balance {
+    /// '(' and ')'
+    pub parens: i64,
+    /// '[' and ']'
+    pub brackets: i64,
+    /// 'do' and 'end'
+    pub blocks: i64,
+}
+
+impl Balance {
+    /// Update the count with a new [`Token`] of 'source'
+    #[inline]
+    fn update(&mut self, token: &Token, source: &str) {
+        match (&token.0, &source[token.1.clone()]) {
+            (TokenType::Other, "(") => self.parens += 1,
+            (TokenType::Other, ")") => self.parens -= 1,
+            (TokenType::Other, "[") => self.brackets += 1,
+            (TokenType::Other, "]") => self.brackets -= 1,
+            (TokenType::Kw, "do") => self.blocks += 1,
+            (TokenType::Kw, "end") => self.blocks -= 1,
+            _ => (),
+        }
+    }
+
+    /// Is everything that has been opened also closed?
+    pub fn is_balanced(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::ops::AddAssign for Balance {
+    fn add_assign(&mut self, other: Self) {
+        self.parens += other.parens;
+        self.brackets += other.brackets;
+        self.blocks += other.blocks;
+    }
+}
+
+/// Description of the language analysed by the [`Highligher`]. For betty, the
+/// names and operators are added to the betty ones, while other languages are
+/// described by the configuration alone.
+#[derive(Default, Clone)]
+pub struct Config {
+    /// Name of the language
+    pub name: String,
+    /// Include the betty names and operators?
+    pub betty: bool,
+    /// Comment up to the end of the line
+    pub line_comment: Option<String>,
+    /// Opening and closing delimiters of the multi-line comments
+    pub block_comment: Option<(String, String)>,
+    /// Characters that open and close strings
+    pub string_delimiters: Vec<char>,
+    /// Reserved keywords
+    pub keywords: Vec<String>,
+    /// Builtin functions
+    pub builtins: Vec<String>,
+    /// Builtin errors
+    pub errors: Vec<String>,
+    /// Operators. Single characters are colored as symbols
+    pub operators: Vec<String>,
+}
+
+impl Config {
+    /// The marker of the line comments. An empty marker means that there are none,
+    /// since it would match everywhere.
+    pub fn line_marker(&self) -> Option<&str> {
+        self.line_comment
+            .as_deref()
+            .filter(|marker| !marker.is_empty())
+    }
+
+    /// The delimiters of the multi-line comments. There are none if one of them is
+    /// empty, like for [`Self::line_marker`].
+    pub fn block_delimiters(&self) -> Option<(&str, &str)> {
+        self.block_comment
+            .as_ref()
+            .map(|(open, close)| (open.as_str(), close.as_str()))
+            .filter(|(open, close)| !open.is_empty() && !close.is_empty())
+    }
+}
+
+/// The struct responsible for the analysis of the code editor text. It receives
+/// as input the text as a sequence of characters and returns a sequence of tokens
+/// out of it.
+pub struct Highligher<'a> {
+    source: &'a str,
+    pos: usize,         // byte index of the current character
+    position: Position, // line and column of the current character
+    current_char: Option<char>,
+    config: &'a Config,
+
+    // betty names and operators merged with the ones of the configuration
+    keywords: Vec<&'a str>,
+    builtins: Vec<&'a str>,
+    errors: Vec<&'a str>,
+    symbols: Vec<char>,
+    operators: Vec<&'a str>,
+}
+
+impl Highligher<'_> {
+    // betty reserved keywords
+    const KEYWORDS: [&'static str; 25] = [
+        "and", "or", "not", "if", "else", "do", "end", "for", "foreach", "while", "fun",
+        "continue", "break", "return", "match", "try", "catch", "in", "throw", "using", "as",
+        "true", "false", "nothing", "newerror",
+    ];
+
+    // betty builtin errors that can be catched and thrown
+    const ERRORS: [&'static str; 11] = [
+        "ValueError",
+        "TypeError",
+        "UnknownIdentifierError",
+        "OverflowError",
+        "DivisionByZeroError",
+        "IndexOutOfBoundsError",
+        "FileIOError",
+        "VectorMutationError",
+        "ModuleImportError",
+        "AssertionError",
+        "WrongArgumentsNumberError",
+    ];
+
+    // betty builtin functions
+    const BUILTIN_FUNCTIONS: [&'static str; 42] = [
+        "print",
+        "println",
+        "read_line",
+        "to_int",
+        "to_float",
+        "to_str",
+        "vpush_back",
+        "vpush_front",
+        "vpush_at",
+        "vpop_front",
+        "vpop_back",
+        "vpop_at",
+        "vfrom_range",
+        "vcopy",
+        "str_starts_with",
+        "str_ends_with",
+        "str_is_lowercase",
+        "str_is_uppercase",
+        "str_to_lowercase",
+        "str_to_uppercase",
+        "len",
+        "get",
+        "join",
+        "slice",
+        "split",
+        "replace",
+        "fread",
+        "fwrite",
+        "fappend",
+        "err_short",
+        "err_traceback",
+        "err_kind",
+        "err_line",
+        "err_description",
+        "assert",
+        "isint",
+        "isfloat",
+        "isstr",
+        "isbool",
+        "isvec",
+        "iscallable",
+        "iserr",
+    ];
+
+    /// Valid simbols in betty. Some are commented out because I want to pass them as
+    /// [`TokenType::Other`] color
+    const SYMBOLS: [char; 12] = [
+        '+', '-', '*', '/', '^', '%', ':', /*'(', ')', '[', ']',*/ '=', '>', '<',
+        '!', /* '.', */
+        /*',', */ '?',
+    ];
+
+    /// Operators made of more than one character. They are colored as a whole,
+    /// so they cannot be made of [`Self::SYMBOLS`] each with its own color.
+    const OPERATORS: [&'static str; 5] = ["==", "!=", ">=", "<=", "->"];
+}
+
+impl<'a> Highligher<'a> {
+    /// The names and operators of 'config' are merged with the betty ones, unless
+    /// 'config' describes another language.
+    #[inline]
+    pub fn new(source: &'a str, config: &'a Config) -> Self {
+        let merge = |betty: &[&'static str], user: &'a [String]| -> Vec<&'a str> {
+            let betty = if config.betty { betty } else { &[] };
+            betty
+                .iter()
+                .copied()
+                .chain(user.iter().map(String::as_str))
+                .collect()
+        };
+        let single_chars = config.operators.iter().filter_map(|op| {
+            let mut chars = op.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Some(ch),
+                _ => None,
+            }
+        });
+        let symbols = if config.betty {
+            Self::SYMBOLS.iter().copied().chain(single_chars).collect()
+        } else {
+            single_chars.collect()
+        };
+
+        Self {
+            source,
+            pos: 0,
+            position: Position::default(),
+            current_char: source.chars().next(),
+            config,
+            keywords: merge(&Self::KEYWORDS, &config.keywords),
+            builtins: merge(&Self::BUILTIN_FUNCTIONS, &config.builtins),
+            errors: merge(&Self::ERRORS, &config.errors),
+            symbols,
+            operators: merge(&Self::OPERATORS, &config.operators)
+                .into_iter()
+                .filter(|op| op.chars().count() > 1)
+                .collect(),
+        }
+    }
+
+    /// Analyse a single line (including its newline), which starts in 'state'. Return
+    /// its [`Token`]s and [`Balance`], along with the state at the end of the line.
+    /// Spans and positions of the tokens are relative to the line.
+    pub fn make_line_tokens(
+        line: &str,
+        state: LineState,
+        config: &Config,
+    ) -> (Vec<Token>, Balance, LineState) {
+        // A string or a comment coming from the previous line is continued by
+        // prepending its opening delimiter, which is removed from the tokens later
+        let prefix = match (state, config.block_delimiters()) {
+            (LineState::Str(delimiter), _) => delimiter.to_string(),
+            (LineState::BlockComment, Some((open, _))) => open.to_string(),
+            _ => String::new(),
+        };
+
+        let source = format!("{}{}", prefix, line);
+        let (tokens, balance) = Highligher::new(&source, config).make_tokens();
+
+        let end_state = match tokens.last() {
+            Some(Token(TokenType::Str, span, _)) => {
+                let literal = &source[span.clone()];
+                match literal.chars().next() {
+                    Some(delimiter)
+                        if literal.len() == delimiter.len_utf8()
+                            || !literal.ends_with(delimiter) =>
+                    {
+                        LineState::Str(delimiter)
+                    }
+                    _ => LineState::Code,
+                }
+            }
+            Some(Token(TokenType::Comment, span, _)) => {
+                let literal = &source[span.clone()];
+                match config.block_delimiters() {
+                    Some((open, close))
+                        if literal.starts_with(open)
+                            && (literal.len() < open.len() + close.len()
+                                || !literal.ends_with(close)) =>
+                    {
+                        LineState::BlockComment
+                    }
+                    _ => LineState::Code,
+                }
+            }
+            _ => LineState::Code,
+        };
+
+        // Make the spans and positions relative to the line, dropping what belongs to
+        // the prefix
+        let prefix_chars = prefix.chars().count();
+        let tokens = tokens
+            .into_iter()
+            .filter_map(|Token(typ, span, mut position)| {
+                let span = span.start.saturating_sub(prefix.len())..span.end - prefix.len();
+                position.col = position.col.saturating_sub(prefix_chars);
+                (!span.is_empty()).then_some(Token(typ, span, position))
+            })
+            .collect();
+        (tokens, balance, end_state)
+    }
+
+    /// Advance to the next character of the source by moving past the current one.
+    #[inline]
+    pub fn advance(&mut self) {
+        if let Some(ch) = self.current_char {
+            self.pos += ch.len_utf8();
+            if ch == '\n' {
+                self.position.line += 1;
+                self.position.col = 0;
+            } else {
+                self.position.col += 1;
+            }
+        }
+        self.current_char = self.rest().chars().next();
+    }
+
+    /// The source starting from the current character
+    #[inline]
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    /// Checks whether the next character is 'ch' in the stream of character.
+    /// This does not consider spaces and tabs.
+    #[inline]
+    fn next_is(&mut self, ch: char) -> bool {
+        let first = self.rest().chars().find(|ch| ![' ', '\t'].contains(ch));
+        match first {
+            Some(first) => first == ch,
+            None => false,
+        }
+    }
+
+    /// Checks whether the source, starting from the current character, begins
+    /// with 'text'.
+    #[inline]
+    fn starts_with(&self, text: &str) -> bool {
+        self.rest().starts_with(text)
+    }
+
+    /// Create a new [`Token`] that MAY be an identifier: indeed, if the [`String`]
+    /// is one of:
+    ///     - Reserved keyword
+    ///     - Builtin function
+    ///     - Type
+    ///     - Error
+    /// then its type will be that one.
+    #[inline]
+    fn make_ident(&mut self) -> TokenType {
+        let start = self.pos;
+
+        // Loop as long as we find a valid identifier character.
+        while let Some(ch) = self.current_char {
+            if !is_ident_continue(ch) {
+                break;
+            }
+            self.advance();
+        }
+
+        let ident_str = &&self.source[start..self.pos];
+        if self.keywords.contains(ident_str) {
+            TokenType::Kw
+        } else if self.builtins.contains(ident_str) {
+            TokenType::BuiltinFun
+        } else if self.errors.contains(ident_str) {
+            TokenType::Error
+        } else if self.next_is('(') {
+            TokenType::Fun
+        } else {
+            TokenType::Ident
+        }
+    }
+
+    /// Create a new [`Token`] of type [`TokenType::Num`] (integer or real doesn't matter
+    /// because their color will be the very same). Follow the betty convention
+    /// of numbers, where a user can type an undefined number of underscores, because
+    /// they will be ignored by the lexer.
+    /// Hexadecimal (`0x`), binary (`0b`) and exponent (`1.5e-3`) notations are
+    /// recognized as well.
+    #[inline]
+    fn make_num(&mut self) -> TokenType {
+        let start = self.pos;
+
+        // Hexadecimal or binary prefix
+        if self.current_char == Some('0') {
+            if let Some(prefix @ ('x' | 'X' | 'b' | 'B')) = self.rest().chars().nth(1) {
+                self.advance(); // Skip '0'
+                self.advance(); // Skip the prefix
+                return self
+                    .make_radix_num(start, if matches!(prefix, 'x' | 'X') { 16 } else { 2 });
+            }
+        }
+
+        loop {
+            match self.current_char {
+                Some('0'..='9' | '.' | '_') => self.advance(),
+                // Exponent, only if followed by a digit or a signed digit
+                Some('e' | 'E') if self.exponent_follows() => {
+                    self.advance();
+                    if let Some('+' | '-') = self.current_char {
+                        self.advance();
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let num = &self.source[start..self.pos];
+        if num.replace('_', "").parse::<Int>().is_ok()
+            || num.replace('_', "").parse::<Float>().is_ok()
+        {
+            TokenType::Num
+        } else {
+            TokenType::Other
+        }
+    }
+
+    /// Continue a number starting at 'start', whose prefix (`0x` or `0b`) has already
+    /// been consumed, consuming every alphanumeric character so that invalid digits
+    /// end up in the same [`TokenType::Other`] token instead of being split.
+    #[inline]
+    fn make_radix_num(&mut self, start: usize, radix: u32) -> TokenType {
+        loop {
+            match self.current_char {
+                Some(ch) if ch.is_ascii_alphanumeric() || ch == '_' => {
+                    self.advance();
+                }
+                _ => break,
+            }
+        }
+
+        let digits = self.source[start + 2..self.pos].replace('_', "");
+        if !digits.is_empty() && Int::from_str_radix(&digits, radix).is_ok() {
+            TokenType::Num
+        } else {
+            TokenType::Other
+        }
+    }
+
+    /// Checks whether the characters after an 'e' or 'E' form a valid exponent,
+    /// i.e. a digit optionally preceded by a sign.
+    #[inline]
+    fn exponent_follows(&self) -> bool {
+        let mut rest = self.rest().chars().skip(1);
+        match rest.next() {
+            Some('0'..='9') => true,
+            Some('+' | '-') => matches!(rest.next(), Some('0'..='9')),
+            _ => false,
+        }
+    }
+
+    /// Create a new [`Token`] of type [`String`]. Loop as long as we dont't find the
+    /// closing 'delimiter' or EOF. In that case return the [`Token`].
+    #[inline]
+    fn make_str(&mut self, delimiter: char) -> TokenType {
+        self.advance(); // skip the delimiter, otherwise we would not enter the loop
+
+        loop {
+            match self.current_char {
+                Some(ch) if ch != delimiter => {
+                    self.advance();
+                }
+                Some(ch) if ch == delimiter => {
+                    self.advance();
+                    return TokenType::Str;
+                }
+                _ => return TokenType::Str,
+            }
+        }
+    }
+
+    /// Make a [`Token`] of type [`TokenType::Comment`]. It starts with 'marker' (the pipe
+    /// operator in betty), and are single line only. Therefore, we loop as long as we
+    /// don't find a newline or EOF.
+    #[inline]
+    fn make_comment(&mut self, marker: &str) -> TokenType {
+        for _ in marker.chars() {
+            self.advance(); // skip the marker
+        }
+
+        loop {
+            match self.current_char {
+                Some(ch) if ch != '\n' => {
+                    self.advance();
+                }
+                _ => return TokenType::Comment,
+            }
+        }
+    }
+
+    /// Make a [`Token`] of type [`TokenType::Comment`] out of a multi-line comment,
+    /// delimited by 'open' and 'close'. Newlines are part of the comment, so we loop
+    /// as long as we don't find the closing delimiter or EOF.
+    #[inline]
+    fn make_block_comment(&mut self, open: &str, close: &str) -> TokenType {
+        for _ in open.chars() {
+            self.advance(); // skip the opening delimiter
+        }
+
+        loop {
+            if self.starts_with(close) {
+                for _ in close.chars() {
+                    self.advance(); // skip the closing delimiter
+                }
+                return TokenType::Comment;
+            }
+            match self.current_char {
+                Some(_) => self.advance(),
+                None => return TokenType::Comment,
+            }
+        }
+    }
+
+    /// Make a [`Token`] of type [`TokenType::Op`] if the character and the next ones form
+    /// a multi-character operator, of type [`TokenType::Sym`] if the character is a valid
+    /// symbol, otherwise the type will be [`TokenType::Other`].
+    #[inline]
+    fn make_sym_or_other(&mut self, ch: char) -> TokenType {
+        if let Some(op) = self.operators.iter().find(|op| self.starts_with(op)) {
+            for _ in op.chars() {
+                self.advance(); // Skip the operator
+            }
+            return TokenType::Op;
+        }
+
+        let typ = if self.symbols.contains(&ch) {
+            TokenType::Sym
+        } else {
+            TokenType::Other
+        };
+
+        self.advance(); // Skip the character
+        typ
+    }
+
+    /// Main function, loop over all the characters and turn them into [`Token`]s, then
+    /// return them when there are no more characters, along with the [`Balance`] of
+    /// brackets and blocks. Each `make_*` function consumes the characters of a token
+    /// and returns its type, while its span and position are tracked here.
+    #[inline]
+    pub fn make_tokens(mut self) -> (Vec<Token>, Balance) {
+        let mut tokens = Vec::new();
+        let mut balance = Balance::default();
+        let config = self.config;
+
+        while let Some(ch) = self.current_char {
+            let (start, position) = (self.pos, self.position);
+
+            // The comment delimiters may start with any character, including the
+            // ones of other tokens, therefore they must be checked first. Block
+            // comments may start like line comments, so they come before them
+            let typ = match (config.block_delimiters(), config.line_marker()) {
+                (Some((open, close)), _) if self.starts_with(open) => {
+                    self.make_block_comment(open, close)
+                }
+                (_, Some(marker)) if self.starts_with(marker) => self.make_comment(marker),
+                _ if config.string_delimiters.contains(&ch) => self.make_str(ch),
+                _ => match ch {
+                    '0'..='9' => self.make_num(),
+                    _ if is_ident_start(ch) => self.make_ident(),
+                    _ => self.make_sym_or_other(ch),
+                },
+            };
+
+            // Each token holds at least a character, so that the loop always ends
+            if self.pos == start {
+                self.advance();
+            }
+
+            let token = Token(typ, start..self.pos, position);
+            balance.update(&token, self.source);
+            tokens.push(token);
+        }
+        (tokens, balance)
+    }
+}
+
+/// Identifiers follow the Unicode rules (`XID_Start`), and may also start with '_'
+#[inline]
+pub fn is_ident_start(ch: char) -> bool {
+    ch == '_' || ch.is_xid_start()
+}
+
+/// Characters that may follow the first one of an identifier (`XID_Continue`)
+#[inline]
+pub fn is_ident_continue(ch: char) -> bool {
+    ch.is_xid_continue()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn betty() -> Config {
+        Config {
+            name: "betty".into(),
+            betty: true,
+            line_comment: Some("|".into()),
+            block_comment: Some(("|*".into(), "*|".into())),
+            string_delimiters: vec!['"'],
+            ..Config::default()
+        }
+    }
+
+    /// The tokens of 'source' that are not whitespace, with their text
+    fn tokens<'a>(source: &'a str, config: &Config) -> Vec<(TokenType, &'a str)> {
+        let (tokens, _) = Highligher::new(source, config).make_tokens();
+        tokens
+            .into_iter()
+            .map(|Token(typ, span, _)| (typ, &source[span]))
+            .filter(|(_, text)| !text.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn numbers() {
+        let config = betty();
+        for num in [
+            "42", "1_000", "3.14", "1.5e-3", "2E+10", "0x1F", "0b1010", "0xff_ff",
+        ] {
+            assert_eq!(tokens(num, &config), [(TokenType::Num, num)], "{}", num);
+        }
+        // Invalid digits stay in the token of the number
+        assert_eq!(tokens("0x1G", &config), [(TokenType::Other, "0x1G")]);
+        assert_eq!(tokens("1.2.3", &config), [(TokenType::Other, "1.2.3")]);
+        // An 'e' without digits is not an exponent
+        assert_eq!(
+            tokens("2e", &config),
+            [(TokenType::Num, "2"), (TokenType::Ident, "e")]
+        );
+    }
+
+    #[test]
+    fn operators_and_symbols() {
+        let config = betty();
+        assert_eq!(
+            tokens("a >= b -> c == !d", &config),
+            [
+                (TokenType::Ident, "a"),
+                (TokenType::Op, ">="),
+                (TokenType::Ident, "b"),
+                (TokenType::Op, "->"),
+                (TokenType::Ident, "c"),
+                (TokenType::Op, "=="),
+                (TokenType::Sym, "!"),
+                (TokenType::Ident, "d"),
+            ]
+        );
+        // Single characters of the configuration are symbols, longer ones operators
+        let config = Config {
+            operators: vec!["@".into(), "..".into()],
+            ..betty()
+        };
+        assert_eq!(
+            tokens("@x..y", &config),
+            [
+                (TokenType::Sym, "@"),
+                (TokenType::Ident, "x"),
+                (TokenType::Op, ".."),
+                (TokenType::Ident, "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn balance_counts_brackets_and_blocks() {
+        let (_, balance) = Highligher::new("if (a[0] do\n", &betty()).make_tokens();
+        assert_eq!(balance.parens, 1);
+        assert_eq!(balance.brackets, 0);
+        assert_eq!(balance.blocks, 1);
+        let (_, balance) = Highligher::new("f(x[1]) do end", &betty()).make_tokens();
+        assert!(balance.is_balanced());
+    }
+
+    #[test]
+    fn block_comments_do_not_nest() {
+        // The first closing delimiter ends the comment
+        assert_eq!(
+            tokens("|* a |* b *| c *|", &betty()),
+            [
+                (TokenType::Comment, "|* a |* b *|"),
+                (TokenType::Ident, "c"),
+                (TokenType::Sym, "*"),
+                (TokenType::Comment, "|"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_continues_on_the_next_lines() {
+        let config = betty();
+        let (tokens, _, state) = Highligher::make_line_tokens("x |* a\n", LineState::Code, &config);
+        assert_eq!(tokens.last().map(|token| token.1.clone()), Some(2..7));
+        assert_eq!(state, LineState::BlockComment);
+
+        let (tokens, _, state) =
+            Highligher::make_line_tokens("still\n", LineState::BlockComment, &config);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].0, TokenType::Comment);
+        assert_eq!(tokens[0].1, 0..6);
+        assert_eq!(state, LineState::BlockComment);
+
+        let (tokens, _, state) =
+            Highligher::make_line_tokens("end *| y\n", LineState::BlockComment, &config);
+        assert_eq!(tokens[0].0, TokenType::Comment);
+        assert_eq!(tokens[0].1, 0..6);
+        assert_eq!(tokens[0].2.col, 0);
+        assert_eq!(state, LineState::Code);
+
+        // The whole text ends inside the comment
+        let (tokens, _) = Highligher::new("|* never closed", &config).make_tokens();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].0, TokenType::Comment);
+    }
+
+    #[test]
+    fn unterminated_string_continues_on_the_next_line() {
+        let config = betty();
+        let (_, _, state) = Highligher::make_line_tokens("s = \"abc\n", LineState::Code, &config);
+        assert_eq!(state, LineState::Str('"'));
+        let (tokens, _, state) =
+            Highligher::make_line_tokens("def\" + 1\n", LineState::Str('"'), &config);
+        assert_eq!(tokens[0].0, TokenType::Str);
+        assert_eq!(tokens[0].1, 0..4);
+        assert_eq!(state, LineState::Code);
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let config = betty();
+        assert_eq!(
+            tokens("città = _naïve + Δx", &config),
+            [
+                (TokenType::Ident, "città"),
+                (TokenType::Sym, "="),
+                (TokenType::Ident, "_naïve"),
+                (TokenType::Sym, "+"),
+                (TokenType::Ident, "Δx"),
+            ]
+        );
+        // Positions count characters, not bytes
+        let (tokens, _) = Highligher::new("é = ü", &config).make_tokens();
+        let last = tokens.last().unwrap();
+        assert_eq!(last.1, 5..7);
+        assert_eq!(last.2.col, 4);
+    }
+
+    #[test]
+    fn keywords_builtins_and_functions() {
+        assert_eq!(
+            tokens("fun f() do print(ValueError) end", &betty()),
+            [
+                (TokenType::Kw, "fun"),
+                (TokenType::Fun, "f"),
+                (TokenType::Other, "("),
+                (TokenType::Other, ")"),
+                (TokenType::Kw, "do"),
+                (TokenType::BuiltinFun, "print"),
+                (TokenType::Other, "("),
+                (TokenType::Error, "ValueError"),
+                (TokenType::Other, ")"),
+                (TokenType::Kw, "end"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_comment_delimiters_are_ignored() {
+        let config = Config {
+            line_comment: Some(String::new()),
+            block_comment: Some((String::new(), String::new())),
+            ..betty()
+        };
+        let source = "x = 1\ny = \"a\"\n";
+        let (tokens, _) = Highligher::new(source, &config).make_tokens();
+        assert!(tokens.iter().all(|token| token.0 != TokenType::Comment));
+        assert_eq!(tokens.last().map(|token| token.1.end), Some(source.len()));
+
+        let (tokens, _, state) =
+            Highligher::make_line_tokens("x = 1\n", LineState::BlockComment, &config);
+        assert_eq!(tokens.first().map(|token| token.0), Some(TokenType::Ident));
+        assert_eq!(state, LineState::Code);
+    }
+
+    #[test]
+    fn one_empty_block_delimiter_disables_block_comments() {
+        let config = Config {
+            block_comment: Some(("|*".into(), String::new())),
+            ..betty()
+        };
+        assert_eq!(config.block_delimiters(), None);
+        let (tokens, _) = Highligher::new("|* x\ny", &config).make_tokens();
+        // The line comment still works, and ends at the newline
+        assert_eq!(tokens[0].0, TokenType::Comment);
+        assert_eq!(tokens[0].1, 0..4);
+    }
+}
@@ -0,0 +1,199 @@
+//! Nesting of the blocks of betty code (`fun ... do ... end`, `if ... do ... else ...
+//! end` and so on), computed once from the [`Token`]s of the [`Highligher`] and
+//! shared by everything that depends on it: folding, indentation and the headers of
+//! the blocks that contain a line.
+//!
+//! [`Highligher`]: super::Highligher
+
+use super::{Position, Token, TokenType};
+
+/// The statement that opens a [`Block`]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    /// `fun name(...) do`, or an anonymous function
+    Fun,
+    /// `if ... do`
+    If,
+    /// `else`, which closes the `if` block before it
+    Else,
+    /// `while ... do`
+    While,
+    /// `for ... do`
+    For,
+    /// `foreach ... do`
+    Foreach,
+    /// `try do`
+    Try,
+    /// `catch ... do`, which closes the `try` block before it
+    Catch,
+    /// `match ... do`
+    Match,
+    /// A bare `do`
+    Do,
+}
+
+impl BlockKind {
+    /// The kind of the block opened by 'keyword', if any
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "fun" => Some(Self::Fun),
+            "if" => Some(Self::If),
+            "while" => Some(Self::While),
+            "for" => Some(Self::For),
+            "foreach" => Some(Self::Foreach),
+            "try" => Some(Self::Try),
+            "catch" => Some(Self::Catch),
+            "match" => Some(Self::Match),
+            _ => None,
+        }
+    }
+}
+
+/// A block of code and the blocks nested in it
+#[derive(Clone)]
+pub struct Block {
+    /// Statement that opens the block
+    pub kind: BlockKind,
+    /// Position of the keyword that opens the block
+    pub start: Position,
+    /// Position of the keyword that closes the block (`end`, or `else`/`catch` for the
+    /// block before them), or [`None`] if the block is never closed
+    pub end: Option<Position>,
+    /// Blocks nested in this one, in order
+    pub children: Vec<Block>,
+}
+
+impl Block {
+    /// Does the block contain 'line', header and closing keyword included?
+    pub fn contains_line(&self, line: usize) -> bool {
+        self.start.line <= line && self.end.map_or(true, |end| line <= end.line)
+    }
+
+    /// Is the block on more than one line, i.e. can it be folded?
+    pub fn is_multiline(&self) -> bool {
+        self.end.map_or(true, |end| end.line > self.start.line)
+    }
+}
+
+/// The blocks of some code
+#[derive(Clone, Default)]
+pub struct Structure {
+    /// The outermost blocks, in order
+    pub blocks: Vec<Block>,
+}
+
+impl Structure {
+    /// Find the blocks of the code, whose [`Token`]s are 'tokens'. Blocks that are
+    /// never closed end with the code.
+    pub fn new(tokens: &[Token], source: &str) -> Self {
+        // Blocks that are still open, the innermost last
+        let mut open: Vec<Block> = Vec::new();
+        let mut blocks = Vec::new();
+        let mut header: Option<(BlockKind, Position)> = None; // waiting for its 'do'
+        let mut else_do = false; // is the keyword right after an 'else'?
+
+        // Close the innermost block at 'end', moving it into its parent
+        let close = |open: &mut Vec<Block>, blocks: &mut Vec<Block>, end: Option<Position>| {
+            if let Some(mut block) = open.pop() {
+                block.end = end;
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(block),
+                    None => blocks.push(block),
+                }
+            }
+        };
+
+        for Token(typ, span, position) in tokens {
+            if *typ != TokenType::Kw {
+                continue;
+            }
+            match &source[span.clone()] {
+                // The block of 'else' is already open
+                "do" if else_do => (),
+                "do" => {
+                    let (kind, start) = header.take().unwrap_or((BlockKind::Do, *position));
+                    open.push(Block {
+                        kind,
+                        start,
+                        end: None,
+                        children: Vec::new(),
+                    });
+                }
+                "end" => {
+                    header = None;
+                    close(&mut open, &mut blocks, Some(*position));
+                }
+                // 'else' closes the 'if' block and opens its own, with or without 'do'
+                "else" => {
+                    if let Some(BlockKind::If) = open.last().map(|block| block.kind) {
+                        close(&mut open, &mut blocks, Some(*position));
+                    }
+                    open.push(Block {
+                        kind: BlockKind::Else,
+                        start: *position,
+                        end: None,
+                        children: Vec::new(),
+                    });
+                    header = None;
+                    else_do = true;
+                    continue;
+                }
+                // 'catch' closes the 'try' block, and opens its own at its 'do'
+                "catch" => {
+                    if let Some(BlockKind::Try) = open.last().map(|block| block.kind) {
+                        close(&mut open, &mut blocks, Some(*position));
+                    }
+                    header = Some((BlockKind::Catch, *position));
+                }
+                keyword => {
+                    if let Some(kind) = BlockKind::from_keyword(keyword) {
+                        header = Some((kind, *position));
+                    }
+                }
+            }
+            else_do = false;
+        }
+
+        while !open.is_empty() {
+            close(&mut open, &mut blocks, None);
+        }
+        Self { blocks }
+    }
+
+    /// The blocks that contain 'line', the outermost first. Their headers are the
+    /// context of the line.
+    pub fn enclosing(&self, line: usize) -> Vec<&Block> {
+        let mut enclosing = Vec::new();
+        let mut blocks = &self.blocks;
+        while let Some(block) = blocks.iter().find(|block| block.contains_line(line)) {
+            enclosing.push(block);
+            blocks = &block.children;
+        }
+        enclosing
+    }
+
+    /// Indentation level of 'line', i.e. the number of blocks it is inside of. The
+    /// lines that open or close a block are not inside of it.
+    pub fn depth_at(&self, line: usize) -> usize {
+        self.enclosing(line)
+            .into_iter()
+            .filter(|block| {
+                block.start.line < line && block.end.map_or(true, |end| line < end.line)
+            })
+            .count()
+    }
+
+    /// The blocks that can be folded, as pairs of first and last line, in order
+    pub fn folds(&self) -> Vec<(usize, Option<usize>)> {
+        fn visit(blocks: &[Block], folds: &mut Vec<(usize, Option<usize>)>) {
+            for block in blocks.iter().filter(|block| block.is_multiline()) {
+                folds.push((block.start.line, block.end.map(|end| end.line)));
+                visit(&block.children, folds);
+            }
+        }
+
+        let mut folds = Vec::new();
+        visit(&self.blocks, &mut folds);
+        folds
+    }
+}
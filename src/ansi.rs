@@ -0,0 +1,230 @@
+//! ANSI escape sequences in the output of the programs. The color and style
+//! sequences (SGR, `ESC [ ... m`) become colored spans of the console, and the other
+//! sequences, such as cursor movements, are removed.
+
+use std::ops::Range;
+
+use eframe::egui;
+use egui::text::{LayoutJob, TextFormat};
+use egui::Color32;
+
+const ESC: char = '\x1b';
+
+/// The 16 standard colors, as in the terminal of VS Code: black, red, green, yellow,
+/// blue, magenta, cyan and white, then their bright variants
+const PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 49, 49],
+    [13, 188, 121],
+    [229, 229, 16],
+    [36, 114, 200],
+    [188, 63, 188],
+    [17, 168, 205],
+    [229, 229, 229],
+    [102, 102, 102],
+    [241, 76, 76],
+    [35, 209, 139],
+    [245, 245, 67],
+    [59, 142, 234],
+    [214, 112, 214],
+    [41, 184, 219],
+    [255, 255, 255],
+];
+
+/// Style set by the SGR sequences
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Style {
+    pub foreground: Option<Color32>,
+    pub background: Option<Color32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// Text of the output with a style other than the default one
+pub struct Span {
+    pub range: Range<usize>, // in bytes of the text without escape sequences
+    pub style: Style,
+}
+
+/// Return 'text' without its escape sequences, and the styled spans they made
+pub fn parse(text: &str) -> (String, Vec<Span>) {
+    let mut plain = String::with_capacity(text.len());
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut span_start = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != ESC {
+            plain.push(ch);
+            continue;
+        }
+        match chars.next() {
+            // Control Sequence Introducer: parameters, then a final byte
+            Some('[') => {
+                let mut params = String::new();
+                let mut last = None;
+                for ch in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&ch) {
+                        last = Some(ch);
+                        break;
+                    }
+                    params.push(ch);
+                }
+                if last != Some('m') {
+                    continue;
+                }
+                let new_style = apply_sgr(style, &params);
+                if new_style != style {
+                    if style != Style::default() && plain.len() > span_start {
+                        spans.push(Span {
+                            range: span_start..plain.len(),
+                            style,
+                        });
+                    }
+                    span_start = plain.len();
+                    style = new_style;
+                }
+            }
+            // Operating System Command (e.g. the window title), ended by BEL or ESC \
+            Some(']') => {
+                while let Some(ch) = chars.next() {
+                    if ch == '\x07' {
+                        break;
+                    }
+                    if ch == ESC && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Other sequences have a single character after ESC
+            _ => (),
+        }
+    }
+
+    if style != Style::default() && plain.len() > span_start {
+        spans.push(Span {
+            range: span_start..plain.len(),
+            style,
+        });
+    }
+    (plain, spans)
+}
+
+/// Return 'style' changed by the SGR parameters 'params', e.g. `1;31`
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    let mut codes = params
+        .split(';')
+        .map(|code| code.parse::<u8>().unwrap_or(0));
+
+    while let Some(code) = codes.next() {
+        match code {
+            0 => style = Style::default(),
+            1 => style.bold = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            9 => style.strikethrough = true,
+            22 => style.bold = false,
+            23 => style.italic = false,
+            24 => style.underline = false,
+            29 => style.strikethrough = false,
+            30..=37 => style.foreground = Some(palette(code - 30)),
+            38 => style.foreground = extended_color(&mut codes),
+            39 => style.foreground = None,
+            40..=47 => style.background = Some(palette(code - 40)),
+            48 => style.background = extended_color(&mut codes),
+            49 => style.background = None,
+            90..=97 => style.foreground = Some(palette(code - 90 + 8)),
+            100..=107 => style.background = Some(palette(code - 100 + 8)),
+            _ => (),
+        }
+    }
+    style
+}
+
+/// The color of a `38` or `48` SGR parameter: `5;n` for one of the 256 colors of
+/// xterm, or `2;r;g;b`
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color32> {
+    match codes.next()? {
+        5 => {
+            let index = codes.next()?;
+            Some(match index {
+                0..=15 => palette(index),
+                // 6x6x6 color cube
+                16..=231 => {
+                    let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+                    let index = index - 16;
+                    Color32::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+                }
+                // Grayscale ramp
+                _ => {
+                    let gray = 8 + (index - 232) * 10;
+                    Color32::from_gray(gray)
+                }
+            })
+        }
+        2 => Some(Color32::from_rgb(
+            codes.next()?,
+            codes.next()?,
+            codes.next()?,
+        )),
+        _ => None,
+    }
+}
+
+fn palette(index: u8) -> Color32 {
+    let [r, g, b] = PALETTE[index as usize];
+    Color32::from_rgb(r, g, b)
+}
+
+/// Lay out 'text' (without escape sequences) with the styles of 'spans', and
+/// 'color' where no color was set. Bold text without a color is in 'strong_color'.
+pub fn layout_job(
+    text: &str,
+    spans: &[Span],
+    font_id: egui::FontId,
+    color: Color32,
+    strong_color: Color32,
+) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let plain = TextFormat::simple(font_id.clone(), color);
+    let mut pos = 0;
+
+    for span in spans {
+        if span.range.start > pos {
+            job.append(&text[pos..span.range.start], 0.0, plain.clone());
+        }
+        let style = span.style;
+        let color = match style.foreground {
+            Some(foreground) => foreground,
+            None if style.bold => strong_color,
+            None => color,
+        };
+        let format = TextFormat {
+            font_id: font_id.clone(),
+            color,
+            background: style.background.unwrap_or(Color32::TRANSPARENT),
+            italics: style.italic,
+            underline: if style.underline {
+                egui::Stroke::new(1.0, color)
+            } else {
+                egui::Stroke::NONE
+            },
+            strikethrough: if style.strikethrough {
+                egui::Stroke::new(1.0, color)
+            } else {
+                egui::Stroke::NONE
+            },
+            ..TextFormat::default()
+        };
+        job.append(&text[span.range.clone()], 0.0, format);
+        pos = span.range.end;
+    }
+    if pos < text.len() {
+        job.append(&text[pos..], 0.0, plain);
+    }
+    job
+}
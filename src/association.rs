@@ -0,0 +1,67 @@
+//! Association of the betty files with the IDE on Windows, so that they open in it with
+//! a double-click, along with an "Open with Colors" entry in the menu of every file.
+//! The keys are written for the current user only, with `reg`, so no administrator
+//! rights are needed.
+
+use std::env;
+use std::io;
+use std::process::{Command, Stdio};
+
+use super::runner;
+
+/// Class of the betty files, to which the extension points
+const PROG_ID: &str = r"HKCU\Software\Classes\Colors.betty";
+
+/// Extension of the betty files
+const EXTENSION: &str = r"HKCU\Software\Classes\.betty";
+
+/// Entry of the menu shown when right-clicking any file
+const OPEN_WITH: &str = r"HKCU\Software\Classes\*\shell\Colors";
+
+/// Open the betty files with this executable
+pub fn register() -> io::Result<()> {
+    let exe = env::current_exe()?;
+    let open = format!("\"{}\" \"%1\"", exe.display());
+    let icon = format!("\"{}\",0", exe.display());
+
+    let icon_key = format!(r"{}\DefaultIcon", PROG_ID);
+    let open_key = format!(r"{}\shell\open\command", PROG_ID);
+    let open_with_key = format!(r"{}\command", OPEN_WITH);
+
+    reg(&["add", EXTENSION, "/ve", "/d", "Colors.betty", "/f"])?;
+    reg(&["add", PROG_ID, "/ve", "/d", "betty file", "/f"])?;
+    reg(&["add", &icon_key, "/ve", "/d", &icon, "/f"])?;
+    reg(&["add", &open_key, "/ve", "/d", &open, "/f"])?;
+    reg(&["add", OPEN_WITH, "/ve", "/d", "Open with Colors", "/f"])?;
+    reg(&["add", OPEN_WITH, "/v", "Icon", "/d", &icon, "/f"])?;
+    reg(&["add", &open_with_key, "/ve", "/d", &open, "/f"])
+}
+
+/// Remove the keys written by [`register`]
+pub fn unregister() -> io::Result<()> {
+    for key in [OPEN_WITH, PROG_ID, EXTENSION] {
+        // Deleting a key that does not exist fails
+        if reg(&["query", key]).is_ok() {
+            reg(&["delete", key, "/f"])?;
+        }
+    }
+    Ok(())
+}
+
+/// Run `reg` with 'args', failing if it does
+fn reg(args: &[&str]) -> io::Result<()> {
+    if !cfg!(windows) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "files can only be associated on Windows",
+        ));
+    }
+    let mut command = Command::new("reg");
+    command.args(args).stdin(Stdio::null());
+    let output = runner::without_window(&mut command).output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(io::Error::new(io::ErrorKind::Other, message))
+}
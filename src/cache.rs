@@ -0,0 +1,401 @@
+//! Cache of the highlighted lines of code. Running the [`Highligher`] over the whole
+//! text every frame is expensive, so every line is analysed once and its
+//! [`egui::text::LayoutJob`] is reused as long as the line does not change. The job
+//! of the whole text is kept too, so that nothing is done while the text stays the same.
+//! Large texts are analysed in a worker thread, and meanwhile the editor shows them
+//! with the colors of the lines already known. Only the lines around the ones visible
+//! in the editor are colored, which keeps the job small in long files.
+
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use colors_highlight::{Balance, Config, Highligher, LineState, Position, Token, TokenType};
+use eframe::egui;
+
+use super::parser::{self, Diagnostic};
+use super::semantic;
+use super::todo::{self, Todo};
+
+/// All the lines of a text, to color them all
+pub const ALL_LINES: Range<usize> = 0..usize::MAX;
+
+/// Texts from this size, in bytes, are analysed in the background by the editor
+const BACKGROUND_MIN_BYTES: usize = 256 * 1024;
+
+/// A line is identified by its text and by the state in which the previous line
+/// ended, as the same text is colored differently inside a string.
+type LineKey = (Arc<str>, LineState);
+
+/// The result of the analysis of a single line, which does not depend on the colors
+struct LineTokens {
+    tokens: Vec<Token>,
+    balance: Balance,
+    end_state: LineState,
+}
+
+/// What is known about the whole code, besides its colors
+#[derive(Default, Clone, PartialEq)]
+pub struct Analysis {
+    pub balance: Balance,
+    pub diagnostics: Vec<Diagnostic>, // only for betty
+    pub todos: Vec<Todo>,
+    pub builtins: Vec<Range<usize>>, // bytes of the calls of builtin functions
+}
+
+/// The analysis of a whole text, line by line
+struct Analysed {
+    lines: Vec<(LineKey, Arc<LineTokens>)>,
+    marked: Vec<Option<Vec<Token>>>, // tokens of the lines with undefined names marked
+    analysis: Analysis,
+}
+
+/// A text being analysed in the background
+struct Worker {
+    text: Arc<str>,
+    receiver: Receiver<Analysed>,
+}
+
+#[derive(Default)]
+pub struct HighlightCache {
+    lines: HashMap<LineKey, Arc<LineTokens>>,
+    jobs: HashMap<LineKey, egui::text::LayoutJob>,
+
+    /// Name of the language of the cached lines
+    language: String,
+
+    /// Job and analysis of the last text laid out, which is the text of the job. The
+    /// colors and the font size are not checked, as the cache is cleared when they change.
+    job: egui::text::LayoutJob,
+    analysis: Analysis,
+
+    /// Lines of the last text analysed, with their tokens marked if they have names
+    /// that are never defined
+    order: Vec<LineKey>,
+    marked: Vec<Option<Vec<Token>>>,
+
+    /// Lines colored in the job, from 0
+    shown: Range<usize>,
+
+    /// Whether the job and the analysis are the ones of an older text
+    stale: bool,
+    worker: Option<Worker>,
+}
+
+impl HighlightCache {
+    /// Build the [`egui::text::LayoutJob`] of the whole 'text', unless it is the same text
+    /// as the last time, analysing only the lines that are not in the cache.
+    /// 'make_job' turns a line and its [`Token`]s into its job. Only the lines in 'shown'
+    /// (from 0) are colored, and the others are plain text.
+    /// Lines that are no longer in the text are dropped from the cache.
+    /// In betty code, the lines referring to names that are never defined are built
+    /// again with those names marked, as they depend on the rest of the code, and the
+    /// whole code is checked by the [`parser`].
+    pub fn layout(
+        &mut self,
+        text: &str,
+        config: &Config,
+        shown: Range<usize>,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> (egui::text::LayoutJob, Analysis) {
+        self.set_language(config);
+        if self.is_current(text) {
+            if shown != self.shown {
+                self.job = self.colored_job(text, &shown, &make_job);
+                self.shown = shown;
+            }
+            return (self.job.clone(), self.analysis.clone());
+        }
+        let analysed = analyse(text, config, &self.lines);
+        self.finish(text, analysed, shown, &make_job)
+    }
+
+    /// Like [`HighlightCache::layout`], but a large 'text' is analysed in a worker thread.
+    /// Until it is done, the lines already known keep their colors, the others are
+    /// plain, and the analysis is the one of the last text.
+    pub fn layout_in_background(
+        &mut self,
+        text: &str,
+        config: &Config,
+        shown: Range<usize>,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> (egui::text::LayoutJob, Analysis) {
+        if text.len() < BACKGROUND_MIN_BYTES {
+            self.worker = None;
+            return self.layout(text, config, shown, make_job);
+        }
+        self.set_language(config);
+
+        let finished = self.worker.as_ref().and_then(|worker| {
+            match worker.receiver.try_recv() {
+                Ok(analysed) => Some(Some((worker.text.clone(), analysed))),
+                Err(TryRecvError::Empty) => None,
+                // Started again below
+                Err(TryRecvError::Disconnected) => Some(None),
+            }
+        });
+        if let Some(finished) = finished {
+            self.worker = None;
+            if let Some((worker_text, analysed)) = finished {
+                if *worker_text == *text {
+                    return self.finish(text, analysed, shown, &make_job);
+                }
+                // The text changed meanwhile, but most of its lines are known now, so
+                // the job is built again below
+                self.lines.extend(analysed.lines);
+                self.job = egui::text::LayoutJob::default();
+            }
+        }
+
+        if self.is_current(text) {
+            if shown != self.shown {
+                self.job = self.colored_job(text, &shown, &make_job);
+                self.shown = shown;
+            }
+        } else if self.job.text != text || shown != self.shown {
+            self.job = self.stale_job(text, &shown, &make_job);
+            self.shown = shown;
+            self.stale = true;
+        }
+        if self.stale && self.worker.is_none() {
+            let (sender, receiver) = mpsc::channel();
+            let text: Arc<str> = text.into();
+            let (worker_text, config, lines) = (text.clone(), config.clone(), self.lines.clone());
+            thread::spawn(move || {
+                let _ = sender.send(analyse(&worker_text, &config, &lines));
+            });
+            self.worker = Some(Worker { text, receiver });
+        }
+        (self.job.clone(), self.analysis.clone())
+    }
+
+    /// Whether a text is being analysed in the background
+    pub fn is_pending(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// The same line is highlighted differently in another language
+    fn set_language(&mut self, config: &Config) {
+        if self.language != config.name {
+            *self = Self {
+                language: config.name.clone(),
+                ..Self::default()
+            };
+        }
+    }
+
+    /// Whether the job and the analysis kept are the ones of 'text'
+    fn is_current(&self, text: &str) -> bool {
+        !self.stale && self.job.text == text
+    }
+
+    /// Keep the analysis of 'text', and build its job
+    fn finish(
+        &mut self,
+        text: &str,
+        analysed: Analysed,
+        shown: Range<usize>,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> (egui::text::LayoutJob, Analysis) {
+        let mut old_jobs = mem::take(&mut self.jobs);
+        self.lines.clear();
+        self.order.clear();
+        for (line_key, tokens) in analysed.lines {
+            if let Some(line_job) = old_jobs.remove(&line_key) {
+                self.jobs.insert(line_key.clone(), line_job);
+            }
+            self.lines.insert(line_key.clone(), tokens);
+            self.order.push(line_key);
+        }
+        self.marked = analysed.marked;
+
+        self.job = self.colored_job(text, &shown, &make_job);
+        self.shown = shown;
+        self.analysis = analysed.analysis;
+        self.stale = false;
+        (self.job.clone(), self.analysis.clone())
+    }
+
+    /// The job of 'text', as analysed, with the lines in 'shown' colored
+    fn colored_job(
+        &mut self,
+        text: &str,
+        shown: &Range<usize>,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob::default();
+        let mut plain = 0..0;
+        for (idx, line) in text.split_inclusive('\n').enumerate() {
+            if !shown.contains(&idx) {
+                plain.end += line.len();
+                continue;
+            }
+            append_plain(&mut job, &text[plain.clone()], &make_job);
+            plain = plain.end + line.len()..plain.end + line.len();
+
+            let key = self.order[idx].clone();
+            let marked = self.marked[idx]
+                .as_ref()
+                .map(|tokens| make_job(line, tokens.clone()));
+            let tokens = &self.lines[&key].tokens;
+            let line_job = self
+                .jobs
+                .entry(key)
+                .or_insert_with(|| make_job(line, tokens.clone()));
+            append_job(&mut job, marked.as_ref().unwrap_or(line_job));
+        }
+        append_plain(&mut job, &text[plain], &make_job);
+        job
+    }
+
+    /// The job of 'text' with the lines in 'shown' colored if they are in the cache
+    fn stale_job(
+        &mut self,
+        text: &str,
+        shown: &Range<usize>,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob::default();
+        let mut plain = 0..0;
+        let mut state = LineState::default();
+        for (idx, line) in text.split_inclusive('\n').enumerate() {
+            let key = (Arc::from(line), state);
+            let Some(tokens) = self.lines.get(&key) else {
+                plain.end += line.len();
+                continue;
+            };
+            state = tokens.end_state;
+            if !shown.contains(&idx) {
+                plain.end += line.len();
+                continue;
+            }
+            append_plain(&mut job, &text[plain.clone()], &make_job);
+            plain = plain.end + line.len()..plain.end + line.len();
+
+            let line_job = self
+                .jobs
+                .entry(key)
+                .or_insert_with(|| make_job(line, tokens.tokens.clone()));
+            append_job(&mut job, line_job);
+        }
+        append_plain(&mut job, &text[plain], &make_job);
+        job
+    }
+}
+
+/// Analyse the lines of 'text', except the 'known' ones, and then the whole code
+fn analyse(text: &str, config: &Config, known: &HashMap<LineKey, Arc<LineTokens>>) -> Analysed {
+    let mut found: HashMap<LineKey, Arc<LineTokens>> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut state = LineState::default();
+    for line in text.split_inclusive('\n') {
+        let key: LineKey = (line.into(), state);
+        let tokens = found
+            .entry(key.clone())
+            .or_insert_with(|| {
+                known.get(&key).cloned().unwrap_or_else(|| {
+                    let (tokens, balance, end_state) =
+                        Highligher::make_line_tokens(line, state, config);
+                    Arc::new(LineTokens {
+                        tokens,
+                        balance,
+                        end_state,
+                    })
+                })
+            })
+            .clone();
+        state = tokens.end_state;
+        lines.push((key, tokens));
+    }
+
+    let texts: Vec<&str> = text.split_inclusive('\n').collect();
+    let defined =
+        semantic::defined_names(texts.iter().zip(&lines).flat_map(|(line, (_, tokens))| {
+            tokens
+                .tokens
+                .iter()
+                .map(|Token(typ, span, _)| (&line[span.clone()], *typ))
+        }));
+    let marked = texts
+        .iter()
+        .zip(&lines)
+        .map(|(line, (_, tokens))| {
+            config
+                .betty
+                .then(|| semantic::mark_undefined(line, &tokens.tokens, &defined))
+                .flatten()
+        })
+        .collect();
+
+    // Move the tokens of each line to their place in the text
+    let mut balance = Balance::default();
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    for (line_number, (line, (_, line_tokens))) in texts.iter().zip(&lines).enumerate() {
+        tokens.extend(line_tokens.tokens.iter().map(|Token(typ, span, position)| {
+            let position = Position {
+                line: line_number,
+                col: position.col,
+            };
+            Token(*typ, span.start + offset..span.end + offset, position)
+        }));
+        balance += line_tokens.balance;
+        offset += line.len();
+    }
+
+    let mut diagnostics = Vec::new();
+    if config.betty {
+        diagnostics = parser::check(&tokens, text);
+        diagnostics.extend(semantic::unused_variables(&tokens, text));
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    }
+    let todos = tokens
+        .iter()
+        .filter(|Token(typ, ..)| *typ == TokenType::Comment)
+        .flat_map(|Token(_, span, position)| {
+            todo::find_todos(&text[span.clone()], span.start, *position)
+        })
+        .collect();
+    let builtins = tokens
+        .iter()
+        .filter(|Token(typ, ..)| *typ == TokenType::BuiltinFun)
+        .map(|Token(_, span, _)| span.clone())
+        .collect();
+
+    Analysed {
+        lines,
+        marked,
+        analysis: Analysis {
+            balance,
+            diagnostics,
+            todos,
+            builtins,
+        },
+    }
+}
+
+/// Add the job of a line at the end of 'job'
+fn append_job(job: &mut egui::text::LayoutJob, line_job: &egui::text::LayoutJob) {
+    let offset = job.text.len();
+    job.text.push_str(&line_job.text);
+    job.sections
+        .extend(line_job.sections.iter().cloned().map(|mut section| {
+            section.byte_range = section.byte_range.start + offset..section.byte_range.end + offset;
+            section
+        }));
+}
+
+/// Add 'text' at the end of 'job', without colors
+fn append_plain(
+    job: &mut egui::text::LayoutJob,
+    text: &str,
+    make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+) {
+    if !text.is_empty() {
+        let tokens = vec![Token(TokenType::Other, 0..text.len(), Position::default())];
+        append_job(job, &make_job(text, tokens));
+    }
+}
@@ -0,0 +1,389 @@
+//! Debugging of the programs with a debug adapter of the Debug Adapter Protocol, started
+//! with the `debugger` command of the file type of the open file and spoken to over its
+//! stdin and stdout. The adapter runs the program, stops it at the breakpoints of the
+//! open file, and tells where it stopped and the variables there.
+
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::Receiver;
+
+use eframe::egui;
+use serde_json::{json, Value};
+
+use super::log;
+use super::lsp;
+use super::runner;
+
+/// Name of the debug adapters, as told to them
+const ADAPTER_ID: &str = "betty";
+
+/// Where the program is
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Starting,
+    Running,
+    Stopped,
+    Ended,
+}
+
+/// The variables of a scope (e.g. the locals) of the line where the program stopped
+pub struct Scope {
+    pub name: String,
+    pub variables: Vec<(String, String)>, // names and values
+}
+
+/// Requests waiting for their response, to know what the response holds
+enum Request {
+    Initialize,
+    Launch,
+    StackTrace,
+    Scopes,
+    Variables(usize), // index of the scope
+    Other,
+}
+
+pub struct Debugger {
+    child: Option<Child>, // taken when the debugger is dropped
+    stdin: ChildStdin,
+
+    /// Messages of the adapter, read in the background
+    messages: Receiver<Value>,
+
+    /// Requests sent, by sequence number
+    pending: HashMap<u64, Request>,
+    next_seq: u64,
+
+    /// The program debugged, its arguments and its working directory
+    program: PathBuf,
+    args: Vec<String>,
+    cwd: PathBuf,
+
+    /// Lines of the breakpoints in the program, from 1
+    breakpoints: Vec<usize>,
+
+    state: State,
+
+    /// Thread stopped, and the file and line (from 1) where it stopped
+    thread_id: Option<i64>,
+    location: Option<(PathBuf, usize)>,
+
+    scopes: Vec<Scope>,
+
+    /// Output of the program not shown yet
+    output: String,
+}
+
+impl Debugger {
+    /// Debug 'program' with the arguments 'args', in the folder 'cwd', with the debug
+    /// adapter started with the command line 'command'. 'ctx' is repainted when the
+    /// adapter sends something.
+    pub fn start(
+        command: &str,
+        program: &Path,
+        args: Vec<String>,
+        cwd: &Path,
+        breakpoints: Vec<usize>,
+        ctx: egui::Context,
+    ) -> io::Result<Self> {
+        let command = runner::split_args(command);
+        let Some((adapter, adapter_args)) = command.split_first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the command of the debugger is empty",
+            ));
+        };
+        let mut child = Command::new(adapter)
+            .args(adapter_args)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let mut debugger = Self {
+            child: Some(child),
+            stdin,
+            messages: lsp::read_in_background(stdout, ctx),
+            pending: HashMap::new(),
+            next_seq: 1,
+            program: program.to_path_buf(),
+            args,
+            cwd: cwd.to_path_buf(),
+            breakpoints,
+            state: State::Starting,
+            thread_id: None,
+            location: None,
+            scopes: Vec::new(),
+            output: String::new(),
+        };
+        debugger.request(
+            Request::Initialize,
+            "initialize",
+            json!({
+                "clientID": "colors",
+                "clientName": "Colors",
+                "adapterID": ADAPTER_ID,
+                "linesStartAt1": true,
+                "columnsStartAt1": true,
+                "pathFormat": "path",
+            }),
+        )?;
+        Ok(debugger)
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The file and line (from 1) where the program stopped, if it did
+    pub fn location(&self) -> Option<(&Path, usize)> {
+        let (path, line) = self.location.as_ref()?;
+        Some((path, *line))
+    }
+
+    /// The variables where the program stopped
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    /// Stop the program at the lines 'breakpoints' (from 1) from now on
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<usize>) -> io::Result<()> {
+        self.breakpoints = breakpoints;
+        if self.state == State::Starting {
+            // They are sent once the adapter is ready
+            return Ok(());
+        }
+        self.send_breakpoints()
+    }
+
+    /// Run the program until the next breakpoint
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.step("continue")
+    }
+
+    /// Run the program to the next line
+    pub fn step_over(&mut self) -> io::Result<()> {
+        self.step("next")
+    }
+
+    /// Run the program into the function called at the line
+    pub fn step_into(&mut self) -> io::Result<()> {
+        self.step("stepIn")
+    }
+
+    /// Run the program out of the function
+    pub fn step_out(&mut self) -> io::Result<()> {
+        self.step("stepOut")
+    }
+
+    fn step(&mut self, command: &str) -> io::Result<()> {
+        let Some(thread_id) = self.thread_id.filter(|_| self.state == State::Stopped) else {
+            return Ok(());
+        };
+        self.state = State::Running;
+        self.location = None;
+        self.scopes.clear();
+        self.request(Request::Other, command, json!({ "threadId": thread_id }))
+    }
+
+    /// Handle what the adapter sent since the last call, and return the output of the
+    /// program meanwhile
+    pub fn poll(&mut self) -> String {
+        while let Ok(message) = self.messages.try_recv() {
+            if let Err(err) = self.handle(message) {
+                log::warning!(format!("The debugger could not be told: {}", err));
+            }
+        }
+        if self.state != State::Ended
+            && matches!(self.child.as_mut().map(Child::try_wait), Some(Ok(Some(_))))
+        {
+            self.state = State::Ended;
+        }
+        mem::take(&mut self.output)
+    }
+
+    fn handle(&mut self, message: Value) -> io::Result<()> {
+        let body = &message["body"];
+        match message["type"].as_str() {
+            Some("response") => {
+                let request = message["request_seq"]
+                    .as_u64()
+                    .and_then(|seq| self.pending.remove(&seq));
+                if message["success"] == false {
+                    let error = message["message"].as_str().unwrap_or_default();
+                    log::warning!(format!("The debugger failed: {}", error));
+                    if matches!(request, Some(Request::Initialize | Request::Launch)) {
+                        self.output
+                            .push_str(&format!("The debugger failed: {}\n", error));
+                        self.state = State::Ended;
+                    }
+                    return Ok(());
+                }
+                // What the program was asked about may have changed since it stopped
+                let outdated = self.state != State::Stopped;
+                match request {
+                    Some(Request::Initialize) => self.launch()?,
+                    Some(Request::StackTrace | Request::Scopes | Request::Variables(_))
+                        if outdated => {}
+                    Some(Request::StackTrace) => {
+                        let frame = &body["stackFrames"][0];
+                        let path = frame["source"]["path"].as_str().map(PathBuf::from);
+                        let line = frame["line"].as_u64().map(|line| line as usize);
+                        self.location = path.zip(line);
+                        if let Some(frame_id) = frame["id"].as_i64() {
+                            self.request(
+                                Request::Scopes,
+                                "scopes",
+                                json!({ "frameId": frame_id }),
+                            )?;
+                        }
+                    }
+                    Some(Request::Scopes) => {
+                        for scope in body["scopes"].as_array().into_iter().flatten() {
+                            let index = self.scopes.len();
+                            self.scopes.push(Scope {
+                                name: scope["name"].as_str().unwrap_or_default().to_string(),
+                                variables: Vec::new(),
+                            });
+                            let reference = &scope["variablesReference"];
+                            self.request(
+                                Request::Variables(index),
+                                "variables",
+                                json!({ "variablesReference": reference }),
+                            )?;
+                        }
+                    }
+                    Some(Request::Variables(index)) => {
+                        let variables = body["variables"].as_array().into_iter().flatten();
+                        if let Some(scope) = self.scopes.get_mut(index) {
+                            scope.variables = variables
+                                .map(|variable| {
+                                    let text = |key: &str| {
+                                        variable[key].as_str().unwrap_or_default().to_string()
+                                    };
+                                    (text("name"), text("value"))
+                                })
+                                .collect();
+                        }
+                    }
+                    Some(Request::Launch | Request::Other) | None => (),
+                }
+            }
+            Some("event") => match message["event"].as_str() {
+                // The adapter is ready for the breakpoints
+                Some("initialized") => {
+                    self.send_breakpoints()?;
+                    self.request(Request::Other, "configurationDone", json!({}))?;
+                    self.state = State::Running;
+                }
+                Some("stopped") => {
+                    self.state = State::Stopped;
+                    self.thread_id = body["threadId"].as_i64().or(self.thread_id);
+                    self.scopes.clear();
+                    if let Some(thread_id) = self.thread_id {
+                        self.request(
+                            Request::StackTrace,
+                            "stackTrace",
+                            json!({ "threadId": thread_id, "levels": 1 }),
+                        )?;
+                    }
+                }
+                Some("continued") => {
+                    self.state = State::Running;
+                    self.location = None;
+                    self.scopes.clear();
+                }
+                Some("output") if body["category"] != "telemetry" => {
+                    self.output
+                        .push_str(body["output"].as_str().unwrap_or_default());
+                }
+                Some("terminated" | "exited") => {
+                    self.state = State::Ended;
+                    self.location = None;
+                    self.scopes.clear();
+                }
+                _ => (),
+            },
+            // Requests of the adapter, such as running the program in a terminal, which
+            // the editor does not support
+            Some("request") => {
+                let seq = self.next_seq;
+                self.next_seq += 1;
+                lsp::write_message(
+                    &mut self.stdin,
+                    &json!({
+                        "seq": seq,
+                        "type": "response",
+                        "request_seq": message["seq"],
+                        "command": message["command"],
+                        "success": false,
+                        "message": "not supported",
+                    }),
+                )?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Ask the adapter to start the program
+    fn launch(&mut self) -> io::Result<()> {
+        let arguments = json!({
+            "program": self.program,
+            "args": self.args,
+            "cwd": self.cwd,
+            "noDebug": false,
+        });
+        self.request(Request::Launch, "launch", arguments)
+    }
+
+    fn send_breakpoints(&mut self) -> io::Result<()> {
+        let breakpoints: Vec<Value> = self
+            .breakpoints
+            .iter()
+            .map(|line| json!({ "line": line }))
+            .collect();
+        let arguments = json!({
+            "source": { "path": self.program },
+            "breakpoints": breakpoints,
+        });
+        self.request(Request::Other, "setBreakpoints", arguments)
+    }
+
+    fn request(&mut self, request: Request, command: &str, arguments: Value) -> io::Result<()> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.pending.insert(seq, request);
+        lsp::write_message(
+            &mut self.stdin,
+            &json!({
+                "seq": seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            }),
+        )
+    }
+}
+
+impl Drop for Debugger {
+    /// Stop the program and the adapter
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        let disconnect = json!({ "terminateDebuggee": true });
+        if self
+            .request(Request::Other, "disconnect", disconnect)
+            .is_err()
+        {
+            let _ = child.kill();
+        }
+        lsp::reap(child);
+    }
+}
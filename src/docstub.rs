@@ -0,0 +1,71 @@
+//! Documentation stubs for betty functions. Given the line where a function is
+//! declared, build a comment block listing its parameters, following the format
+//! chosen by the user in `settings.json`.
+
+use colors_highlight::is_ident_continue;
+
+use super::settings::DocStub;
+
+/// A function declaration found on a single line of betty code
+pub struct FunDecl<'a> {
+    pub name: &'a str,
+    pub params: Vec<&'a str>,
+}
+
+/// Parse a line declaring a function. Both `fun name(a, b)` and `name = fun(a, b)`
+/// are accepted. Return [`None`] if the line does not declare a function.
+pub fn parse_fun_decl(line: &str) -> Option<FunDecl<'_>> {
+    let line = line.trim();
+    let (before, after) = split_keyword(line, "fun")?;
+
+    let open = after.find('(')?;
+    let close = open + after[open..].find(')')?;
+
+    // `fun name(...)` has the name between the keyword and the parenthesis,
+    // `name = fun(...)` has it before the equal sign
+    let name = match after[..open].trim() {
+        "" => before.trim().strip_suffix('=')?.trim(),
+        name => name,
+    };
+
+    let params = after[open + 1..close]
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .collect();
+
+    Some(FunDecl { name, params })
+}
+
+/// Split 'line' around the first occurrence of 'kw' as a whole word
+fn split_keyword<'a>(line: &'a str, kw: &str) -> Option<(&'a str, &'a str)> {
+    line.match_indices(kw).find_map(|(idx, _)| {
+        let (before, after) = (&line[..idx], &line[idx + kw.len()..]);
+        let starts_word = !before.ends_with(is_ident_continue);
+        let ends_word = !after.starts_with(is_ident_continue);
+        (starts_word && ends_word).then_some((before, after))
+    })
+}
+
+/// Build the documentation block for the function declared on 'line', indented
+/// like the declaration itself. Every line of the block ends with a newline, so it
+/// can be inserted right before 'line'. Return [`None`] if the line does not
+/// declare a function.
+pub fn make_stub(line: &str, format: &DocStub) -> Option<String> {
+    let decl = parse_fun_decl(line)?;
+    let indent = &line[..line.len() - line.trim_start().len()];
+
+    let mut stub = format!(
+        "{}{}\n",
+        indent,
+        format.description.replace("{name}", decl.name)
+    );
+    for param in decl.params {
+        stub.push_str(&format!(
+            "{}{}\n",
+            indent,
+            format.param.replace("{param}", param)
+        ));
+    }
+    Some(stub)
+}
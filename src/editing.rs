@@ -0,0 +1,163 @@
+//! Behavior of the code editor set by the `editor` settings: indentation with spaces,
+//! automatic closing of brackets and quotes, visible whitespace, the highlight of the
+//! current line and the numbers of wrapped lines.
+
+use std::fmt::Write;
+
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+use egui::widgets::text_edit::CursorRange;
+use egui::Galley;
+
+/// Characters that are closed automatically, with their closing character
+const PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// Replace the presses of Tab (without Shift) in 'events' with 'tab_size' spaces
+pub fn tabs_to_spaces(events: &mut [egui::Event], tab_size: usize) {
+    for event in events {
+        if let egui::Event::Key {
+            key: egui::Key::Tab,
+            pressed: true,
+            modifiers,
+        } = event
+        {
+            if !modifiers.shift {
+                *event = egui::Event::Text(" ".repeat(tab_size));
+            }
+        }
+    }
+}
+
+/// How the cursor moves when a bracket or a quote is typed
+pub enum Pairing {
+    /// The closing character after the cursor was typed again, and is skipped: the
+    /// cursor must be moved before the text is edited
+    Skip(CCursor),
+    /// The closing character was added: the cursor must be moved back between the
+    /// two characters after the text is edited
+    Close(CCursor),
+}
+
+/// Close the bracket or quote typed in 'events', if it is the only text typed in the
+/// frame and 'cursor' selects nothing. Brackets are closed only before whitespace or
+/// a closing character, so that the code after them can be wrapped.
+pub fn auto_pair(
+    events: &mut Vec<egui::Event>,
+    text: &str,
+    cursor: CCursorRange,
+) -> Option<Pairing> {
+    if cursor.primary != cursor.secondary {
+        return None;
+    }
+    let mut typed = events
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, event)| match event {
+            egui::Event::Text(text) => Some((idx, text.clone())),
+            _ => None,
+        });
+    let (idx, typed_text) = typed.next()?;
+    if typed.next().is_some() {
+        return None;
+    }
+
+    let mut chars = typed_text.chars();
+    let (Some(ch), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let index = cursor.primary.index;
+    let next = text.chars().nth(index);
+
+    if next == Some(ch) && PAIRS.iter().any(|(_, close)| *close == ch) {
+        events.remove(idx);
+        return Some(Pairing::Skip(CCursor::new(index + 1)));
+    }
+
+    let (open, close) = PAIRS.iter().find(|(open, _)| *open == ch)?;
+    let closes_before = next.map_or(true, |next| {
+        next.is_whitespace() || PAIRS.iter().any(|(_, close)| *close == next)
+    });
+    if !closes_before {
+        return None;
+    }
+    events[idx] = egui::Event::Text(format!("{}{}", open, close));
+    Some(Pairing::Close(CCursor::new(index + 1)))
+}
+
+/// Draw a dot on each space and an arrow on each tab of 'galley', which is drawn
+/// at 'pos'. Only the visible rows are drawn.
+pub fn paint_whitespace(
+    painter: &egui::Painter,
+    galley: &Galley,
+    pos: egui::Pos2,
+    color: egui::Color32,
+) {
+    let offset = pos.to_vec2();
+    let clip = painter.clip_rect();
+
+    for row in &galley.rows {
+        if !clip.intersects(row.rect.translate(offset)) {
+            continue;
+        }
+        for glyph in &row.glyphs {
+            let rect = glyph.logical_rect().translate(offset);
+            match glyph.chr {
+                ' ' => painter.circle_filled(rect.center(), 1.0, color),
+                '\t' => painter.arrow(
+                    rect.left_center() + egui::vec2(2.0, 0.0),
+                    egui::vec2(rect.width() - 4.0, 0.0),
+                    egui::Stroke::new(1.0, color),
+                ),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// The row of 'galley' (drawn at 'pos') with the cursor, 'width' wide
+pub fn current_line_rect(
+    galley: &Galley,
+    pos: egui::Pos2,
+    cursor: &CursorRange,
+    width: f32,
+) -> egui::Rect {
+    let rect = galley
+        .pos_from_cursor(&cursor.primary)
+        .translate(pos.to_vec2());
+    egui::Rect::from_min_size(
+        egui::pos2(pos.x, rect.min.y),
+        egui::vec2(width, rect.height()),
+    )
+}
+
+/// The numbers from 1 to 'count', one per row
+pub fn line_numbers(count: usize) -> String {
+    // Each number takes its digits and a newline at most
+    let width = count.to_string().len() + 1;
+    let mut numbers = String::with_capacity(count * width);
+    for n in 1..=count {
+        if n > 1 {
+            numbers.push('\n');
+        }
+        let _ = write!(numbers, "{}", n);
+    }
+    numbers
+}
+
+/// The line numbers of the rows of 'galley', one per row: each line has its number on
+/// its first row, and the rows made by wrapping it are empty
+pub fn wrapped_line_numbers(galley: &Galley) -> String {
+    let mut numbers = Vec::with_capacity(galley.rows.len());
+    let mut line = 0;
+    let mut starts_line = true;
+    for row in &galley.rows {
+        if starts_line {
+            line += 1;
+            numbers.push(line.to_string());
+        } else {
+            numbers.push(String::new());
+        }
+        starts_line = row.ends_with_newline;
+    }
+    numbers.join("\n")
+}
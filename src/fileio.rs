@@ -0,0 +1,285 @@
+//! Opening and saving files in the background, so that large files do not block the
+//! editor. The file is read or written in chunks, and the progress is shown in the
+//! status bar until the result is applied to the editor. Files are saved through a
+//! temporary file, so that a failed save leaves them as they were.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+/// Bytes read or written at once, between two reports of the progress
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// The UTF-8 byte order mark, which some editors write at the start of the files
+const BOM: &str = "\u{feff}";
+
+/// What a [`FileIo`] does with its file
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Open,
+    Save,
+}
+
+/// What a file holds
+pub enum Contents {
+    Text(String),
+    Binary(Vec<u8>), // with NUL bytes, or not valid UTF-8
+}
+
+impl Contents {
+    /// The contents as text, where the bytes that are not valid UTF-8 are replaced
+    pub fn into_text(self) -> String {
+        match self {
+            Contents::Text(text) => text,
+            Contents::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        }
+    }
+}
+
+enum Message {
+    Progress(f32),
+    Done(io::Result<Contents>), // the contents read or written
+}
+
+/// A file being opened or saved
+pub struct FileIo {
+    path: PathBuf,
+    operation: Operation,
+    progress: f32, // from 0 to 1
+    receiver: Receiver<Message>,
+}
+
+impl FileIo {
+    /// Read the whole 'file', which is at 'path'. Files with NUL bytes or that are not
+    /// valid UTF-8 are binary.
+    pub fn open(mut file: File, path: PathBuf) -> Self {
+        Self::start(path, Operation::Open, move |sender| {
+            let len = file.metadata().map_or(0, |metadata| metadata.len()) as usize;
+            let mut bytes = Vec::with_capacity(len);
+            while (&mut file)
+                .take(CHUNK_BYTES as u64)
+                .read_to_end(&mut bytes)?
+                > 0
+            {
+                let _ = sender.send(Message::Progress(bytes.len() as f32 / len.max(1) as f32));
+            }
+            if bytes.contains(&0) {
+                return Ok(Contents::Binary(bytes));
+            }
+            Ok(String::from_utf8(bytes)
+                .map_or_else(|err| Contents::Binary(err.into_bytes()), Contents::Text))
+        })
+    }
+
+    /// Write 'contents' to 'path', after a byte order mark if 'bom'. They are written to
+    /// a temporary file in the same folder, which then replaces the file at 'path'.
+    /// They are given back once written.
+    pub fn save(path: PathBuf, contents: String, bom: bool) -> Self {
+        // Replace the file a link points to, not the link
+        let target = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        Self::start(path, Operation::Save, move |sender| {
+            let temp = temp_path(&target);
+            let result = write_chunks(&temp, &contents, bom, sender)
+                .and_then(|()| keep_permissions(&target, &temp))
+                .and_then(|()| fs::rename(&temp, &target));
+            if result.is_err() {
+                let _ = fs::remove_file(&temp);
+            }
+            result.map(|()| Contents::Text(contents))
+        })
+    }
+
+    fn start(
+        path: PathBuf,
+        operation: Operation,
+        work: impl FnOnce(&Sender<Message>) -> io::Result<Contents> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result = work(&sender);
+            let _ = sender.send(Message::Done(result));
+        });
+        Self {
+            path,
+            operation,
+            progress: 0.0,
+            receiver,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// The contents read or written, once the operation is done
+    pub fn poll(&mut self) -> Option<io::Result<Contents>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Progress(progress)) => self.progress = progress,
+                Ok(Message::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(stopped())),
+            }
+        }
+    }
+
+    /// Block until the operation is done, and return the contents read or written
+    pub fn wait(self) -> io::Result<Contents> {
+        loop {
+            match self.receiver.recv() {
+                Ok(Message::Progress(_)) => (),
+                Ok(Message::Done(result)) => return result,
+                Err(_) => return Err(stopped()),
+            }
+        }
+    }
+}
+
+/// Write 'contents' to a new file at 'path', after a byte order mark if 'bom', reporting
+/// the progress to 'sender'
+fn write_chunks(
+    path: &Path,
+    contents: &str,
+    bom: bool,
+    sender: &Sender<Message>,
+) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    if bom {
+        file.write_all(BOM.as_bytes())?;
+    }
+    let len = contents.len();
+    let mut written = 0;
+    for chunk in contents.as_bytes().chunks(CHUNK_BYTES) {
+        file.write_all(chunk)?;
+        written += chunk.len();
+        let _ = sender.send(Message::Progress(written as f32 / len as f32));
+    }
+    // The contents must be on disk before they replace the file
+    file.sync_all()
+}
+
+/// Give 'temp' the permissions of the file at 'target' it replaces, if there is one
+fn keep_permissions(target: &Path, temp: &Path) -> io::Result<()> {
+    match fs::metadata(target) {
+        Ok(metadata) => fs::set_permissions(temp, metadata.permissions()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Is the file at 'path' read-only? Files that cannot be read are not.
+pub fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).map_or(false, |metadata| metadata.permissions().readonly())
+}
+
+/// The temporary file next to 'path', written while saving it
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.colors-save", name))
+}
+
+/// 'contents' without their byte order mark, and whether they had one
+pub fn strip_bom(contents: String) -> (String, bool) {
+    match contents.strip_prefix(BOM) {
+        Some(stripped) => (stripped.to_string(), true),
+        None => (contents, false),
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "the file operation stopped unexpectedly",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty folder for the files of the test 'name', in the temporary folder
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("colors-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Open the file at 'path' as the editor does
+    fn open(path: &Path) -> Contents {
+        FileIo::open(File::open(path).unwrap(), path.to_path_buf())
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn strip_bom_only_at_the_start() {
+        assert_eq!(strip_bom("\u{feff}a".into()), ("a".into(), true));
+        assert_eq!(strip_bom("a\u{feff}".into()), ("a\u{feff}".into(), false));
+        assert_eq!(strip_bom(String::new()), (String::new(), false));
+    }
+
+    #[test]
+    fn temp_path_is_hidden_next_to_the_file() {
+        let path = Path::new("dir").join("main.betty");
+        assert_eq!(
+            temp_path(&path),
+            Path::new("dir").join(".main.betty.colors-save")
+        );
+    }
+
+    #[test]
+    fn open_tells_text_from_binary() {
+        let dir = temp_dir("open");
+        let cases: [(&str, &[u8]); 3] = [
+            ("text", "héllo\n".as_bytes()),
+            ("nul", b"a\0b"),
+            ("latin1", b"h\xe9llo"),
+        ];
+        for (name, bytes) in cases {
+            fs::write(dir.join(name), bytes).unwrap();
+        }
+
+        assert!(matches!(open(&dir.join("text")), Contents::Text(text) if text == "héllo\n"));
+        assert!(matches!(open(&dir.join("nul")), Contents::Binary(bytes) if bytes == b"a\0b"));
+        assert!(
+            matches!(open(&dir.join("latin1")), Contents::Binary(bytes) if bytes == b"h\xe9llo")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_replaces_a_longer_file() {
+        let dir = temp_dir("save");
+        let path = dir.join("main.betty");
+        fs::write(&path, "a much longer text than the new one").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        FileIo::save(path.clone(), "short".into(), true)
+            .wait()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\u{feff}short");
+        assert!(!temp_path(&path).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
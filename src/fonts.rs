@@ -0,0 +1,47 @@
+//! The fonts added to the ones of egui. egui has a single monospace font without a
+//! bold variant, so the bold code is drawn with DejaVu Sans Mono Bold, registered as
+//! its own font family.
+
+use eframe::egui;
+
+/// Name of the bold monospace font family
+const BOLD_MONOSPACE: &str = "bold_monospace";
+
+const DEJAVU_SANS_MONO_BOLD: &[u8] = include_bytes!("../fonts/DejaVuSansMono-Bold.ttf");
+
+/// Register the fonts with egui
+pub fn install(ctx: &egui::Context) {
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert(
+        "DejaVuSansMono-Bold".to_string(),
+        egui::FontData::from_static(DEJAVU_SANS_MONO_BOLD),
+    );
+
+    // The characters missing from the bold font fall back to the regular ones
+    let mut family = vec!["DejaVuSansMono-Bold".to_string()];
+    family.extend(
+        fonts
+            .families
+            .get(&egui::FontFamily::Monospace)
+            .cloned()
+            .unwrap_or_default(),
+    );
+    fonts
+        .families
+        .insert(egui::FontFamily::Name(BOLD_MONOSPACE.into()), family);
+    ctx.set_fonts(fonts);
+}
+
+/// The monospace font family, bold or regular
+pub fn monospace(bold: bool) -> egui::FontFamily {
+    if bold {
+        egui::FontFamily::Name(BOLD_MONOSPACE.into())
+    } else {
+        egui::FontFamily::Monospace
+    }
+}
+
+/// Is the text of 'family' bold?
+pub fn is_bold(family: &egui::FontFamily) -> bool {
+    matches!(family, egui::FontFamily::Name(name) if &**name == BOLD_MONOSPACE)
+}
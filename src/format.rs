@@ -0,0 +1,163 @@
+//! Formatting of the code, either by an external formatter reading the code on stdin
+//! and writing it formatted on stdout, or by indenting the lines again after the
+//! blocks and brackets they are in.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use colors_highlight::{Config, Highligher, Token, TokenType};
+
+use super::runner;
+
+/// Format 'text' with the formatter started with the command line 'command', where
+/// `{file}` is replaced by 'path', e.g. for the formatter to find its configuration.
+/// Return the formatted text, or why it could not be formatted.
+pub fn run_formatter(command: &str, path: Option<&Path>, text: &str) -> Result<String, String> {
+    let file = path.map_or_else(String::new, |path| path.display().to_string());
+    let args: Vec<String> = runner::split_args(command)
+        .into_iter()
+        .map(|arg| arg.replace("{file}", &file))
+        .collect();
+    let Some((program, args)) = args.split_first() else {
+        return Err("The command of the formatter is empty".to_string());
+    };
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = path.and_then(Path::parent) {
+        command.current_dir(dir);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("'{}' could not be started: {}", program, err))?;
+
+    // Written in another thread, so that a formatter writing while it reads does
+    // not block both sides
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = text.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("'{}' failed: {}", program, err))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("'{}' failed: {}", program, stderr.trim()));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("'{}' did not write UTF-8 text", program))
+}
+
+/// Indent each line of 'text' by 'indent' spaces for each block and bracket it is in,
+/// and remove the whitespace at the end of the lines. The blocks and brackets opened on
+/// the same line indent the next ones once. The lines inside multi-line strings and
+/// comments are left as they are.
+pub fn reindent(text: &str, config: &Config, indent: usize) -> String {
+    let line_count = text.split('\n').count();
+    let mut levels = vec![None; line_count]; // set by the first token that is not closing
+    let mut depths = vec![None; line_count]; // levels after the tokens of each line
+    let mut verbatim = vec![false; line_count];
+
+    // Line of each block and bracket still open, and whether it indents
+    let mut open: Vec<(usize, bool)> = Vec::new();
+    let depth = |open: &[(usize, bool)]| open.iter().filter(|(_, indents)| *indents).count();
+    let mut else_do = false; // the next 'do' belongs to an 'else'
+
+    let (tokens, _) = Highligher::new(text, config).make_tokens();
+    for Token(typ, span, position) in &tokens {
+        let word = &text[span.clone()];
+        let line = position.line;
+        if matches!(typ, TokenType::Str | TokenType::Comment) {
+            let end_line = line + word.matches('\n').count();
+            verbatim[line + 1..=end_line].fill(true);
+        }
+        if word.trim().is_empty() || *typ == TokenType::Comment {
+            continue;
+        }
+
+        match (typ, word) {
+            // The block of 'else' is already open
+            (TokenType::Kw, "do") if else_do => (),
+            (TokenType::Kw, "do") | (TokenType::Other, "(" | "[") => {
+                levels[line].get_or_insert(depth(&open));
+                let indents = open.last().map_or(true, |(start, _)| *start != line);
+                open.push((line, indents));
+            }
+            (TokenType::Kw, "end" | "catch") | (TokenType::Other, ")" | "]") => {
+                open.pop();
+                if word == "catch" {
+                    levels[line].get_or_insert(depth(&open));
+                }
+            }
+            // 'else' closes the 'if' block and opens its own, with or without 'do'
+            (TokenType::Kw, "else") => {
+                levels[line].get_or_insert(depth(&open).saturating_sub(1));
+            }
+            _ => {
+                levels[line].get_or_insert(depth(&open));
+            }
+        }
+        depths[line] = Some(depth(&open));
+        if *typ == TokenType::Kw {
+            else_do = word == "else";
+        }
+    }
+
+    let mut depth = 0;
+    let mut lines = Vec::with_capacity(line_count);
+    for (idx, line) in text.split('\n').enumerate() {
+        // A line that only closes is at the level after it
+        let level = levels[idx].or(depths[idx]).unwrap_or(depth);
+        depth = depths[idx].unwrap_or(depth);
+        if verbatim[idx] {
+            lines.push(line.to_string());
+            continue;
+        }
+        // Windows line endings are kept
+        let (line, ending) = match line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (line, ""),
+        };
+        let code = line.trim();
+        if code.is_empty() {
+            lines.push(ending.to_string());
+        } else {
+            lines.push(format!("{}{}{}", " ".repeat(level * indent), code, ending));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The index (in characters) in 'formatted' of the cursor at 'index' in 'text', on the
+/// same line and at the same place in the code of the line, whose indentation may
+/// have changed
+pub fn moved_cursor(text: &str, formatted: &str, index: usize) -> usize {
+    let before: String = text.chars().take(index).collect();
+    let line = before.matches('\n').count();
+    let col = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count();
+    let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+
+    let old_line = text.split('\n').nth(line).unwrap_or_default();
+    let lines: Vec<&str> = formatted.split('\n').collect();
+    let line = line.min(lines.len() - 1);
+    let new_line = lines[line];
+    let code_col = col.saturating_sub(indent(old_line));
+    let col = (indent(new_line) + code_col).min(new_line.trim_end_matches('\r').chars().count());
+
+    let line_start: usize = lines[..line]
+        .iter()
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    line_start + col
+}
@@ -0,0 +1,52 @@
+//! Size and position of the window, written to `window.json` in the user folder when
+//! the IDE closes, so that it opens as it was left.
+
+use std::fs;
+use std::io;
+
+use eframe::egui;
+use serde_derive::{Deserialize, Serialize};
+
+use super::paths;
+
+/// How close to the width of the monitor the window must be to be maximized. The window
+/// does not tell whether it is maximized, but its borders are all it lacks then.
+const MAXIMIZED_MARGIN: f32 = 20.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Geometry {
+    pub position: Option<[f32; 2]>, // of the top left corner, in points
+    pub size: [f32; 2],             // inside the borders, in points
+    pub maximized: bool,
+}
+
+impl Geometry {
+    /// The geometry of the window in 'info'
+    pub fn of(info: &eframe::WindowInfo) -> Self {
+        let maximized = info.monitor_size.map_or(false, |monitor| {
+            info.fullscreen || info.size.x >= monitor.x - MAXIMIZED_MARGIN
+        });
+        Self {
+            position: info.position.map(|pos| [pos.x, pos.y]),
+            size: [info.size.x, info.size.y],
+            maximized,
+        }
+    }
+
+    /// The geometry saved when the IDE last closed, if any
+    pub fn load() -> Option<Self> {
+        let json = fs::read_to_string(paths::geometry_file()).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(paths::geometry_file(), serde_json::to_string(self)?)
+    }
+
+    /// Open the window with this geometry
+    pub fn apply(&self, options: &mut eframe::NativeOptions) {
+        options.maximized = self.maximized;
+        options.initial_window_pos = self.position.map(|[x, y]| egui::pos2(x, y));
+        options.initial_window_size = Some(egui::vec2(self.size[0], self.size[1]));
+    }
+}
@@ -0,0 +1,293 @@
+//! Changes of the open file since the last commit, when it is in a git repository. The
+//! contents of the file in `HEAD` are diffed with the ones in the editor, so that the
+//! changed lines are marked next to their numbers. The source control window stages
+//! the file, shows its diff and commits it.
+
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use git2::{DiffFormat, DiffOptions, Patch, Repository, Status};
+
+/// Length of the commit IDs shown
+const SHORT_ID_LEN: usize = 7;
+
+/// How a line differs from `HEAD`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    Removed, // lines were removed before this one
+}
+
+/// The repository of the file in 'path', and the path of the file in it
+fn open(path: &Path) -> Result<(Repository, PathBuf), git2::Error> {
+    let not_found = || git2::Error::from_str("the file is not in a git repository");
+    let repo = Repository::discover(path.parent().ok_or_else(not_found)?)?;
+    let workdir = repo.workdir().ok_or_else(not_found)?.canonicalize();
+    let path = path.canonicalize();
+    let (Ok(workdir), Ok(path)) = (workdir, path) else {
+        return Err(not_found());
+    };
+    let relative = path.strip_prefix(workdir).map_err(|_| not_found())?;
+    Ok((repo, relative.to_path_buf()))
+}
+
+/// The contents of the file in 'path' in `HEAD`, if it is in a git repository. A file
+/// that is not committed yet has empty contents.
+pub fn head_contents(path: &Path) -> Option<String> {
+    let (repo, relative) = open(path).ok()?;
+
+    // A repository without commits has no HEAD
+    let Ok(tree) = repo.head().and_then(|head| head.peel_to_tree()) else {
+        return Some(String::new());
+    };
+    let Ok(entry) = tree.get_path(&relative) else {
+        return Some(String::new());
+    };
+    let blob = entry.to_object(&repo).ok()?.peel_to_blob().ok()?;
+    Some(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+/// The lines (from 0) of 'contents' that differ from 'head'
+pub fn line_changes(head: &str, contents: &str) -> Vec<(usize, LineChange)> {
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let Ok(patch) = Patch::from_buffers(
+        head.as_bytes(),
+        None,
+        contents.as_bytes(),
+        None,
+        Some(&mut options),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut changes = Vec::new();
+    for idx in 0..patch.num_hunks() {
+        let Ok((hunk, _)) = patch.hunk(idx) else {
+            continue;
+        };
+        let (old_lines, new_lines) = (hunk.old_lines() as usize, hunk.new_lines() as usize);
+        // Lines are counted from 1, and an empty range starts at the line before it
+        let start = hunk.new_start() as usize;
+        if new_lines == 0 {
+            changes.push((start, LineChange::Removed));
+            continue;
+        }
+        for line in start - 1..start - 1 + new_lines {
+            let change = if line - (start - 1) < old_lines {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            };
+            changes.push((line, change));
+        }
+    }
+    changes
+}
+
+/// Stage the changes of the file in 'path', as saved
+pub fn stage(path: &Path) -> Result<(), git2::Error> {
+    let (repo, relative) = open(path)?;
+    let mut index = repo.index()?;
+    index.add_path(&relative)?;
+    index.write()
+}
+
+/// Commit the changes staged in the repository of the file in 'path', with 'message'.
+/// Return the short ID of the commit.
+pub fn commit(path: &Path, message: &str) -> Result<String, git2::Error> {
+    let (repo, _) = open(path)?;
+    let signature = repo.signature().map_err(|_| {
+        git2::Error::from_str(
+            "set your name and email with 'git config user.name' and 'user.email'",
+        )
+    })?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    // The first commit has no parent
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<_> = parent.iter().collect();
+    let oid = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    let id = oid.to_string();
+    Ok(id[..id.len().min(SHORT_ID_LEN)].to_string())
+}
+
+/// How the file in 'path' differs from `HEAD`, as told by `git status`
+fn status(path: &Path) -> Result<String, git2::Error> {
+    let (repo, relative) = open(path)?;
+    let status = repo.status_file(&relative)?;
+    let staged = Status::INDEX_NEW | Status::INDEX_MODIFIED | Status::INDEX_DELETED;
+    let unstaged = Status::WT_NEW | Status::WT_MODIFIED | Status::WT_DELETED;
+    Ok(
+        match (status.intersects(staged), status.intersects(unstaged)) {
+            _ if status.contains(Status::IGNORED) => "ignored",
+            (false, false) => "unchanged",
+            (true, false) => "staged",
+            (false, true) => "changed, not staged",
+            (true, true) => "partly staged",
+        }
+        .to_string(),
+    )
+}
+
+/// The changes of the file in 'path' since `HEAD`, staged or not, as a patch
+fn diff(path: &Path) -> Result<String, git2::Error> {
+    let (repo, relative) = open(path)?;
+    let tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let mut options = DiffOptions::new();
+    options
+        .pathspec(&relative)
+        .include_untracked(true)
+        .show_untracked_content(true);
+    let diff = repo.diff_tree_to_workdir_with_index(tree.as_ref(), Some(&mut options))?;
+
+    let mut patch = String::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        // The file and hunk headers hold their own origin
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })?;
+    Ok(patch)
+}
+
+/// Window staging the open file, showing its diff and committing it
+pub struct GitWindow {
+    /// Message of the next commit
+    message: String,
+
+    /// Status and diff of the file, or the error getting them
+    status: Result<String, String>,
+    diff: String,
+
+    /// Outcome of the last action, e.g. the commit made
+    notice: Option<String>,
+
+    /// Is the window still open?
+    open: bool,
+}
+
+impl GitWindow {
+    pub fn new(path: &Path) -> Self {
+        let mut window = Self {
+            message: String::new(),
+            status: Ok(String::new()),
+            diff: String::new(),
+            notice: None,
+            open: true,
+        };
+        window.refresh(path);
+        window
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Read the status and the diff of the file in 'path' again
+    fn refresh(&mut self, path: &Path) {
+        self.status = status(path).map_err(|err| err.message().to_string());
+        self.diff = diff(path).unwrap_or_else(|err| err.message().to_string());
+    }
+
+    /// Draw the window for the file in 'path', whose changes in the editor are
+    /// 'saved' or not. Return whether a commit was made.
+    pub fn show(&mut self, ctx: &egui::Context, path: &Path, saved: bool) -> bool {
+        let mut committed = false;
+        let mut open = self.open;
+
+        egui::Window::new("Source control")
+            .open(&mut open)
+            .default_size((600.0, 500.0))
+            .show(ctx, |ui| {
+                let status = match self.status {
+                    Ok(ref status) => status.clone(),
+                    Err(ref err) => {
+                        ui.label(err);
+                        return;
+                    }
+                };
+                ui.label(format!("{}: {}", path.display(), status));
+                if !saved {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "Save the file to stage its latest changes",
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Stage the file").clicked() {
+                        self.notice = stage(path).err().map(|err| err.message().to_string());
+                        self.refresh(path);
+                    }
+                    if ui.button("Refresh").clicked() {
+                        self.notice = None;
+                        self.refresh(path);
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::both()
+                    .id_source("git_diff")
+                    .max_height(ui.available_height() - 120.0)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        if self.diff.is_empty() {
+                            ui.label("No changes");
+                        }
+                        for line in self.diff.lines() {
+                            let color = match line.chars().next() {
+                                Some('+') if !line.starts_with("+++") => {
+                                    egui::Color32::from_rgb(80, 180, 80)
+                                }
+                                Some('-') if !line.starts_with("---") => {
+                                    egui::Color32::from_rgb(220, 90, 90)
+                                }
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.label(egui::RichText::new(line).monospace().color(color));
+                        }
+                    });
+                ui.separator();
+
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.message)
+                        .hint_text("Commit message")
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.horizontal(|ui| {
+                    let can_commit = !self.message.trim().is_empty();
+                    if ui
+                        .add_enabled(can_commit, egui::Button::new("Commit"))
+                        .clicked()
+                    {
+                        match commit(path, self.message.trim()) {
+                            Ok(id) => {
+                                self.notice = Some(format!("Committed {}", id));
+                                self.message.clear();
+                                committed = true;
+                            }
+                            Err(err) => self.notice = Some(err.message().to_string()),
+                        }
+                        self.refresh(path);
+                    }
+                    if let Some(ref notice) = self.notice {
+                        ui.label(notice);
+                    }
+                });
+            });
+
+        self.open = open;
+        committed
+    }
+}
@@ -0,0 +1,197 @@
+//! Highlighting with the Sublime Text grammars (`.sublime-syntax` files) in the
+//! `syntaxes` folder, available with the `syntect` feature. The scopes assigned by
+//! the grammar are mapped to the [`TokenType`]s of the [`Highligher`], so the code
+//! is colored with the same settings.
+//!
+//! TextMate grammars (`.tmLanguage`) are not supported by `syntect`, but Sublime Text
+//! can convert them to `.sublime-syntax` files.
+//!
+//! [`Highligher`]: colors_highlight::Highligher
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use colors_highlight::{Position, Token, TokenType};
+use eframe::egui;
+use syntect::parsing::{
+    ParseState, Scope, ScopeStack, SyntaxDefinition, SyntaxReference, SyntaxSet, SyntaxSetBuilder,
+};
+
+use super::log;
+
+/// Scopes matched against the scopes of the code, most specific first
+const SCOPES: [(&str, TokenType); 17] = [
+    ("comment", TokenType::Comment),
+    ("string", TokenType::Str),
+    ("constant.numeric", TokenType::Num),
+    ("constant.character.escape", TokenType::Str),
+    ("constant.language", TokenType::Kw),
+    ("keyword.operator", TokenType::Op),
+    ("keyword", TokenType::Kw),
+    ("storage", TokenType::Kw),
+    ("support.function", TokenType::BuiltinFun),
+    ("support.type", TokenType::BuiltinFun),
+    ("entity.name.function", TokenType::Fun),
+    ("variable.function", TokenType::Fun),
+    ("invalid", TokenType::Error),
+    ("punctuation", TokenType::Sym),
+    ("variable", TokenType::Ident),
+    ("entity.name", TokenType::Ident),
+    ("meta.generic-name", TokenType::Ident),
+];
+
+/// The grammars found in the syntaxes folder
+pub struct Grammars {
+    set: SyntaxSet,
+    scopes: Vec<(Scope, TokenType)>,
+}
+
+impl Grammars {
+    /// Load the `.sublime-syntax` files in 'dir'. Grammars that cannot be read or
+    /// parsed are logged and skipped.
+    pub fn load(dir: &Path) -> Self {
+        let mut builder = SyntaxSetBuilder::new();
+
+        if let Ok(entries) = fs::read_dir(dir) {
+            for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+                if path.extension().map_or(true, |ext| ext != "sublime-syntax") {
+                    continue;
+                }
+
+                // Lines are always parsed with their '\n'
+                let fallback_name = path.file_stem().map(|stem| stem.to_string_lossy());
+                let def = fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|yaml| {
+                        SyntaxDefinition::load_from_str(&yaml, true, fallback_name.as_deref())
+                            .map_err(|err| err.to_string())
+                    });
+                match def {
+                    Ok(def) => builder.add(def),
+                    Err(err) => log::warning!(format!(
+                        "The grammar '{}' could not be loaded: {}",
+                        path.display(),
+                        err
+                    )),
+                }
+            }
+        }
+
+        Self {
+            set: builder.build(),
+            scopes: SCOPES
+                .iter()
+                .filter_map(|&(scope, typ)| Some((Scope::new(scope).ok()?, typ)))
+                .collect(),
+        }
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&SyntaxReference> {
+        self.set.find_syntax_by_name(name)
+    }
+
+    pub fn by_extension(&self, ext: &str) -> Option<&SyntaxReference> {
+        self.set.find_syntax_by_extension(ext)
+    }
+
+    /// Split 'text' into [`Token`]s according to the grammar of 'syntax'. If the grammar
+    /// fails on a line, the rest of the text is left uncolored.
+    pub fn make_tokens(&self, text: &str, syntax: &SyntaxReference) -> Vec<Token> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+        let mut line_start = 0;
+
+        for (line_number, line) in text.split_inclusive('\n').enumerate() {
+            let Ok(ops) = state.parse_line(line, &self.set) else {
+                let position = Position {
+                    line: line_number,
+                    col: 0,
+                };
+                tokens.push(Token(TokenType::Other, line_start..text.len(), position));
+                return tokens;
+            };
+
+            let mut start = 0;
+            for (end, op) in ops
+                .iter()
+                .map(|(index, op)| (*index, Some(op)))
+                .chain([(line.len(), None)])
+            {
+                if end > start {
+                    let typ = self.token_type(&stack);
+                    let span = line_start + start..line_start + end;
+                    match tokens.last_mut() {
+                        // Merge the tokens of the same type to keep the job short
+                        Some(Token(last_typ, last_span, _))
+                            if *last_typ == typ && last_span.end == span.start =>
+                        {
+                            last_span.end = span.end;
+                        }
+                        _ => {
+                            let position = Position {
+                                line: line_number,
+                                col: line[..start].chars().count(),
+                            };
+                            tokens.push(Token(typ, span, position));
+                        }
+                    }
+                    start = end;
+                }
+                if let Some(op) = op {
+                    // A broken stack only affects the colors
+                    let _ = stack.apply(op);
+                }
+            }
+            line_start += line.len();
+        }
+        tokens
+    }
+
+    /// The type of the innermost scope that has one
+    fn token_type(&self, stack: &ScopeStack) -> TokenType {
+        stack
+            .as_slice()
+            .iter()
+            .rev()
+            .find_map(|&scope| {
+                self.scopes
+                    .iter()
+                    .find(|(prefix, _)| prefix.is_prefix_of(scope))
+                    .map(|&(_, typ)| typ)
+            })
+            .unwrap_or(TokenType::Other)
+    }
+}
+
+/// The last text highlighted with a grammar. Grammars cannot resume from the middle
+/// of the text, so the whole text is analysed again only when it changes.
+#[derive(Default)]
+pub struct GrammarCache {
+    key: u64,
+    job: egui::text::LayoutJob,
+}
+
+impl GrammarCache {
+    /// Build the [`egui::text::LayoutJob`] of 'text', unless it is the same text as the
+    /// last time. 'make_job' turns the text and its [`Token`]s into the job.
+    pub fn layout(
+        &mut self,
+        text: &str,
+        grammars: &Grammars,
+        syntax: &SyntaxReference,
+        make_job: impl Fn(&str, Vec<Token>) -> egui::text::LayoutJob,
+    ) -> egui::text::LayoutJob {
+        let mut hasher = DefaultHasher::new();
+        (text, &syntax.name).hash(&mut hasher);
+        let key = hasher.finish();
+
+        if key != self.key || self.job.text.len() != text.len() {
+            self.key = key;
+            self.job = make_job(text, grammars.make_tokens(text, syntax));
+        }
+        self.job.clone()
+    }
+}
@@ -0,0 +1,97 @@
+//! Read-only hex view of the files that are not text. Each row shows the offset of its
+//! first byte, the bytes in hexadecimal and their ASCII characters. Like the output
+//! viewer, only the visible rows are laid out.
+
+use eframe::egui;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Bytes shown on each row
+const ROW_BYTES: usize = 16;
+
+pub struct HexView {
+    /// Path of the file shown
+    path: PathBuf,
+
+    /// Contents of the file
+    bytes: Vec<u8>,
+
+    /// Is the window still open?
+    open: bool,
+}
+
+impl HexView {
+    pub fn new(path: PathBuf, bytes: Vec<u8>) -> Self {
+        Self {
+            path,
+            bytes,
+            open: true,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draw the view as a separate window
+    pub fn show(&mut self, ctx: &egui::Context, font_size: f32) {
+        let font_id = egui::FontId::new(font_size, egui::FontFamily::Monospace);
+        let row_height = ctx.fonts().row_height(&font_id);
+        let bytes = &self.bytes;
+        let name = self.path.file_name().map_or_else(
+            || self.path.to_string_lossy(),
+            |name| name.to_string_lossy(),
+        );
+
+        egui::Window::new(format!("{} (read-only)", name))
+            .id(egui::Id::new("hex_view"))
+            .open(&mut self.open)
+            .default_size((700.0, 600.0))
+            .vscroll(false)
+            .show(ctx, |ui| {
+                let rows = (bytes.len() + ROW_BYTES - 1) / ROW_BYTES;
+                egui::ScrollArea::both()
+                    .id_source("hex_view_rows")
+                    .auto_shrink([false, false])
+                    .show_rows(ui, row_height, rows, |ui, rows| {
+                        for row in rows {
+                            let start = row * ROW_BYTES;
+                            let end = (start + ROW_BYTES).min(bytes.len());
+                            ui.label(
+                                egui::RichText::new(format_row(start, &bytes[start..end]))
+                                    .font(font_id.clone())
+                                    .color(ui.visuals().strong_text_color()),
+                            );
+                        }
+                    });
+            });
+    }
+}
+
+/// The row of 'bytes' starting at 'offset': `00000010  48 65 6c ...  Hel...`
+fn format_row(offset: usize, bytes: &[u8]) -> String {
+    let mut row = format!("{:08x} ", offset);
+    for (idx, byte) in bytes.iter().enumerate() {
+        // A wider gap between the two halves of the row
+        if idx % (ROW_BYTES / 2) == 0 {
+            row.push(' ');
+        }
+        let _ = write!(row, "{:02x} ", byte);
+    }
+    // Keep the characters of a short last row aligned with the others
+    for idx in bytes.len()..ROW_BYTES {
+        if idx % (ROW_BYTES / 2) == 0 {
+            row.push(' ');
+        }
+        row.push_str("   ");
+    }
+    row.push(' ');
+    row.extend(bytes.iter().map(|&byte| {
+        if byte.is_ascii_graphic() || byte == b' ' {
+            byte as char
+        } else {
+            '.'
+        }
+    }));
+    row
+}
@@ -0,0 +1,123 @@
+//! History of the runs of the programs, with their output, so that the output of two
+//! runs can be compared without running the programs again.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use eframe::egui;
+
+use super::ansi;
+
+/// Runs kept in the history, the oldest ones being forgotten first
+const MAX_RUNS: usize = 30;
+
+/// The format of the time at which the runs started
+const TIME_FORMAT: &str = "%H:%M:%S";
+
+pub struct RunRecord {
+    pub command: String,
+    pub started: chrono::DateTime<chrono::Local>,
+    pub exit_code: Option<i32>, // None if the program was stopped or killed
+    pub elapsed: Duration,
+    pub output: String, // as shown in the console, so maybe truncated
+}
+
+impl RunRecord {
+    /// Short description of the run, to choose it from a list
+    fn title(&self) -> String {
+        let status = match self.exit_code {
+            Some(code) => format!("code {}", code),
+            None => "stopped".to_string(),
+        };
+        format!(
+            "{}  {}  ({}, {:.2}s)",
+            self.started.format(TIME_FORMAT),
+            self.command,
+            status,
+            self.elapsed.as_secs_f32()
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct RunHistory {
+    /// The latest run first
+    records: VecDeque<RunRecord>,
+
+    /// Runs shown side by side, as indices of 'records' from the oldest run, so that
+    /// they stay the same when runs are added
+    shown: [Option<usize>; 2],
+
+    /// Runs added since the first one, i.e. the index from the oldest of the next run
+    count: usize,
+}
+
+impl RunHistory {
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn push(&mut self, record: RunRecord) {
+        self.records.push_front(record);
+        self.records.truncate(MAX_RUNS);
+        self.count += 1;
+
+        // The latest run is shown on the left, and the one it replaces on the right
+        self.shown = [Some(self.count - 1), self.shown[0]];
+    }
+
+    /// The run added 'number'-th, if it is still in the history
+    fn get(&self, number: usize) -> Option<&RunRecord> {
+        let age = self.count.checked_sub(number + 1)?;
+        self.records.get(age)
+    }
+
+    /// Draw two runs side by side, each chosen from a list of the runs
+    pub fn show(&mut self, ui: &mut egui::Ui, font_size: f32) {
+        if self.records.is_empty() {
+            ui.label(egui::RichText::new("No runs").monospace());
+            return;
+        }
+
+        let font_id = egui::FontId::new(font_size, egui::FontFamily::Monospace);
+        let color = ui.visuals().text_color();
+        let strong_color = ui.visuals().strong_text_color();
+
+        ui.columns(2, |columns| {
+            for (column, ui) in columns.iter_mut().enumerate() {
+                let title = self.shown[column]
+                    .and_then(|number| self.get(number))
+                    .map_or_else(|| "Choose a run".to_string(), RunRecord::title);
+                let mut choice = None;
+                egui::ComboBox::from_id_source(("run_history", column))
+                    .selected_text(title)
+                    .width(ui.available_width())
+                    .show_ui(ui, |ui| {
+                        for (age, record) in self.records.iter().enumerate() {
+                            let number = self.count - 1 - age;
+                            let current = self.shown[column] == Some(number);
+                            if ui.selectable_label(current, record.title()).clicked() {
+                                choice = Some(number);
+                            }
+                        }
+                    });
+                if choice.is_some() {
+                    self.shown[column] = choice;
+                }
+
+                let Some(record) = self.shown[column].and_then(|number| self.get(number)) else {
+                    continue;
+                };
+                let (text, spans) = ansi::parse(&record.output);
+                let job = ansi::layout_job(&text, &spans, font_id.clone(), color, strong_color);
+                egui::ScrollArea::both()
+                    .id_source(("run_history_output", column))
+                    .auto_shrink([false, true])
+                    .max_height(ui.available_height())
+                    .show(ui, |ui| {
+                        ui.label(job);
+                    });
+            }
+        });
+    }
+}
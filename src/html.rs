@@ -0,0 +1,183 @@
+//! Export of the highlighted code to a standalone HTML file, whose colors and font
+//! styles are inline, so that it looks like the editor wherever it is opened. The code
+//! is printed the same way, by the browser, from a page with line numbers and a header
+//! repeated on each printed page.
+
+use std::fmt::Write;
+
+use eframe::egui;
+
+use super::fonts;
+
+/// The HTML page showing the text of 'job' in its colors and styles, on 'background',
+/// titled 'title'
+pub fn export(job: &egui::text::LayoutJob, title: &str, background: egui::Color32) -> String {
+    let font_size = job
+        .sections
+        .first()
+        .map_or(14.0, |section| section.format.font_id.size);
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>{}</title>\n\
+        </head>\n\
+        <body style=\"margin: 0; background: {};\">\n\
+        <pre style=\"margin: 0; padding: 1em; font-family: monospace; font-size: {}px;\">",
+        escape(title),
+        css_color(background),
+        font_size
+    );
+
+    for (format, text) in runs(job) {
+        push_span(&mut html, format, text);
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+/// The HTML page printing the text of 'job' in its colors and styles, on 'background',
+/// with its line numbers and with 'header' at the top of each page. The print dialog
+/// of the browser opens with the page, which can also save it as PDF.
+pub fn print_page(job: &egui::text::LayoutJob, header: &str, background: egui::Color32) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>{header}</title>\n\
+        <style>\n\
+        @page {{ margin: 1.5cm; }}\n\
+        body {{ margin: 0; background: {background}; \
+            print-color-adjust: exact; -webkit-print-color-adjust: exact; }}\n\
+        table {{ border-collapse: collapse; width: 100%; \
+            font-family: monospace; font-size: 10pt; }}\n\
+        th {{ text-align: left; font-weight: normal; color: {muted}; \
+            padding-bottom: 0.5em; }}\n\
+        tr {{ break-inside: avoid; }}\n\
+        td {{ vertical-align: top; }}\n\
+        td.number {{ text-align: right; padding-right: 1em; color: {muted}; \
+            user-select: none; }}\n\
+        td.code {{ white-space: pre-wrap; width: 100%; }}\n\
+        </style>\n\
+        <script>window.onload = () => window.print();</script>\n\
+        </head>\n\
+        <body>\n\
+        <table>\n\
+        <thead><tr><th colspan=\"2\">{header}</th></tr></thead>\n\
+        <tbody>\n",
+        header = escape(header),
+        background = css_color(background),
+        muted = MUTED_COLOR,
+    );
+
+    // The runs are split into the rows of their lines
+    let mut number = 1;
+    let mut row = String::new();
+    for (format, text) in runs(job) {
+        let mut lines = text.split('\n');
+        if let Some(first) = lines.next() {
+            push_span(&mut row, format, first);
+        }
+        for line in lines {
+            push_row(&mut html, number, &row);
+            number += 1;
+            row.clear();
+            push_span(&mut row, format, line);
+        }
+    }
+    push_row(&mut html, number, &row);
+
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Color of the line numbers and of the header of the printed pages
+const MUTED_COLOR: &str = "#888888";
+
+/// The text of 'job' cut where its format changes. Sections of the same format are
+/// merged, as the lines are highlighted apart.
+fn runs(job: &egui::text::LayoutJob) -> Vec<(&egui::text::TextFormat, &str)> {
+    let mut runs = Vec::new();
+    let mut sections = job.sections.iter().peekable();
+    while let Some(section) = sections.next() {
+        let mut end = section.byte_range.end;
+        while let Some(next) = sections.next_if(|next| next.format == section.format) {
+            end = next.byte_range.end;
+        }
+        runs.push((&section.format, &job.text[section.byte_range.start..end]));
+    }
+    runs
+}
+
+fn push_span(html: &mut String, format: &egui::text::TextFormat, text: &str) {
+    if !text.is_empty() {
+        let _ = write!(
+            html,
+            "<span style=\"{}\">{}</span>",
+            css_style(format),
+            escape(text)
+        );
+    }
+}
+
+fn push_row(html: &mut String, number: usize, code: &str) {
+    let _ = writeln!(
+        html,
+        "<tr><td class=\"number\">{}</td><td class=\"code\">{}</td></tr>",
+        number, code
+    );
+}
+
+/// The inline style of the text formatted with 'format'
+fn css_style(format: &egui::text::TextFormat) -> String {
+    let mut style = format!("color: {};", css_color(format.color));
+    if fonts::is_bold(&format.font_id.family) {
+        style.push_str(" font-weight: bold;");
+    }
+    if format.italics {
+        style.push_str(" font-style: italic;");
+    }
+    let lines: Vec<&str> = [
+        (format.underline, "underline"),
+        (format.strikethrough, "line-through"),
+    ]
+    .into_iter()
+    .filter(|(stroke, _)| stroke.width > 0.0)
+    .map(|(_, line)| line)
+    .collect();
+    if !lines.is_empty() {
+        let _ = write!(style, " text-decoration: {};", lines.join(" "));
+    }
+    style
+}
+
+fn css_color(color: egui::Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == u8::MAX {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("rgba({}, {}, {}, {:.3})", r, g, b, a as f32 / 255.0)
+    }
+}
+
+/// 'text' with the characters that have a meaning in HTML escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
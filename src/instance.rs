@@ -0,0 +1,124 @@
+//! Single instance of the IDE. The IDE listens on a local port, written to
+//! `instance.port` in the user folder, so that an IDE started later with files to open
+//! sends their paths there and exits, and the files open in the window already there.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use eframe::egui;
+
+use super::log;
+use super::paths;
+
+/// First line sent to the IDE, to tell its clients from other programs
+const HELLO: &str = "colors open";
+
+/// Line sent back by the IDE once it has the paths
+const ACK: &str = "ok";
+
+/// How long a client waits for the IDE
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Send 'files' to the IDE already running, if any. Return whether it got them.
+pub fn forward(files: &[PathBuf]) -> bool {
+    let Some(port) = fs::read_to_string(paths::instance_file())
+        .ok()
+        .and_then(|port| port.trim().parse::<u16>().ok())
+    else {
+        return false;
+    };
+    // If the port cannot be reached, the IDE that wrote it has exited
+    send(port, files).unwrap_or(false)
+}
+
+/// Send 'files' to the port 'port', returning whether the IDE acknowledged them
+fn send(port: u16, files: &[PathBuf]) -> io::Result<bool> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut message = format!("{}\n", HELLO);
+    for file in files {
+        message.push_str(&format!("{}\n", file.display()));
+    }
+    // An empty line ends the paths
+    message.push('\n');
+    stream.write_all(message.as_bytes())?;
+
+    let mut ack = String::new();
+    BufReader::new(stream).read_line(&mut ack)?;
+    Ok(ack.trim_end() == ACK)
+}
+
+/// Paths sent by the IDEs started later
+pub struct Instance {
+    paths: Receiver<PathBuf>,
+}
+
+impl Instance {
+    /// Listen for the paths sent by the IDEs started later, repainting 'ctx' when
+    /// some arrive
+    pub fn listen(ctx: egui::Context) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        fs::write(paths::instance_file(), port.to_string())?;
+
+        let (sender, paths) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let files = match stream.and_then(receive) {
+                    Ok(files) => files,
+                    Err(err) => {
+                        log::warning!(format!("The files to open could not be received: {}", err));
+                        continue;
+                    }
+                };
+                for file in files {
+                    if sender.send(file).is_err() {
+                        // The IDE is closing
+                        return;
+                    }
+                }
+                ctx.request_repaint();
+            }
+        });
+        Ok(Self { paths })
+    }
+
+    /// The next path received, if any
+    pub fn poll(&self) -> Option<PathBuf> {
+        self.paths.try_recv().ok()
+    }
+}
+
+/// Read the paths sent to 'stream' by another IDE, and acknowledge them
+fn receive(stream: TcpStream) -> io::Result<Vec<PathBuf>> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+
+    if lines.first().map(String::as_str) != Some(HELLO) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the message does not come from colors",
+        ));
+    }
+    (&stream).write_all(format!("{}\n", ACK).as_bytes())?;
+    Ok(lines.into_iter().skip(1).map(PathBuf::from).collect())
+}
@@ -0,0 +1,564 @@
+//! JSON with comments, as used by the settings, themes and syntax definitions. The
+//! text can have `//` and `/* */` comments and trailing commas. It can be edited
+//! while keeping its formatting and comments, so that the settings changed in the IDE
+//! can be written back to `settings.json` without reordering or reindenting the file
+//! of the user.
+
+use std::ops::Range;
+
+use serde_json::Value;
+
+/// Return 'text' as plain JSON, with its comments and trailing commas replaced by
+/// spaces. Lines and byte offsets are the same in both texts.
+pub fn strip_comments(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    let mut comma = None; // last ',' followed only by whitespace so far
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        match (bytes[pos], bytes.get(pos + 1)) {
+            (b'"', _) => {
+                comma = None;
+                pos += 1;
+                while pos < bytes.len() && bytes[pos] != b'"' {
+                    pos += if bytes[pos] == b'\\' { 2 } else { 1 };
+                }
+            }
+            (b'/', Some(b'/')) => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    bytes[pos] = b' ';
+                    pos += 1;
+                }
+            }
+            (b'/', Some(b'*')) => {
+                let end = text[pos + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |idx| pos + idx + 4);
+                for byte in &mut bytes[pos..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                pos = end;
+                continue;
+            }
+            (b',', _) => comma = Some(pos),
+            (b'}' | b']', _) => {
+                if let Some(comma) = comma.take() {
+                    bytes[comma] = b' ';
+                }
+            }
+            (byte, _) if !byte.is_ascii_whitespace() => comma = None,
+            _ => (),
+        }
+        pos += 1;
+    }
+
+    // Only ASCII bytes were replaced, by ASCII bytes
+    String::from_utf8(bytes).unwrap_or_default()
+}
+
+/// A member of an object found in the text
+struct Member {
+    key: String,
+    start: usize, // start of its key
+    end: usize,   // end of its value
+}
+
+/// An object found in the text
+struct Object {
+    path: Vec<String>, // keys leading to it
+    open: usize,       // byte after its '{'
+    members: Vec<Member>,
+}
+
+/// Walks JSON text, remembering where the value of each key of the objects is
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    spans: Vec<(Vec<String>, Range<usize>)>, // path of the keys, span of the value
+    objects: Vec<Object>,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.skip_whitespace();
+        (self.peek() == Some(byte)).then(|| self.pos += 1)
+    }
+
+    fn value(&mut self, path: &mut Vec<String>) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.object(path),
+            b'[' => self.array(path),
+            b'"' => self.string().map(|_| ()),
+            _ => {
+                // Numbers, booleans and null
+                let start = self.pos;
+                while self
+                    .peek()
+                    .map_or(false, |b| !b.is_ascii_whitespace() && !b",]}".contains(&b))
+                {
+                    self.pos += 1;
+                }
+                (self.pos > start).then_some(())
+            }
+        }
+    }
+
+    fn object(&mut self, path: &mut Vec<String>) -> Option<()> {
+        self.expect(b'{')?;
+        let mut object = Object {
+            path: path.clone(),
+            open: self.pos,
+            members: Vec::new(),
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == b'}' {
+                break;
+            }
+            let key_span = self.string()?;
+            let key: String = serde_json::from_slice(&self.bytes[key_span.clone()]).ok()?;
+            self.expect(b':')?;
+            self.skip_whitespace();
+
+            let start = self.pos;
+            path.push(key.clone());
+            self.value(path)?;
+            self.spans.push((path.clone(), start..self.pos));
+            path.pop();
+            object.members.push(Member {
+                key,
+                start: key_span.start,
+                end: self.pos,
+            });
+
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => break,
+                _ => return None,
+            }
+        }
+
+        self.pos += 1;
+        self.objects.push(object);
+        Some(())
+    }
+
+    /// The values in arrays are not remembered
+    fn array(&mut self, path: &mut Vec<String>) -> Option<()> {
+        self.expect(b'[')?;
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == b']' {
+                break;
+            }
+            self.value(path)?;
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => break,
+                _ => return None,
+            }
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    /// Return the span of the string, quotes included
+    fn string(&mut self) -> Option<Range<usize>> {
+        let start = self.pos;
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.pos += 1;
+        loop {
+            match self.peek()? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(start..self.pos);
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+/// A difference between the text and the new values
+enum Change<'a> {
+    Replace(Vec<String>, &'a Value),
+    Insert(Vec<String>, &'a str, Value), // object, key, value
+    Remove(Vec<String>, &'a str),        // object, key
+}
+
+/// Return 'text' with the values that differ from the ones in 'new' replaced, leaving
+/// the rest of it, comments included, untouched. Keys of 'text' that are not in 'new'
+/// are removed, and keys of 'new' that are not in 'text' are added at the end of their
+/// object, unless their value is the one in 'defaults'. The objects at 'maps' (keys
+/// joined with dots) are maps rather than `struct`s: they are added whole, even the
+/// members that have their default value. Return [`None`] if 'text' is not valid JSON.
+pub fn update(text: &str, new: &Value, defaults: &Value, maps: &[&str]) -> Option<String> {
+    let json = strip_comments(text);
+    let old: Value = serde_json::from_str(&json).ok()?;
+    let mut scanner = Scanner {
+        bytes: json.as_bytes(),
+        pos: 0,
+        spans: Vec::new(),
+        objects: Vec::new(),
+    };
+    scanner.value(&mut Vec::new())?;
+
+    let mut changes = Vec::new();
+    find_changes(
+        &old,
+        new,
+        Some(defaults),
+        maps,
+        &mut Vec::new(),
+        &mut changes,
+    );
+
+    // Position and text of each edit
+    let mut edits = Vec::new();
+    let mut filled = Vec::new(); // empty objects where a key was already added
+    let mut removed: Vec<(&Object, Vec<&str>)> = Vec::new();
+    for change in changes {
+        match change {
+            Change::Replace(path, value) => {
+                let (_, span) = scanner.spans.iter().find(|(keys, _)| *keys == path)?;
+                edits.push((span.clone(), to_inline(value)));
+            }
+            Change::Remove(path, key) => {
+                let object = scanner.objects.iter().find(|object| object.path == path)?;
+                match removed
+                    .iter_mut()
+                    .find(|(other, _)| std::ptr::eq(*other, object))
+                {
+                    Some((_, keys)) => keys.push(key),
+                    None => removed.push((object, vec![key])),
+                }
+            }
+            Change::Insert(path, key, value) => {
+                let object = scanner.objects.iter().find(|object| object.path == path)?;
+                let member = format!("{}: {}", Value::from(key), to_inline(&value));
+                let (pos, separator) = match object.members.last() {
+                    Some(last) => (last.end, ","),
+                    None if filled.contains(&object.open) => (object.open, ","),
+                    None => {
+                        filled.push(object.open);
+                        (object.open, "")
+                    }
+                };
+
+                // On a new line, unless the object fits in one line
+                let line_start = text[..pos].rfind('\n').map_or(0, |idx| idx + 1);
+                let edit = if object.open > line_start {
+                    // No space after the '{' of an empty object
+                    match separator {
+                        "" => member,
+                        _ => format!("{} {}", separator, member),
+                    }
+                } else {
+                    let indent: String = text[line_start..]
+                        .chars()
+                        .take_while(|ch| *ch == ' ' || *ch == '\t')
+                        .collect();
+                    format!("{}\n{}{}", separator, indent, member)
+                };
+                edits.push((pos..pos, edit));
+            }
+        }
+    }
+
+    for (object, keys) in removed {
+        edits.extend(
+            removals(text, object, &keys)
+                .into_iter()
+                .map(|span| (span, String::new())),
+        );
+    }
+
+    // From the end, so that the spans before are still valid. Insertions at the same
+    // place are made in reverse, to keep their order, and after the removal starting
+    // there.
+    edits.sort_by_key(|(span, _)| (span.start, span.end));
+    let mut text = text.to_string();
+    for (span, edit) in edits.into_iter().rev() {
+        text.replace_range(span, &edit);
+    }
+    Some(text)
+}
+
+/// The spans of the text to remove, so that the members of 'object' with 'keys' are
+/// removed along with their commas. A member on its own line is removed with its line.
+/// At least one member of 'object' is kept.
+fn removals(text: &str, object: &Object, keys: &[&str]) -> Vec<Range<usize>> {
+    let line_start = |pos: usize| text[..pos].rfind('\n').map_or(0, |idx| idx + 1);
+    let starts_line = |pos: usize| text[line_start(pos)..pos].trim().is_empty();
+    let is_removed = |member: &Member| keys.contains(&member.key.as_str());
+
+    let mut spans = Vec::new();
+    let members = &object.members;
+    let mut idx = 0;
+    while idx < members.len() {
+        if !is_removed(&members[idx]) {
+            idx += 1;
+            continue;
+        }
+        let first = idx;
+        while idx < members.len() && is_removed(&members[idx]) {
+            idx += 1;
+        }
+
+        // Up to the next member that is kept, or else from the end of the previous one
+        match (members.get(idx), first.checked_sub(1)) {
+            (Some(next), _) if starts_line(members[first].start) && starts_line(next.start) => {
+                spans.push(line_start(members[first].start)..line_start(next.start))
+            }
+            (Some(next), _) => spans.push(members[first].start..next.start),
+            (None, Some(previous)) => spans.push(members[previous].end..members[idx - 1].end),
+            (None, None) => (),
+        }
+    }
+    spans
+}
+
+/// Collect the values of 'new' that differ from the ones of 'old', entering the
+/// objects that are in both. An object whose keys all changed is replaced whole.
+fn find_changes<'a>(
+    old: &'a Value,
+    new: &'a Value,
+    defaults: Option<&Value>,
+    maps: &[&str],
+    path: &mut Vec<String>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    match (old, new) {
+        (Value::Object(old_object), Value::Object(new_object))
+            if old_object.is_empty()
+                || old_object.keys().any(|key| new_object.contains_key(key)) =>
+        {
+            for key in old_object
+                .keys()
+                .filter(|key| !new_object.contains_key(*key))
+            {
+                changes.push(Change::Remove(path.clone(), key));
+            }
+            for (key, new) in new_object {
+                let default = defaults.and_then(|defaults| defaults.get(key));
+                path.push(key.clone());
+                match old_object.get(key) {
+                    // The members of the maps have no defaults of their own
+                    Some(old) if maps.contains(&path.join(".").as_str()) => {
+                        find_changes(old, new, None, maps, path, changes)
+                    }
+                    Some(old) => find_changes(old, new, default, maps, path, changes),
+                    None if default != Some(new) => {
+                        let value = match default {
+                            Some(default) => without_defaults(new, default, maps, path),
+                            None => new.clone(),
+                        };
+                        changes.push(Change::Insert(path[..path.len() - 1].to_vec(), key, value))
+                    }
+                    None => (),
+                }
+                path.pop();
+            }
+        }
+        _ if old != new => changes.push(Change::Replace(path.clone(), new)),
+        _ => (),
+    }
+}
+
+/// Return 'value', at 'path', without the members of its objects that have their
+/// default value. The objects at 'maps' are kept whole.
+fn without_defaults(
+    value: &Value,
+    default: &Value,
+    maps: &[&str],
+    path: &mut Vec<String>,
+) -> Value {
+    match (value, default) {
+        (Value::Object(_), _) if maps.contains(&path.join(".").as_str()) => value.clone(),
+        (Value::Object(object), Value::Object(default)) => Value::Object(
+            object
+                .iter()
+                .filter(|(key, value)| default.get(*key) != Some(*value))
+                .map(|(key, value)| match default.get(key) {
+                    Some(default) => {
+                        path.push(key.clone());
+                        let value = without_defaults(value, default, maps, path);
+                        path.pop();
+                        (key.clone(), value)
+                    }
+                    None => (key.clone(), value.clone()),
+                })
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Write 'value' on one line, with a space after each ',' and ':'
+fn to_inline(value: &Value) -> String {
+    match value {
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(to_inline).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Object(object) => {
+            let members: Vec<String> = object
+                .iter()
+                .map(|(key, value)| format!("{}: {}", Value::from(key.as_str()), to_inline(value)))
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SETTINGS: &str = r#"{
+    // Size of the code
+    "code_font_size": 20.0, /* in points */
+    "theme": "dark",
+    "log": {
+        "enabled": true,
+        "level": "warning", // the least severe
+    },
+}
+"#;
+
+    #[test]
+    fn strip_comments_keeps_the_offsets() {
+        let json = strip_comments(SETTINGS);
+        assert_eq!(json.len(), SETTINGS.len());
+        assert_eq!(json.lines().count(), SETTINGS.lines().count());
+        assert!(!json.contains("//") && !json.contains("/*"));
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["log"]["level"], "warning");
+        // The values start where they did
+        assert_eq!(json.find("20.0"), SETTINGS.find("20.0"));
+    }
+
+    #[test]
+    fn strip_comments_leaves_the_strings() {
+        let text = r#"{"url": "https://example.com/*x*/", "quote": "a \"//\" b",}"#;
+        let value: Value = serde_json::from_str(&strip_comments(text)).unwrap();
+        assert_eq!(value["url"], "https://example.com/*x*/");
+        assert_eq!(value["quote"], "a \"//\" b");
+    }
+
+    #[test]
+    fn strip_comments_blanks_an_unterminated_block_comment() {
+        assert_eq!(strip_comments("[1] /* no end\n"), "[1]          \n");
+    }
+
+    #[test]
+    fn update_replaces_values_in_place() {
+        let mut new: Value = serde_json::from_str(&strip_comments(SETTINGS)).unwrap();
+        new["code_font_size"] = json!(14.0);
+        new["log"]["level"] = json!("debug");
+        let updated = update(SETTINGS, &new, &json!({}), &[]).unwrap();
+        assert_eq!(
+            updated,
+            SETTINGS
+                .replace("20.0", "14.0")
+                .replace(r#""warning""#, r#""debug""#)
+        );
+    }
+
+    #[test]
+    fn update_without_changes_keeps_the_text() {
+        let old: Value = serde_json::from_str(&strip_comments(SETTINGS)).unwrap();
+        assert_eq!(update(SETTINGS, &old, &json!({}), &[]).unwrap(), SETTINGS);
+    }
+
+    #[test]
+    fn update_adds_the_missing_keys_with_the_indentation() {
+        let mut new: Value = serde_json::from_str(&strip_comments(SETTINGS)).unwrap();
+        new["log"]["file"] = json!("colors.log");
+        new["ui_scale"] = json!(1.0); // the default, so not added
+        let defaults = json!({"ui_scale": 1.0});
+        let updated = update(SETTINGS, &new, &defaults, &[]).unwrap();
+        assert_eq!(
+            updated,
+            SETTINGS.replace(
+                r#""level": "warning","#,
+                "\"level\": \"warning\",\n        \"file\": \"colors.log\","
+            )
+        );
+    }
+
+    #[test]
+    fn update_fills_empty_and_inline_objects() {
+        let text = "{\n    \"env\": {},\n    \"style\": {\"a\": 1}\n}";
+        let new = json!({"env": {"A": "1", "B": "2"}, "style": {"a": 1, "b": [1, 2]}});
+        assert_eq!(
+            update(text, &new, &json!({}), &[]).unwrap(),
+            "{\n    \"env\": {\"A\": \"1\", \"B\": \"2\"},\n    \"style\": {\"a\": 1, \"b\": [1, 2]}\n}"
+        );
+    }
+
+    #[test]
+    fn update_removes_the_keys_with_their_lines_and_commas() {
+        let text = "{\n    \"env\": {\n        \"A\": \"1\", // first\n        \"B\": \"2\",\n        \"C\": \"3\"\n    },\n    \"args\": {\"x\": 1, \"y\": 2, \"z\": 3}\n}";
+        let new = json!({"env": {"B": "2"}, "args": {"x": 1}});
+        assert_eq!(
+            update(text, &new, &json!({}), &[]).unwrap(),
+            "{\n    \"env\": {\n        \"B\": \"2\"\n    },\n    \"args\": {\"x\": 1}\n}"
+        );
+    }
+
+    #[test]
+    fn update_replaces_an_object_whose_keys_all_changed() {
+        let text = "{\n    \"env\": {\n        \"A\": \"1\"\n    },\n    \"args\": {\"x\": 1}\n}";
+        let new = json!({"env": {"B": "2"}, "args": {}});
+        assert_eq!(
+            update(text, &new, &json!({}), &[]).unwrap(),
+            "{\n    \"env\": {\"B\": \"2\"},\n    \"args\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn update_adds_the_maps_whole() {
+        let defaults = json!({
+            "file_types": {"txt": {"run": ""}, "md": {"run": ""}},
+            "log": {"level": "warning", "modules": {"ui": "debug"}},
+        });
+        let mut new = defaults.clone();
+        new["file_types"]["betty"] = json!({"run": "betty"});
+        new["log"]["modules"]["lsp"] = json!("info");
+        let maps = ["file_types", "log.modules"];
+        assert_eq!(
+            update("{}", &new, &defaults, &maps).unwrap(),
+            concat!(
+                r#"{"file_types": {"betty": {"run": "betty"}, "md": {"run": ""}, "txt": {"run": ""}}, "#,
+                r#""log": {"modules": {"lsp": "info", "ui": "debug"}}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn update_rejects_invalid_json() {
+        assert_eq!(update("{\"a\": }", &json!({"a": 1}), &json!({}), &[]), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Linting of the saved file with the `linter` command of its file type, run in the
+//! background. The linter prints the problems it finds as `file:line: message` or
+//! `file:line:col: message`, which are marked in the editor and listed in the Problems
+//! page of the console.
+
+use std::io;
+use std::ops::Range;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use colors_highlight::Position;
+
+use super::parser::{Diagnostic, Severity};
+
+/// A linter running in the background
+pub struct Lint {
+    /// Output of the linter, stdout then stderr, once it exits
+    receiver: Receiver<io::Result<String>>,
+    program: String,
+}
+
+impl Lint {
+    pub fn start(mut command: Command) -> Self {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let output = command.output().map(|output| {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                text
+            });
+            let _ = sender.send(output);
+        });
+        Self { receiver, program }
+    }
+
+    /// Name of the linter, as started
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// The output of the linter, if it exited
+    pub fn poll(&self) -> Option<io::Result<String>> {
+        match self.receiver.try_recv() {
+            Ok(output) => Some(output),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the linter stopped unexpectedly",
+            ))),
+        }
+    }
+}
+
+/// The problems of 'code' (the file 'file_name') reported in 'output'. Lines of the
+/// output about other files, or without a line number, are ignored. The problems whose
+/// message starts with `warning` are warnings, and the others errors.
+pub fn parse(output: &str, file_name: &str, code: &str) -> Vec<Diagnostic> {
+    let prefix = format!("{}:", file_name);
+    output
+        .lines()
+        .filter_map(|line| {
+            let idx = line.find(&prefix)?;
+            let (number, rest) = leading_number(&line[idx + prefix.len()..])?;
+            let (col, rest) = match rest.strip_prefix(':').and_then(leading_number) {
+                Some((col, rest)) => (Some(col), rest),
+                None => (None, rest),
+            };
+            let message = rest.trim_start_matches(':').trim();
+            let severity = if message.to_lowercase().starts_with("warning") {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            let (span, position) = problem_span(code, number, col)?;
+            Some(Diagnostic {
+                span,
+                position,
+                message: message.to_string(),
+                severity,
+            })
+        })
+        .collect()
+}
+
+/// The number at the start of 'text', and the text after it
+fn leading_number(text: &str) -> Option<(usize, &str)> {
+    let len = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    Some((text[..len].parse().ok()?, &text[len..]))
+}
+
+/// The bytes of 'code' a problem on the line 'number' is about, and where they start:
+/// the word at the column 'col' (both from 1), or the whole line without its
+/// indentation
+fn problem_span(code: &str, number: usize, col: Option<usize>) -> Option<(Range<usize>, Position)> {
+    let line_start: usize = code
+        .split('\n')
+        .take(number.checked_sub(1)?)
+        .map(|line| line.len() + 1)
+        .sum();
+    let line = code.get(line_start..)?.split('\n').next()?;
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let start_col = match col {
+        Some(col) => col.saturating_sub(1),
+        None => line.chars().take_while(|c| c.is_whitespace()).count(),
+    };
+    let start = line
+        .char_indices()
+        .nth(start_col)
+        .map_or(line.len(), |(idx, _)| idx);
+    let end = match col {
+        Some(_) => line[start..]
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .map_or(line.len(), |len| start + len),
+        None => line.trim_end().len(),
+    };
+    let position = Position {
+        line: number - 1,
+        col: start_col,
+    };
+    // Problems at the end of a line or on a symbol are shown on one character
+    let span = line_start + start..(line_start + end).max(line_start + start + 1);
+    Some((
+        span.start.min(code.len())..span.end.min(code.len()),
+        position,
+    ))
+}
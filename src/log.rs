@@ -1,56 +1,360 @@
 //! Little logging library in case the deserialization of `settings.json` fails and other scenarios.
+//! The records are [`tracing`] events, filtered by module with [`Targets`] and written
+//! to the log file by the `fmt` layer of [`tracing_subscriber`], which also logs how long
+//! the spans around slow operations (such as opening, saving or running a file) lasted.
+//! The macros below are shortcuts for the events, keeping the module that logs them.
 
-use std::fmt;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fmt::{self, Write as _};
 use std::fs;
-use std::io::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use serde_derive::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, Layer, Registry};
+
+use super::paths;
+use super::settings::{LogSettings, Settings};
 
 /// The format used by [`chrono`] to convert a [`chrono::DateTime`] to a [`String`].
 const DATETIME_LOG_FORMAT: &str = "%Y-%m-%d %H:%M:%S:%3f";
 
-/// Path to the log file
-const LOG_PATH: &str = "log\\log.log";
+/// Target of the header of the sessions, which is logged whatever the level
+const SESSION_TARGET: &str = "colors::session";
 
-/// Log level
-#[derive(Debug)]
-enum Level {
+/// Log level, from the least to the most severe
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    Info,
     Warning,
     Critical,
 }
 
+impl From<&tracing::Level> for Level {
+    fn from(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::ERROR => Self::Critical,
+            tracing::Level::WARN => Self::Warning,
+            tracing::Level::INFO => Self::Info,
+            _ => Self::Debug,
+        }
+    }
+}
+
+impl From<Level> for LevelFilter {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Debug => Self::DEBUG,
+            Level::Info => Self::INFO,
+            Level::Warning => Self::WARN,
+            Level::Critical => Self::ERROR,
+        }
+    }
+}
+
+/// How the records are written: as lines of text, or as one JSON object per line
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Text,
+    Json,
+}
+
+/// A record in the JSON format
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    session: &'a str,
+    level: Level,
+    module: &'a str,
+    message: &'a str,
+}
+
+/// The layer writing the records, replaced when the settings change
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+static RELOAD: Mutex<Option<reload::Handle<BoxedLayer, Registry>>> = Mutex::new(None);
+
+/// ID of the run of the IDE, telling apart the records of the runs in the same file
+static SESSION: Mutex<String> = Mutex::new(String::new());
+
+/// When the logging was initialized, which is the first thing the IDE does
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Send the records of the whole program to the log file. Must be called before
+/// anything is logged. Until the settings are read, the warnings and critical errors
+/// are logged to the default file.
+pub fn init() {
+    *lock(&STARTED) = Some(Instant::now());
+    *lock(&SESSION) = new_session_id();
+    let (layer, handle) = reload::Layer::new(layer(&LogSettings::default()));
+    // Ignore error (because there is already a subscriber to log with)
+    if tracing::subscriber::set_global_default(Registry::default().with(layer)).is_ok() {
+        *lock(&RELOAD) = Some(handle);
+    }
+}
+
+/// Log as given by the settings from now on
+pub fn configure(settings: &LogSettings) {
+    if let Some(handle) = lock(&RELOAD).as_ref() {
+        // Ignore error (because the subscriber is never dropped)
+        let _ = handle.reload(layer(settings));
+    }
+}
+
+/// The layer filtering the records as given by 'settings' and writing them to the
+/// log file
+fn layer(settings: &LogSettings) -> BoxedLayer {
+    // Without targets, nothing is logged
+    if !settings.enabled {
+        return Targets::new().boxed();
+    }
+    let mut targets = Targets::new()
+        .with_default(settings.level)
+        .with_target(SESSION_TARGET, LevelFilter::INFO);
+    for (module, &level) in &settings.modules {
+        // The modules of the IDE are named without the name of the crate
+        targets = targets
+            .with_target(module.as_str(), level)
+            .with_target(format!("colors::{}", module), level);
+    }
+
+    let path = settings
+        .file
+        .as_ref()
+        .filter(|file| !file.is_empty())
+        .map_or_else(paths::log_file, PathBuf::from);
+    // Ignore errors (because we couldn't log them anywhere else)
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir) as Result<_, _>;
+    }
+    let file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .ok()
+        .map(|file| format_layer(settings.format, Mutex::new(file)));
+
+    // Debug builds have a console window, where the records show up right away
+    let stderr = cfg!(debug_assertions).then(|| format_layer(settings.format, io::stderr));
+    targets.and_then(file).and_then(stderr).boxed()
+}
+
+/// The layer writing the records to 'writer' in 'format', with the spans when they end
+fn format_layer<W>(format: Format, writer: W) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let session = lock(&SESSION).clone();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_span_events(FmtSpan::CLOSE);
+    match format {
+        Format::Text => layer.with_timer(TextTime { session }).boxed(),
+        Format::Json => layer.event_format(JsonFormat { session }).boxed(),
+    }
+}
+
+/// Write the header of the session, telling the version of the IDE, the system it
+/// runs on and the main 'settings'. It is written whatever the level of the log.
+pub fn start_session(settings: &Settings) {
+    tracing::info!(
+        target: SESSION_TARGET,
+        "Session started: colors {} on {} ({}), settings '{}', theme {}, betty '{}'{}",
+        env!("CARGO_PKG_VERSION"),
+        env::consts::OS,
+        env::consts::ARCH,
+        paths::settings_file().display(),
+        settings.theme.as_deref().unwrap_or("custom"),
+        settings.betty_exe_path,
+        if paths::is_portable() { ", portable" } else { "" }
+    );
+}
+
+/// A short random ID, telling apart the records of the runs of the IDE
+fn new_session_id() -> String {
+    let hash = RandomState::new().build_hasher().finish();
+    format!("{:08x}", hash as u32)
+}
+
+/// Milliseconds since the logging was initialized
+pub fn since_start() -> f32 {
+    lock(&STARTED).map_or(0.0, |started| started.elapsed().as_secs_f32() * 1000.0)
+}
+
 impl fmt::Display for Level {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
-/// Main function for logging a message
-fn log(msg: impl Into<String>, level: Level) {
-    let msg = format!(
-        "{} {}: {}\n",
-        today().format(DATETIME_LOG_FORMAT),
-        level,
-        msg.into()
-    );
+/// The module named by the 'target' of an event, without the name of the crate if it
+/// is the IDE, e.g. `ui` or `eframe::native::run`
+fn target_module(target: &str) -> &str {
+    match target.strip_prefix("colors") {
+        Some("") => "main",
+        Some(module) => module.strip_prefix("::").unwrap_or(target),
+        None => target,
+    }
+}
 
-    // Ignore error (because we couldn't log it anywhere else)
-    let Ok(mut file) = fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(LOG_PATH) else { return; };
+/// The time of the records in the text format, followed by the session
+struct TextTime {
+    session: String,
+}
+
+impl FormatTime for TextTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(w, "{} [{}]", today().format(DATETIME_LOG_FORMAT), self.session)
+    }
+}
+
+/// Writes each record as a JSON [`Record`] on its own line
+struct JsonFormat {
+    session: String,
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        // The spans the event is in, e.g. `open{path=...}` for the end of a span
+        let mut message = String::new();
+        for span in ctx.event_scope().into_iter().flat_map(|scope| scope.from_root()) {
+            message.push_str(span.name());
+            if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                if !fields.is_empty() {
+                    write!(message, "{{{}}}", fields)?;
+                }
+            }
+            message.push_str(": ");
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        write!(message, "{}", fields)?;
+
+        let metadata = event.metadata();
+        let record = Record {
+            timestamp: today().to_rfc3339(),
+            session: &self.session,
+            level: Level::from(metadata.level()),
+            module: target_module(metadata.target()),
+            message: &message,
+        };
+        let json = serde_json::to_string(&record).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{}", json)
+    }
+}
+
+/// The fields of an event, as text
+#[derive(Default)]
+struct Fields {
+    message: String,
+    others: String, // the other fields, as `name=value` separated by spaces
+}
+
+impl Visit for Fields {
+    /// The end of a span is told by a message that is a string, not format arguments
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => {
+                if !self.others.is_empty() {
+                    self.others.push(' ');
+                }
+                let _ = write!(self.others, "{}={:?}", name, value);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.message.is_empty(), self.others.is_empty()) {
+            (_, true) => write!(f, "{}", self.message),
+            (true, false) => write!(f, "{}", self.others),
+            (false, false) => write!(f, "{} ({})", self.message, self.others),
+        }
+    }
+}
+
+/// Debug event
+macro_rules! debug {
+    ($msg:expr) => {
+        tracing::debug!("{}", $msg)
+    };
+}
+pub(crate) use debug;
+
+/// Info event
+macro_rules! info {
+    ($msg:expr) => {
+        tracing::info!("{}", $msg)
+    };
+}
+pub(crate) use info;
 
-    // Ignore error (because we couldn't log it anywhere else)
-    let _ = file.write_all(msg.as_bytes()) as Result<_, _>;
+/// Info event telling that the startup reached 'milestone', and how long after the
+/// IDE started
+macro_rules! milestone {
+    ($milestone:expr) => {
+        tracing::info!(
+            "Startup: {} after {:.0}ms",
+            $milestone,
+            $crate::log::since_start()
+        )
+    };
 }
+pub(crate) use milestone;
 
-/// Helper for [`log`] with warning level
-pub fn warning(msg: impl Into<String>) {
-    log(msg, Level::Warning)
+/// Warning event
+macro_rules! warning {
+    ($msg:expr) => {
+        tracing::warn!("{}", $msg)
+    };
 }
+pub(crate) use warning;
 
-/// Helper for [`log`] with critical level
-pub fn critical(msg: impl Into<String>) {
-    log(msg, Level::Critical)
+/// Critical event
+macro_rules! critical {
+    ($msg:expr) => {
+        tracing::error!("{}", $msg)
+    };
 }
+pub(crate) use critical;
 
 /// Return today date as [`chrono::DateTime`]
 fn today() -> chrono::DateTime<chrono::Local> {
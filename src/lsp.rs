@@ -0,0 +1,659 @@
+//! Client of the Language Server Protocol, talking to a language server started with
+//! the `language_server` command of the file type of the open file, over its stdin and
+//! stdout. The server checks the file as it is edited, and gives the hover text and
+//! the completions at a position. Only the open file is sent to the server, whole at
+//! each change. The messages are written in the background, so that a server slow to
+//! read them does not block the editor.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use colors_highlight::Position;
+use eframe::egui;
+use serde_json::{json, Value};
+
+use super::log;
+use super::parser::{Diagnostic, Severity};
+use super::runner;
+
+/// How long a server has to answer the shutdown request, and then to exit, before it is
+/// killed
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What the server sent, for the editor
+pub enum Event {
+    /// The problems in the open file, replacing the previous ones
+    Diagnostics(Vec<Diagnostic>),
+
+    /// The text explaining the code at the position given to [`LanguageServer::hover`]
+    Hover(String),
+
+    /// The completions at the position given to [`LanguageServer::complete`]
+    Completion(Vec<Completion>),
+}
+
+pub struct Completion {
+    pub label: String,
+    pub insert_text: String,
+}
+
+/// Requests waiting for their response, to know what the response holds
+enum Request {
+    Initialize,
+    Hover,
+    Completion,
+    Shutdown,
+}
+
+pub struct LanguageServer {
+    /// Command line of the server, to know whether another file needs another server
+    command: String,
+
+    child: Option<Child>, // taken when the server is dropped
+
+    /// Messages for the server, written in the background
+    writer: Sender<Value>,
+
+    /// Messages of the server, read in the background
+    messages: Receiver<Value>,
+
+    /// Requests sent, by ID
+    pending: HashMap<u64, Request>,
+    next_id: u64,
+
+    /// Notifications to send once the server is initialized
+    queue: Vec<Value>,
+    initialized: bool,
+
+    /// The open file, its contents and their version
+    document: Option<(PathBuf, String)>,
+    version: i64,
+}
+
+impl LanguageServer {
+    /// Start the server with the command line 'command', for the files in the folder
+    /// 'root'. 'ctx' is repainted when the server sends something.
+    pub fn start(command: &str, root: &Path, ctx: egui::Context) -> io::Result<Self> {
+        let args = runner::split_args(command);
+        let Some((program, args)) = args.split_first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the command of the language server is empty",
+            ));
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let messages = read_in_background(stdout, ctx);
+
+        let mut server = Self {
+            command: command.to_string(),
+            child: Some(child),
+            writer: write_in_background(stdin),
+            messages,
+            pending: HashMap::new(),
+            next_id: 0,
+            queue: Vec::new(),
+            initialized: false,
+            document: None,
+            version: 0,
+        };
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": path_to_uri(root),
+            "capabilities": {
+                "textDocument": {
+                    "publishDiagnostics": {},
+                    "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                    "synchronization": { "didSave": true },
+                },
+            },
+            "clientInfo": { "name": "colors", "version": env!("CARGO_PKG_VERSION") },
+        });
+        server.request(Request::Initialize, "initialize", params)?;
+        Ok(server)
+    }
+
+    /// The open file, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.document.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// The command line the server was started with
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Tell the server that the file in 'path' with 'contents', written in the language
+    /// 'language_id', is open, instead of the one open until now
+    pub fn open(&mut self, path: &Path, language_id: &str, contents: &str) -> io::Result<()> {
+        self.close()?;
+        self.version = 1;
+        self.document = Some((path.to_path_buf(), contents.to_string()));
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": path_to_uri(path),
+                    "languageId": language_id,
+                    "version": self.version,
+                    "text": contents,
+                },
+            }),
+        )
+    }
+
+    /// Tell the server that the open file now holds 'contents'
+    pub fn change(&mut self, contents: &str) -> io::Result<()> {
+        let Some((path, text)) = &mut self.document else {
+            return Ok(());
+        };
+        if text == contents {
+            return Ok(());
+        }
+        contents.clone_into(text);
+        self.version += 1;
+        let uri = path_to_uri(path);
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": self.version },
+                "contentChanges": [{ "text": contents }],
+            }),
+        )
+    }
+
+    /// Tell the server that the open file was saved
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some((path, _)) = &self.document else {
+            return Ok(());
+        };
+        let uri = path_to_uri(path);
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+    }
+
+    /// Tell the server that the open file is closed
+    fn close(&mut self) -> io::Result<()> {
+        let Some((path, _)) = self.document.take() else {
+            return Ok(());
+        };
+        let uri = path_to_uri(&path);
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+    }
+
+    /// Ask for the text explaining the code at 'byte_index' of the open file
+    pub fn hover(&mut self, byte_index: usize) -> io::Result<()> {
+        let Some(params) = self.position_params(byte_index) else {
+            return Ok(());
+        };
+        self.request(Request::Hover, "textDocument/hover", params)
+    }
+
+    /// Ask for the completions at 'byte_index' of the open file
+    pub fn complete(&mut self, byte_index: usize) -> io::Result<()> {
+        let Some(params) = self.position_params(byte_index) else {
+            return Ok(());
+        };
+        self.request(Request::Completion, "textDocument/completion", params)
+    }
+
+    /// What the server sent since the last call
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Ok(message) = self.messages.try_recv() {
+            if let Err(err) = self.handle(message, &mut events) {
+                log::warning!(format!("The language server could not be told: {}", err));
+            }
+        }
+        events
+    }
+
+    fn handle(&mut self, message: Value, events: &mut Vec<Event>) -> io::Result<()> {
+        // A response
+        if let Some(id) = message["id"]
+            .as_u64()
+            .filter(|_| message["method"].is_null())
+        {
+            if let Some(error) = message.get("error") {
+                log::warning!(format!(
+                    "The language server failed: {}",
+                    error["message"].as_str().unwrap_or_default()
+                ));
+            }
+            let result = &message["result"];
+            match self.pending.remove(&id) {
+                Some(Request::Initialize) => {
+                    self.initialized = true;
+                    self.send(&json!({
+                        "jsonrpc": "2.0",
+                        "method": "initialized",
+                        "params": {},
+                    }))?;
+                    for message in std::mem::take(&mut self.queue) {
+                        self.send(&message)?;
+                    }
+                }
+                Some(Request::Hover) => {
+                    let text = hover_text(&result["contents"]);
+                    if !text.trim().is_empty() {
+                        events.push(Event::Hover(text));
+                    }
+                }
+                Some(Request::Completion) => {
+                    // Either a list of items, or an object holding them
+                    let items = result
+                        .as_array()
+                        .or_else(|| result["items"].as_array())
+                        .map_or(&[][..], Vec::as_slice);
+                    events.push(Event::Completion(
+                        items.iter().filter_map(completion).collect(),
+                    ));
+                }
+                Some(Request::Shutdown) | None => (),
+            }
+            return Ok(());
+        }
+
+        match message["method"].as_str() {
+            Some("textDocument/publishDiagnostics") => {
+                let params = &message["params"];
+                let Some((path, text)) = &self.document else {
+                    return Ok(());
+                };
+                if params["uri"].as_str() != Some(&path_to_uri(path)) {
+                    return Ok(());
+                }
+                let diagnostics = params["diagnostics"]
+                    .as_array()
+                    .map_or(&[][..], Vec::as_slice)
+                    .iter()
+                    .filter_map(|diagnostic| to_diagnostic(diagnostic, text))
+                    .collect();
+                events.push(Event::Diagnostics(diagnostics));
+            }
+            // Requests of the server, which the editor does not support
+            Some(_) if !message["id"].is_null() => {
+                self.send(&json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "error": { "code": -32601, "message": "method not supported" },
+                }))?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Parameters of the requests about 'byte_index' of the open file
+    fn position_params(&self, byte_index: usize) -> Option<Value> {
+        let (path, text) = self.document.as_ref()?;
+        Some(json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": lsp_position(text, byte_index),
+        }))
+    }
+
+    fn request(&mut self, request: Request, method: &str, params: Value) -> io::Result<()> {
+        // Nothing but the initialization can be asked before it is done
+        if !self.initialized && !matches!(request, Request::Initialize) {
+            return Ok(());
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, request);
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        if self.initialized {
+            self.send(&message)
+        } else {
+            self.queue.push(message);
+            Ok(())
+        }
+    }
+
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        self.writer
+            .send(message.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the language server exited"))
+    }
+}
+
+impl Drop for LanguageServer {
+    /// Ask the server to shut down and, once it answered, to exit. The answer is waited
+    /// for in the background, and the server is killed if it does not answer or exit.
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        let id = self.next_id;
+        let asked = self.initialized
+            && self
+                .request(Request::Shutdown, "shutdown", Value::Null)
+                .is_ok();
+        if !asked {
+            let _ = child.kill();
+            reap(child);
+            return;
+        }
+
+        let messages = std::mem::replace(&mut self.messages, mpsc::channel().1);
+        let writer = self.writer.clone();
+        thread::spawn(move || {
+            let deadline = Instant::now() + EXIT_TIMEOUT;
+            let answered = loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                match messages.recv_timeout(left) {
+                    Ok(message)
+                        if message["id"].as_u64() == Some(id) && message["method"].is_null() =>
+                    {
+                        break true
+                    }
+                    Ok(_) => (),
+                    Err(_) => break false,
+                }
+            };
+            let exit = json!({ "jsonrpc": "2.0", "method": "exit", "params": null });
+            if !answered || writer.send(exit).is_err() {
+                let _ = child.kill();
+            }
+            reap(child);
+        });
+    }
+}
+
+/// The language of the file at 'path', as told to the server: the name of its 'syntax'
+/// (e.g. `betty` or `python`), or its extension if it is highlighted as plain text
+pub fn language_id(path: &Path, syntax: &str) -> String {
+    match syntax {
+        "plain" => path.extension().map_or_else(
+            || "plaintext".into(),
+            |ext| ext.to_string_lossy().to_lowercase(),
+        ),
+        _ => syntax.to_lowercase(),
+    }
+}
+
+/// Kill 'child' if it has not exited in a while, once asked to, without waiting for it
+pub fn reap(mut child: Child) {
+    thread::spawn(move || {
+        thread::sleep(EXIT_TIMEOUT);
+        if let Ok(None) = child.try_wait() {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    });
+}
+
+/// Read the messages written to 'stdout' in a thread, repainting 'ctx' after each one
+pub fn read_in_background(stdout: ChildStdout, ctx: egui::Context) -> Receiver<Value> {
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+                // The program exited
+                Ok(None) => return,
+                Err(err) => {
+                    log::warning!(format!("An invalid message was received: {}", err));
+                    return;
+                }
+            }
+        }
+    });
+    messages
+}
+
+/// Write the messages sent to the returned [`Sender`] to 'stdin' in a thread, which ends
+/// when the [`Sender`] is dropped or the program exits
+fn write_in_background(mut stdin: ChildStdin) -> Sender<Value> {
+    let (sender, messages) = mpsc::channel::<Value>();
+    thread::spawn(move || {
+        for message in messages {
+            if let Err(err) = write_message(&mut stdin, &message) {
+                log::warning!(format!("The language server could not be told: {}", err));
+                return;
+            }
+        }
+    });
+    sender
+}
+
+/// Write 'message' to 'writer', after its length. The debug adapters use the same
+/// framing as the language servers.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Read the next message of the server in 'reader', or [`None`] if it exited
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        // An empty line ends the headers
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the message has no length",
+        ));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// The `file:` URI of 'path', which must be absolute
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    // Windows paths start with their drive
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The position of the protocol (line, and column in UTF-16 code units) of
+/// 'byte_index' in 'text'
+fn lsp_position(text: &str, byte_index: usize) -> Value {
+    let before = &text[..byte_index.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+/// The byte index and the position of the editor of the position of the protocol
+/// 'position' in 'text'
+fn text_position(text: &str, position: &Value) -> Option<(usize, Position)> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = if line == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or_default();
+
+    let mut units = 0;
+    let mut col = 0;
+    let mut index = line_start + line_text.len();
+    for (idx, ch) in line_text.char_indices() {
+        if units >= character {
+            index = line_start + idx;
+            break;
+        }
+        units += ch.len_utf16();
+        col += 1;
+    }
+    Some((index, Position { line, col }))
+}
+
+/// The problem reported by the server in 'diagnostic', about 'text'
+fn to_diagnostic(diagnostic: &Value, text: &str) -> Option<Diagnostic> {
+    let range = &diagnostic["range"];
+    let (start, position) = text_position(text, &range["start"])?;
+    let (end, _) = text_position(text, &range["end"])?;
+    // Errors, and the problems of unknown severity, are the ones that must be fixed
+    let severity = match diagnostic["severity"].as_u64() {
+        Some(1) | None => Severity::Error,
+        Some(_) => Severity::Warning,
+    };
+    Some(Diagnostic {
+        // Empty ranges are shown on the character they point to
+        span: start..end.max(start + 1).min(text.len()),
+        position,
+        message: diagnostic["message"].as_str()?.to_string(),
+        severity,
+    })
+}
+
+/// The text of the 'contents' of a hover, which may be a string, a marked string, a
+/// list of them or markup
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(hover_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(_) => contents["value"].as_str().unwrap_or_default().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// The completion in the completion item 'item'
+fn completion(item: &Value) -> Option<Completion> {
+    let label = item["label"].as_str()?.to_string();
+    let insert_text = item["textEdit"]["newText"]
+        .as_str()
+        .or_else(|| item["insertText"].as_str())
+        .unwrap_or(&label)
+        .to_string();
+    Some(Completion { label, insert_text })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_message_follows_the_framing() {
+        let body = r#"{"id":1,"result":"é"}"#;
+        let stream = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}\
+             content-length: 2\r\n\r\n{{}}",
+            body.len(),
+            body
+        );
+        let mut reader = stream.as_bytes();
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message["result"], "é");
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({})));
+        // The server exited
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn read_message_needs_a_length() {
+        let mut reader = "Content-Type: x\r\n\r\n{}".as_bytes();
+        let err = read_message(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_message_reads_what_write_message_wrote() {
+        let message = json!({"jsonrpc": "2.0", "method": "exit", "params": ["ü\r\n"]});
+        let mut stream = Vec::new();
+        write_message(&mut stream, &message).unwrap();
+        write_message(&mut stream, &message).unwrap();
+        let mut reader = stream.as_slice();
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let text = "a\n😀b = 1\n";
+        let index = text.find('b').unwrap();
+        let position = lsp_position(text, index);
+        assert_eq!(position, json!({"line": 1, "character": 2}));
+        let (back, position) = text_position(text, &position).unwrap();
+        assert_eq!(back, index);
+        assert_eq!((position.line, position.col), (1, 1));
+        // Past the end of the line
+        let (end, _) = text_position(text, &json!({"line": 0, "character": 9})).unwrap();
+        assert_eq!(end, 1);
+    }
+
+    #[test]
+    fn uris_escape_the_paths() {
+        assert_eq!(
+            path_to_uri(Path::new("/home/me/my code.betty")),
+            "file:///home/me/my%20code.betty"
+        );
+        assert_eq!(
+            path_to_uri(Path::new("C:\\code\\a.betty")),
+            "file:///C:/code/a.betty"
+        );
+    }
+
+    #[test]
+    fn language_id_of_the_syntax() {
+        assert_eq!(language_id(Path::new("a.betty"), "Betty"), "betty");
+        assert_eq!(language_id(Path::new("a.RS"), "plain"), "rs");
+        assert_eq!(language_id(Path::new("Makefile"), "plain"), "plaintext");
+    }
+}
@@ -0,0 +1,261 @@
+//! Macros editing the code. Keystroke macros record what is typed in the editor and
+//! play it back; scripts are Rhai files in the `scripts` folder of the user folder,
+//! which edit the code through a few functions:
+//!
+//! - `text()` and `set_text(text)` get and replace the whole code
+//! - `selection()` is the selected text, `selection_start()` and `selection_end()`
+//!   the indices (in characters) where it starts and ends, and `select(start, end)`
+//!   selects other characters
+//! - `insert(text)` replaces the selection with 'text' and puts the cursor after it
+//!
+//! Scripts are run from the Edit menu, or by the shortcuts of the `script_shortcuts`
+//! setting.
+
+use std::cell::RefCell;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use eframe::egui;
+use rhai::{Engine, INT};
+
+use super::paths;
+
+/// Extension of the scripts
+pub const SCRIPT_EXTENSION: &str = "rhai";
+
+/// Operations after which a script is stopped, e.g. if it loops forever
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// Keys that can be used in the shortcuts of the scripts, after the modifiers
+const SHORTCUT_KEYS: [(&str, egui::Key); 36] = [
+    ("A", egui::Key::A),
+    ("B", egui::Key::B),
+    ("C", egui::Key::C),
+    ("D", egui::Key::D),
+    ("E", egui::Key::E),
+    ("F", egui::Key::F),
+    ("G", egui::Key::G),
+    ("H", egui::Key::H),
+    ("I", egui::Key::I),
+    ("J", egui::Key::J),
+    ("K", egui::Key::K),
+    ("L", egui::Key::L),
+    ("M", egui::Key::M),
+    ("N", egui::Key::N),
+    ("O", egui::Key::O),
+    ("P", egui::Key::P),
+    ("Q", egui::Key::Q),
+    ("R", egui::Key::R),
+    ("S", egui::Key::S),
+    ("T", egui::Key::T),
+    ("U", egui::Key::U),
+    ("V", egui::Key::V),
+    ("W", egui::Key::W),
+    ("X", egui::Key::X),
+    ("Y", egui::Key::Y),
+    ("Z", egui::Key::Z),
+    ("0", egui::Key::Num0),
+    ("1", egui::Key::Num1),
+    ("2", egui::Key::Num2),
+    ("3", egui::Key::Num3),
+    ("4", egui::Key::Num4),
+    ("5", egui::Key::Num5),
+    ("6", egui::Key::Num6),
+    ("7", egui::Key::Num7),
+    ("8", egui::Key::Num8),
+    ("9", egui::Key::Num9),
+];
+
+/// Keystrokes recorded in the editor, to type them again
+#[derive(Default)]
+pub struct KeyMacro {
+    recording: Option<Vec<egui::Event>>, // None when not recording
+    recorded: Vec<egui::Event>,
+}
+
+impl KeyMacro {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Can the macro be played, i.e. is there one that is not being recorded?
+    pub fn can_play(&self) -> bool {
+        !self.is_recording() && !self.recorded.is_empty()
+    }
+
+    /// Start recording a new macro, or stop recording it
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(events) => self.recorded = events,
+            None => self.recording = Some(Vec::new()),
+        }
+    }
+
+    /// Record the keystrokes among 'events', typed in the editor. The shortcuts of the
+    /// macros (Ctrl+M and Ctrl+Shift+M) are left out.
+    pub fn record(&mut self, events: &[egui::Event]) {
+        let Some(ref mut recording) = self.recording else {
+            return;
+        };
+        recording.extend(
+            events
+                .iter()
+                .filter(|event| {
+                    matches!(
+                        event,
+                        egui::Event::Text(_)
+                            | egui::Event::Paste(_)
+                            | egui::Event::Key { pressed: true, .. }
+                    )
+                })
+                .filter(|event| {
+                    !matches!(event, egui::Event::Key { key: egui::Key::M, modifiers, .. }
+                        if modifiers.ctrl)
+                })
+                .cloned(),
+        );
+    }
+
+    /// Type the keystrokes of the macro again, by adding them to 'events'
+    pub fn play(&self, events: &mut Vec<egui::Event>) {
+        events.extend(self.recorded.iter().cloned());
+    }
+}
+
+/// The code edited by a script, and its selection in characters
+pub struct Buffer {
+    pub text: String,
+    pub selection: Range<usize>,
+}
+
+impl Buffer {
+    /// The byte index of the character 'index', which may be after the last one
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(idx, _)| idx)
+    }
+
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// Run the script 'script' on 'buffer', and return the buffer as the script left it,
+/// or the error that stopped the script
+pub fn run_script(script: &str, buffer: Buffer) -> Result<Buffer, String> {
+    let buffer = Rc::new(RefCell::new(buffer));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("text", move || shared.borrow().text.clone());
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("set_text", move |text: &str| {
+        let mut buffer = shared.borrow_mut();
+        buffer.text = text.to_string();
+        // The selection stays in the text
+        let count = buffer.char_count();
+        buffer.selection = buffer.selection.start.min(count)..buffer.selection.end.min(count);
+    });
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("selection", move || {
+        let buffer = shared.borrow();
+        let range =
+            buffer.byte_index(buffer.selection.start)..buffer.byte_index(buffer.selection.end);
+        buffer.text[range].to_string()
+    });
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("selection_start", move || {
+        shared.borrow().selection.start as INT
+    });
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("selection_end", move || {
+        shared.borrow().selection.end as INT
+    });
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("select", move |start: INT, end: INT| {
+        let mut buffer = shared.borrow_mut();
+        let count = buffer.char_count();
+        let clamp = |index: INT| (index.max(0) as usize).min(count);
+        let (start, end) = (clamp(start), clamp(end));
+        buffer.selection = start.min(end)..start.max(end);
+    });
+    let shared = Rc::clone(&buffer);
+    engine.register_fn("insert", move |text: &str| {
+        let mut buffer = shared.borrow_mut();
+        let range =
+            buffer.byte_index(buffer.selection.start)..buffer.byte_index(buffer.selection.end);
+        buffer.text.replace_range(range, text);
+        let cursor = buffer.selection.start + text.chars().count();
+        buffer.selection = cursor..cursor;
+    });
+
+    engine.run(script).map_err(|err| err.to_string())?;
+    // The engine, holding the other references, is dropped first
+    drop(engine);
+    Rc::try_unwrap(buffer)
+        .map(RefCell::into_inner)
+        .map_err(|_| "the script is still running".to_string())
+}
+
+/// The scripts in the `scripts` folder, by name
+pub fn list_scripts() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(paths::scripts_dir()) else {
+        return Vec::new();
+    };
+    let mut scripts: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext == SCRIPT_EXTENSION)
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+/// The name of the script in 'path', as used in the `script_shortcuts` setting
+pub fn script_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+/// The modifiers and the key of a shortcut like `Ctrl+Alt+U`, if it is valid
+pub fn parse_shortcut(shortcut: &str) -> Option<(egui::Modifiers, egui::Key)> {
+    let mut modifiers = egui::Modifiers::NONE;
+    let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+    let key = parts.pop()?;
+    for part in parts {
+        match part.to_lowercase().as_str() {
+            "ctrl" => modifiers.ctrl = true,
+            "shift" => modifiers.shift = true,
+            "alt" => modifiers.alt = true,
+            _ => return None,
+        }
+    }
+    let (_, key) = SHORTCUT_KEYS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))?;
+    Some((modifiers, *key))
+}
+
+/// Was the shortcut 'shortcut' pressed among 'events'?
+pub fn is_pressed(shortcut: &str, mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    let Some((expected, expected_key)) = parse_shortcut(shortcut) else {
+        return false;
+    };
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && *key == expected_key
+            && modifiers.ctrl == expected.ctrl
+            && modifiers.shift == expected.shift
+            && modifiers.alt == expected.alt
+        )
+    })
+}
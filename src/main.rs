@@ -1,36 +1,101 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // Hide console window on Windows in release
-#![cfg(all(target_arch = "x86_64", target_os = "windows"))] // Set target os as Windows
 
-mod highligher;
+mod ansi;
+mod association;
+mod cache;
+mod debugger;
+mod docstub;
+mod editing;
+mod fileio;
+mod fonts;
+mod format;
+mod geometry;
+mod git;
+#[cfg(feature = "syntect")]
+mod grammar;
+mod hexview;
+mod history;
+mod html;
+mod instance;
+mod jsonedit;
+mod lint;
 mod log;
+mod lsp;
+mod macros;
+mod palette;
+mod parser;
+mod paths;
+mod perf;
+mod preferences;
+mod recovery;
+mod reference;
+mod repl;
+mod runconfig;
+mod runner;
+mod search;
+mod semantic;
 mod settings;
+mod share;
+mod signature;
+mod spell;
+mod syntax;
+mod tasks;
+mod theme;
+mod todo;
+mod traceback;
 mod ui;
+mod viewer;
+mod vscode;
+mod welcome;
 
+use std::thread;
+
+use geometry::Geometry;
 use ui::CodeEditor;
 
+/// The icon of the window, in the executable so that it is found wherever the IDE is
+/// started from
+const ICON: &[u8] = include_bytes!("../images/coding.png");
+
 fn main() {
-    let Some(editor) = CodeEditor::new() else {
-        // Settings could not be loaded
+    log::init();
+    paths::init_user_dir();
+
+    // The files are opened by the IDE already running, if any
+    let files = paths::file_args();
+    if !files.is_empty() && instance::forward(&files) {
         return;
-    };
+    }
+
+    // Decoded while the settings are read
+    let icon = thread::spawn(|| load_image(ICON));
+    let mut editor = CodeEditor::new();
+    log::milestone!("editor created");
 
-    if cfg!(target_os = "windows") {
-        eframe::run_native(
-            "Colors",
-            eframe::NativeOptions {
-                icon_data: load_image("images\\coding.png"),
-                maximized: true,
-                ..Default::default()
-            },
-            Box::new(|_cc| Box::new(editor)),
-        )
+    let mut options = eframe::NativeOptions {
+        icon_data: icon.join().unwrap_or(None),
+        maximized: true,
+        ..Default::default()
+    };
+    if let Some(geometry) = Geometry::load() {
+        geometry.apply(&mut options);
     }
+
+    eframe::run_native(
+        "Colors",
+        options,
+        Box::new(|cc| {
+            fonts::install(&cc.egui_ctx);
+            editor.listen(cc.egui_ctx.clone());
+            Box::new(editor)
+        }),
+    )
 }
 
-/// Load an image using the [`image`] crate. Return None if the image could not be opened
-fn load_image(path: &str) -> Option<eframe::IconData> {
-    let Some(img) = image::open(path).ok() else {
-        log::warning("App image could not be found");
+/// Load an image using the [`image`] crate. Return None if the image could not be decoded
+fn load_image(bytes: &[u8]) -> Option<eframe::IconData> {
+    let Some(img) = image::load_from_memory(bytes).ok() else {
+        log::warning!("App image could not be decoded");
         return None;
     };
 
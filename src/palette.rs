@@ -0,0 +1,91 @@
+//! Command palette: a list of commands filtered by what is typed, chosen with the
+//! mouse or with the arrows and Enter, and closed with Escape.
+
+use eframe::egui;
+
+/// Width of the palette, in points
+const PALETTE_WIDTH: f32 = 400.0;
+
+/// What the user did with the palette during a frame
+pub enum PaletteAction {
+    None,
+    Chosen(usize), // index of the command, among all of them
+    Closed,
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    /// Text the commands shown contain
+    query: String,
+
+    /// Command chosen by Enter, among the ones shown
+    selected: usize,
+}
+
+impl CommandPalette {
+    /// Draw the palette at the top of the window, listing the 'commands' that match
+    /// the query
+    pub fn show(&mut self, ctx: &egui::Context, commands: &[String]) -> PaletteAction {
+        let words: Vec<String> = self
+            .query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        let shown: Vec<usize> = commands
+            .iter()
+            .enumerate()
+            .filter(|(_, command)| {
+                let command = command.to_lowercase();
+                words.iter().all(|word| command.contains(word))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        self.selected = self.selected.min(shown.len().saturating_sub(1));
+
+        let mut action = PaletteAction::None;
+        {
+            let input = ctx.input();
+            if input.key_pressed(egui::Key::Escape) {
+                action = PaletteAction::Closed;
+            } else if input.key_pressed(egui::Key::ArrowDown) {
+                self.selected = (self.selected + 1).min(shown.len().saturating_sub(1));
+            } else if input.key_pressed(egui::Key::ArrowUp) {
+                self.selected = self.selected.saturating_sub(1);
+            } else if input.key_pressed(egui::Key::Enter) {
+                if let Some(&idx) = shown.get(self.selected) {
+                    action = PaletteAction::Chosen(idx);
+                }
+            }
+        }
+
+        egui::Area::new("command_palette")
+            .anchor(egui::Align2::CENTER_TOP, (0.0, 40.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(PALETTE_WIDTH);
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.query)
+                            .hint_text("Type a command")
+                            .desired_width(f32::INFINITY),
+                    );
+                    response.request_focus();
+                    if response.changed() {
+                        self.selected = 0;
+                    }
+                    if shown.is_empty() {
+                        ui.label("No commands");
+                    }
+                    for (row, &idx) in shown.iter().enumerate() {
+                        if ui
+                            .selectable_label(row == self.selected, &commands[idx])
+                            .clicked()
+                        {
+                            action = PaletteAction::Chosen(idx);
+                        }
+                    }
+                });
+            });
+        action
+    }
+}
@@ -0,0 +1,153 @@
+//! Lightweight parser of betty code, working on the [`Token`]s of the [`Highligher`].
+//! It does not build a syntax tree: it only checks that brackets and blocks are
+//! balanced and that a few statements are well formed, and reports the problems
+//! it finds as [`Diagnostic`]s.
+//!
+//! [`Highligher`]: colors_highlight::Highligher
+
+use std::ops::Range;
+
+use colors_highlight::{Position, Token, TokenType};
+
+/// How serious a [`Diagnostic`] is
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,   // the code is wrong
+    Warning, // the code is valid, but probably not what was meant
+}
+
+/// A problem found in the code
+#[derive(Clone, PartialEq)]
+pub struct Diagnostic {
+    pub span: Range<usize>, // bytes
+    pub position: Position,
+    pub message: String,
+    pub severity: Severity,
+}
+
+/// Something that has been opened and must be closed
+enum Open<'a> {
+    Paren,
+    Bracket,
+    Block(&'a str), // the keyword of the statement, or "do"
+}
+
+/// Keywords of the statements that are followed by a 'do' block
+const BLOCK_STATEMENTS: [&str; 5] = ["if", "while", "for", "foreach", "fun"];
+
+/// Check the code, whose [`Token`]s are 'tokens', and return its problems
+pub fn check(tokens: &[Token], source: &str) -> Vec<Diagnostic> {
+    // Whitespace and comments are irrelevant
+    let tokens: Vec<(&Token, &str)> = tokens
+        .iter()
+        .map(|token| (token, &source[token.1.clone()]))
+        .filter(|(Token(typ, ..), word)| *typ != TokenType::Comment && !word.trim().is_empty())
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    let mut report = |token: &Token, message: String| {
+        diagnostics.push(Diagnostic {
+            span: token.1.clone(),
+            position: token.2,
+            message,
+            severity: Severity::Error,
+        })
+    };
+
+    let mut stack: Vec<(Open, &Token)> = Vec::new();
+    let mut statement: Option<(&str, &Token)> = None; // waiting for its 'do'
+
+    for (i, &(token, word)) in tokens.iter().enumerate() {
+        let Token(typ, _, position) = token;
+        match (typ, word) {
+            (TokenType::Other, "(") => stack.push((Open::Paren, token)),
+            (TokenType::Other, "[") => stack.push((Open::Bracket, token)),
+            (TokenType::Other, ")") => match stack.last() {
+                Some((Open::Paren, _)) => {
+                    stack.pop();
+                }
+                _ => report(token, "Unmatched ')'".to_string()),
+            },
+            (TokenType::Other, "]") => match stack.last() {
+                Some((Open::Bracket, _)) => {
+                    stack.pop();
+                }
+                _ => report(token, "Unmatched ']'".to_string()),
+            },
+
+            (TokenType::Kw, "do") => {
+                let keyword = statement.take().map_or("do", |(keyword, _)| keyword);
+                stack.push((Open::Block(keyword), token));
+            }
+            (TokenType::Kw, "end") => {
+                if let Some((keyword, opener)) = statement.take() {
+                    report(opener, format!("Expected 'do' after '{}'", keyword));
+                }
+
+                // Brackets cannot be left open inside a block
+                while let Some((Open::Paren | Open::Bracket, opener)) = stack.last() {
+                    report(
+                        opener,
+                        format!("'{}' is never closed", &source[opener.1.clone()]),
+                    );
+                    stack.pop();
+                }
+                if stack.pop().is_none() {
+                    report(token, "'end' without a matching 'do'".to_string());
+                }
+            }
+            (TokenType::Kw, keyword) if BLOCK_STATEMENTS.contains(&keyword) => {
+                if let Some((previous, opener)) = statement.replace((keyword, token)) {
+                    report(opener, format!("Expected 'do' after '{}'", previous));
+                }
+            }
+            // A function may also return a single expression, as in 'fun(x) -> x * 2'
+            (TokenType::Op, "->") => {
+                if let Some(("fun", _)) = statement {
+                    statement = None;
+                }
+            }
+
+            (TokenType::Kw, "break" | "continue") => {
+                let in_loop = stack
+                    .iter()
+                    .any(|(open, _)| matches!(open, Open::Block("while" | "for" | "foreach")));
+                if !in_loop {
+                    report(token, format!("'{}' outside of a loop", word));
+                }
+            }
+            (TokenType::Kw, "return") => {
+                let in_fun = stack
+                    .iter()
+                    .any(|(open, _)| matches!(open, Open::Block("fun")));
+                if !in_fun {
+                    report(token, "'return' outside of a function".to_string());
+                }
+            }
+
+            // An assignment needs a value on the same line
+            (TokenType::Sym, "=") => {
+                let has_value = tokens
+                    .get(i + 1)
+                    .map_or(false, |(Token(_, _, next), _)| next.line == position.line);
+                if !has_value {
+                    report(token, "Expected a value after '='".to_string());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if let Some((keyword, opener)) = statement {
+        report(opener, format!("Expected 'do' after '{}'", keyword));
+    }
+    for (open, opener) in stack {
+        let message = match open {
+            Open::Paren => "'(' is never closed".to_string(),
+            Open::Bracket => "'[' is never closed".to_string(),
+            Open::Block(_) => "'do' is never closed by 'end'".to_string(),
+        };
+        report(opener, message);
+    }
+    diagnostics
+}
@@ -0,0 +1,283 @@
+//! Location of the files of the IDE. The user files (settings, log, themes, syntax
+//! definitions and scripts) live in the folder of the configuration of the user (`%APPDATA%\Colors`
+//! on Windows, `~/Library/Application Support/Colors` on macOS and `~/.config/Colors`
+//! on Linux), or next to the executable when the IDE is started with `--portable`. The
+//! files shipped with the IDE are copied from the folder of the executable. Another
+//! settings file can be chosen with `--settings <path>` or the `COLORS_SETTINGS`
+//! environment variable.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::log;
+
+/// Folder of the user files in the configuration folder
+const APP_DIR: &str = "Colors";
+
+/// Command line flag to keep the user files next to the executable
+const PORTABLE_FLAG: &str = "--portable";
+
+/// Command line option with the path to the settings file
+const SETTINGS_FLAG: &str = "--settings";
+
+/// Command line flag of the windows opened with "New window" in another window
+const NEW_WINDOW_FLAG: &str = "--new-window";
+
+/// Environment variable with the path to the settings file
+const SETTINGS_VAR: &str = "COLORS_SETTINGS";
+
+/// The command line arguments, without the executable. They are not required to be
+/// Unicode, since they can be paths.
+fn args() -> impl Iterator<Item = OsString> {
+    env::args_os().skip(1)
+}
+
+/// Is 'arg' an option, such as `--portable`?
+fn is_option(arg: &OsStr) -> bool {
+    arg.to_str().map_or(false, |arg| arg.starts_with("--"))
+}
+
+/// Was the IDE started with `--portable`?
+pub fn is_portable() -> bool {
+    args().any(|arg| arg == PORTABLE_FLAG)
+}
+
+/// Was the IDE opened from another window of the IDE?
+pub fn is_new_window() -> bool {
+    args().any(|arg| arg == NEW_WINDOW_FLAG)
+}
+
+/// Arguments of another window of the IDE sharing the user files of this one: its
+/// options, without the files
+pub fn new_window_args() -> Vec<OsString> {
+    let mut options = vec![OsString::from(NEW_WINDOW_FLAG)];
+    let mut args = args();
+    while let Some(arg) = args.next() {
+        if arg == SETTINGS_FLAG {
+            options.push(arg);
+            options.extend(args.next());
+        } else if is_option(&arg) && arg != NEW_WINDOW_FLAG {
+            options.push(arg);
+        }
+    }
+    options
+}
+
+/// Value of the command line option 'flag', written as `flag value` or `flag=value`.
+/// The value of `flag=value` must be Unicode.
+fn option(flag: &str) -> Option<OsString> {
+    let mut args = args();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix(flag))
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.into());
+        }
+    }
+    None
+}
+
+/// The files given on the command line besides the options, e.g. `colors file.betty`,
+/// relative to the working directory
+pub fn file_args() -> Vec<PathBuf> {
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut files = Vec::new();
+    let mut args = args();
+    while let Some(arg) = args.next() {
+        if arg == SETTINGS_FLAG {
+            args.next();
+        } else if !is_option(&arg) {
+            files.push(cwd.join(arg));
+        }
+    }
+    files
+}
+
+/// Folder of the executable, or the working directory if it cannot be found
+pub fn exe_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// Folder of the user files
+pub fn user_dir() -> PathBuf {
+    if is_portable() {
+        return exe_dir();
+    }
+    config_dir().map_or_else(exe_dir, |dir| dir.join(APP_DIR))
+}
+
+/// Folder of the configuration of the applications of the user, as the system has it
+fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))
+    }
+}
+
+/// Home folder of the user
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The settings file given with `--settings`, else with `COLORS_SETTINGS`, else the
+/// one in the user folder
+pub fn settings_file() -> PathBuf {
+    option(SETTINGS_FLAG)
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os(SETTINGS_VAR)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(default_settings_file)
+}
+
+fn default_settings_file() -> PathBuf {
+    user_dir().join("settings").join("settings.json")
+}
+
+pub fn log_file() -> PathBuf {
+    user_dir().join("log").join("log.log")
+}
+
+/// File holding the size and position of the window
+pub fn geometry_file() -> PathBuf {
+    user_dir().join("window.json")
+}
+
+/// Folder of the unsaved contents of the editor, to recover them after a crash
+pub fn recovery_dir() -> PathBuf {
+    user_dir().join("recovery")
+}
+
+/// File holding the files opened lately
+pub fn recent_file() -> PathBuf {
+    user_dir().join("recent.json")
+}
+
+/// File holding the port of the IDE running, for the IDEs started later
+pub fn instance_file() -> PathBuf {
+    user_dir().join("instance.port")
+}
+
+pub fn themes_dir() -> PathBuf {
+    user_dir().join("themes")
+}
+
+pub fn syntaxes_dir() -> PathBuf {
+    user_dir().join("syntaxes")
+}
+
+/// Folder of the scripts editing the code
+pub fn scripts_dir() -> PathBuf {
+    user_dir().join("scripts")
+}
+
+/// The executable 'program', if it exists: either a path, or a name looked up in the
+/// folders of `PATH` (`.exe` being added on Windows if it has no extension)
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let program = program.trim();
+    if program.is_empty() {
+        return None;
+    }
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let mut names = vec![program.to_string()];
+    if path.extension().is_none() && !env::consts::EXE_SUFFIX.is_empty() {
+        names.push(format!("{}{}", program, env::consts::EXE_SUFFIX));
+    }
+    let dirs = env::var_os("PATH").map_or_else(Vec::new, |path| env::split_paths(&path).collect());
+    dirs.iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Look for the betty interpreter in `PATH`, next to the IDE and where it is usually
+/// installed
+pub fn find_betty() -> Option<PathBuf> {
+    if let Some(path) = find_program("betty") {
+        return Some(path);
+    }
+
+    let betty = format!("betty{}", env::consts::EXE_SUFFIX);
+    let mut dirs = vec![exe_dir()];
+    for (var, dir) in [
+        ("ProgramFiles", &["betty"][..]),
+        ("ProgramFiles(x86)", &["betty"]),
+        ("LOCALAPPDATA", &["Programs", "betty"]),
+    ] {
+        if let Some(root) = env::var_os(var) {
+            dirs.push(
+                dir.iter()
+                    .fold(PathBuf::from(root), |path, name| path.join(name)),
+            );
+        }
+    }
+    if !cfg!(windows) {
+        dirs.push(PathBuf::from("/usr/local/bin"));
+    }
+    if let Some(home) = home_dir() {
+        dirs.push(home.join(".betty").join("bin"));
+        dirs.push(home.join(".local").join("bin"));
+        dirs.push(home.join(".cargo").join("bin"));
+    }
+    dirs.into_iter()
+        .map(|dir| dir.join(&betty))
+        .find(|path| path.is_file())
+}
+
+/// On the first start, copy the settings, themes and syntax definitions shipped
+/// with the IDE to the user folder, so that they can be modified
+pub fn init_user_dir() {
+    let user_dir = user_dir();
+    let exe_dir = exe_dir();
+    if user_dir == exe_dir || default_settings_file().exists() {
+        return;
+    }
+
+    for folder in ["settings", "themes", "syntaxes", "scripts"] {
+        if let Err(err) = copy_files(&exe_dir.join(folder), &user_dir.join(folder)) {
+            log::warning!(format!(
+                "The '{}' folder could not be copied to '{}': {}",
+                folder,
+                user_dir.display(),
+                err
+            ));
+        }
+    }
+}
+
+/// Copy the files in the folder 'from' to the folder 'to', which is created if needed
+fn copy_files(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if let (true, Some(name)) = (path.is_file(), path.file_name()) {
+            fs::copy(&path, to.join(name))?;
+        }
+    }
+    Ok(())
+}
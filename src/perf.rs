@@ -0,0 +1,124 @@
+//! A hidden overlay showing how fast the editor is, toggled with Ctrl+Shift+F12:
+//! the time of the frames and of the highlighting, the size of the document and the
+//! allocations made in each frame, counted by the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use eframe::egui;
+
+/// Frames averaged in the frame time
+const FRAMES: usize = 60;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0); // since the IDE started
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0); // currently in use
+
+/// The system allocator, counting the allocations and the bytes in use
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// What the overlay shows, measured while it is open
+pub struct PerfOverlay {
+    frame_times: VecDeque<f32>, // in seconds, of the last frames
+    last_allocations: usize,    // when the last frame started
+    frame_allocations: usize,   // in the last frame
+    highlight_time: Duration,   // of the last layout of the editor
+}
+
+impl PerfOverlay {
+    pub fn new() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(FRAMES),
+            last_allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            frame_allocations: 0,
+            highlight_time: Duration::ZERO,
+        }
+    }
+
+    /// Called at the start of each frame, with the time taken by the last one
+    pub fn start_frame(&mut self, frame_time: Option<f32>) {
+        if let Some(frame_time) = frame_time {
+            if self.frame_times.len() == FRAMES {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(frame_time);
+        }
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        self.frame_allocations = allocations - self.last_allocations;
+        self.last_allocations = allocations;
+    }
+
+    pub fn record_highlight(&mut self, time: Duration) {
+        self.highlight_time = time;
+    }
+
+    /// Draw the overlay in the top right corner, for a document of 'bytes' and 'lines'
+    pub fn show(&self, ctx: &egui::Context, bytes: usize, lines: usize) {
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        let slowest = self.frame_times.iter().copied().fold(0.0, f32::max);
+        egui::Area::new("perf_overlay")
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 40.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.monospace(format!(
+                        "Frame:      {:.2}ms (slowest {:.2}ms)",
+                        average * 1000.0,
+                        slowest * 1000.0
+                    ));
+                    ui.monospace(format!(
+                        "Highlight:  {:.2}ms",
+                        self.highlight_time.as_secs_f64() * 1000.0
+                    ));
+                    ui.monospace(format!("Document:   {} bytes, {} lines", bytes, lines));
+                    ui.monospace(format!(
+                        "Memory:     {} bytes",
+                        ALLOCATED_BYTES.load(Ordering::Relaxed)
+                    ));
+                    ui.monospace(format!(
+                        "Allocs:     {} in the last frame",
+                        self.frame_allocations
+                    ));
+                });
+            });
+    }
+}
@@ -0,0 +1,672 @@
+//! Window to edit the [`Settings`] from the IDE. The changes apply right away, and
+//! are written to `settings.json` when the user saves them.
+
+use eframe::egui;
+
+use super::log::{Format, Level};
+use super::settings::{
+    FontStyle, Settings, ShareService, UiTheme, DEFAULT_STDERR_COLOR, MAX_FONT_SIZE, MAX_TAB_SIZE,
+    MAX_UI_SCALE, MIN_FONT_SIZE, MIN_UI_SCALE,
+};
+use super::share;
+use super::theme::theme_name;
+
+/// Pages of the window
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Colors,
+    Editor,
+    Run,
+    Keybindings,
+}
+
+/// Shortcuts of the editor, which cannot be changed
+const KEYBINDINGS: [(&str, &str); 11] = [
+    ("Ctrl+S", "Save the file"),
+    ("Ctrl+P", "Print the file"),
+    ("Ctrl+R", "Run the file"),
+    ("Ctrl+Shift+C", "Stop the running program"),
+    ("Ctrl+L", "Clear the console"),
+    ("Ctrl+Shift+P", "Run a task of the project"),
+    ("Ctrl+Enter", "Send the selection, or the line, to the REPL"),
+    (
+        "Ctrl+Shift+D",
+        "Insert a documentation stub above the function",
+    ),
+    ("Ctrl+Shift+F", "Format the document"),
+    ("Ctrl+Shift+M", "Start or stop recording a macro"),
+    ("Ctrl+M", "Play the macro"),
+];
+
+/// What the user did in the window during a frame
+#[derive(Default)]
+pub struct PreferencesOutput {
+    pub changed: bool,              // the settings have been modified
+    pub save: bool,                 // the settings must be written to the file
+    pub save_theme: Option<String>, // the code colors must be saved as this theme
+    pub associate: Option<bool>,    // the betty files must be associated with the IDE, or not
+    pub save_token: Option<String>, // the token of the paste service must be kept
+}
+
+pub struct Preferences {
+    /// Page being shown
+    tab: Tab,
+
+    /// Extension of the file type being added
+    new_extension: String,
+
+    /// Name of the environment variable being added
+    new_env_var: String,
+
+    /// Name under which the code colors are saved as a theme
+    theme_name: String,
+
+    /// Token of the paste service being edited
+    share_token: String,
+
+    /// Is the window still open?
+    open: bool,
+}
+
+impl Preferences {
+    pub fn new() -> Self {
+        Self {
+            tab: Tab::Colors,
+            new_extension: String::new(),
+            new_env_var: String::new(),
+            theme_name: String::new(),
+            share_token: share::load_token(),
+            open: true,
+        }
+    }
+
+    /// The window showing the shortcuts of the editor
+    pub fn keybindings() -> Self {
+        Self {
+            tab: Tab::Keybindings,
+            ..Self::new()
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draw the window, editing 'settings' directly
+    pub fn show(&mut self, ctx: &egui::Context, settings: &mut Settings) -> PreferencesOutput {
+        let mut output = PreferencesOutput::default();
+        let mut open = self.open;
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .default_size((500.0, 500.0))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.tab, Tab::Colors, "Colors");
+                    ui.selectable_value(&mut self.tab, Tab::Editor, "Editor");
+                    ui.selectable_value(&mut self.tab, Tab::Run, "Run");
+                    ui.selectable_value(&mut self.tab, Tab::Keybindings, "Keybindings");
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .id_source("preferences")
+                    .max_height(ui.available_height() - 40.0)
+                    .show(ui, |ui| {
+                        output.changed = match self.tab {
+                            Tab::Colors => {
+                                output.save_theme = self.draw_save_theme(ui, settings);
+                                draw_colors(ui, settings)
+                            }
+                            Tab::Editor => {
+                                output.associate = draw_association(ui);
+                                let changed = draw_editor(ui, settings);
+                                let (share_changed, save_token) = self.draw_share(ui, settings);
+                                output.save_token = save_token;
+                                changed | share_changed
+                            }
+                            Tab::Run => self.draw_run(ui, settings),
+                            Tab::Keybindings => draw_keybindings(ui),
+                        };
+                    });
+
+                ui.separator();
+                output.save = ui.button("Save").clicked();
+            });
+
+        self.open = open;
+        output
+    }
+
+    /// Draw the name of the theme of the code colors, and a button to save them as a
+    /// theme. Return the name of the theme to save, if the button was clicked.
+    fn draw_save_theme(&mut self, ui: &mut egui::Ui, settings: &Settings) -> Option<String> {
+        let mut save = None;
+        ui.horizontal(|ui| {
+            match settings.theme {
+                Some(ref name) => ui.label(format!("Theme: {}", name)),
+                None => ui.label("Theme: custom"),
+            };
+            ui.add(egui::TextEdit::singleline(&mut self.theme_name).hint_text("Theme name"));
+            let name = theme_name(&self.theme_name);
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save as theme"))
+                .clicked()
+            {
+                save = Some(name);
+                self.theme_name.clear();
+            }
+        });
+        ui.separator();
+        save
+    }
+
+    /// Draw where the code is shared, and the token of the service. Return whether the
+    /// settings changed, and the token to keep if the button saving it was clicked.
+    fn draw_share(&mut self, ui: &mut egui::Ui, settings: &mut Settings) -> (bool, Option<String>) {
+        let mut changed = false;
+        let mut save_token = None;
+
+        ui.separator();
+        ui.label("Sharing of the code");
+        egui::Grid::new("share_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Paste service");
+                ui.horizontal(|ui| {
+                    for (service, name) in [
+                        (ShareService::Gist, "GitHub gist"),
+                        (ShareService::Pastebin, "Pastebin"),
+                    ] {
+                        changed |= ui
+                            .selectable_value(&mut settings.share.service, service, name)
+                            .changed();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Address (empty for the one of the service)");
+                let mut url = settings.share.url.clone().unwrap_or_default();
+                if ui.text_edit_singleline(&mut url).changed() {
+                    settings.share.url = (!url.is_empty()).then_some(url);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("Public snippets");
+                changed |= ui.checkbox(&mut settings.share.public, "").changed();
+                ui.end_row();
+
+                ui.label("Token");
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.share_token).password(true));
+                    if ui.button("Save token").clicked() {
+                        save_token = Some(self.share_token.trim().to_string());
+                    }
+                });
+                ui.end_row();
+            });
+
+        (changed, save_token)
+    }
+
+    fn draw_run(&mut self, ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+        let mut changed = false;
+
+        egui::Grid::new("run_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("betty executable");
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .text_edit_singleline(&mut settings.betty_exe_path)
+                        .changed();
+                    if ui.button("Browse").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_file() {
+                            settings.betty_exe_path = path.to_string_lossy().into();
+                            changed = true;
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Save before running");
+                changed |= ui.checkbox(&mut settings.save_and_run, "").changed();
+                ui.end_row();
+
+                ui.label("Console size (bytes)");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut settings.console_max_bytes).speed(1000))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Console lines (0 for no limit)");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut settings.console_max_lines).speed(100))
+                    .changed();
+                ui.end_row();
+
+                ui.label("Wrap the console lines");
+                changed |= ui.checkbox(&mut settings.console_word_wrap, "").changed();
+                ui.end_row();
+
+                ui.label("Clear the console before running");
+                changed |= ui
+                    .checkbox(&mut settings.clear_console_on_run, "")
+                    .changed();
+                ui.end_row();
+
+                ui.label("Remove ANSI colors from the output");
+                changed |= ui.checkbox(&mut settings.strip_ansi, "").changed();
+                ui.end_row();
+
+                ui.label("Color of the errors (stderr)");
+                ui.horizontal(|ui| {
+                    let mut colored = settings.stderr_color.is_some();
+                    if ui.checkbox(&mut colored, "").changed() {
+                        settings.stderr_color = colored.then_some(DEFAULT_STDERR_COLOR);
+                        changed = true;
+                    }
+                    if let Some(ref mut rgb) = settings.stderr_color {
+                        changed |= egui::color_picker::color_edit_button_srgb(ui, rgb).changed();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Stop the programs after (seconds, 0 to never)");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut settings.run_timeout_secs))
+                    .changed();
+                ui.end_row();
+            });
+
+        ui.separator();
+        ui.label("File types. {betty} and {file} are replaced in the run and lint commands.");
+
+        let extensions: Vec<String> = settings.file_types.keys().cloned().collect();
+        let mut removed = None;
+
+        egui::Grid::new("file_types")
+            .num_columns(8)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Extension");
+                ui.strong("Syntax");
+                ui.strong("Run command");
+                ui.strong("Language server");
+                ui.strong("Debugger");
+                ui.strong("Formatter");
+                ui.strong("Linter");
+                ui.end_row();
+
+                for ext in extensions {
+                    let file_type = settings.file_types.get_mut(&ext).unwrap();
+                    ui.label(&ext);
+                    changed |= ui.text_edit_singleline(&mut file_type.syntax).changed();
+
+                    // No command means that the files cannot be run
+                    let mut run = file_type.run.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut run).changed() {
+                        file_type.run = (!run.is_empty()).then_some(run);
+                        changed = true;
+                    }
+                    let mut server = file_type.language_server.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut server).changed() {
+                        file_type.language_server = (!server.is_empty()).then_some(server);
+                        changed = true;
+                    }
+                    let mut debugger = file_type.debugger.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut debugger).changed() {
+                        file_type.debugger = (!debugger.is_empty()).then_some(debugger);
+                        changed = true;
+                    }
+                    let mut formatter = file_type.formatter.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut formatter).changed() {
+                        file_type.formatter = (!formatter.is_empty()).then_some(formatter);
+                        changed = true;
+                    }
+                    let mut linter = file_type.linter.clone().unwrap_or_default();
+                    if ui.text_edit_singleline(&mut linter).changed() {
+                        file_type.linter = (!linter.is_empty()).then_some(linter);
+                        changed = true;
+                    }
+                    if ui.button("Remove").clicked() {
+                        removed = Some(ext);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(ext) = removed {
+            settings.file_types.remove(&ext);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_extension);
+            let ext = self
+                .new_extension
+                .trim()
+                .trim_start_matches('.')
+                .to_lowercase();
+            if ui.button("Add file type").clicked() && !ext.is_empty() {
+                settings.file_types.entry(ext).or_default();
+                self.new_extension.clear();
+                changed = true;
+            }
+        });
+
+        ui.separator();
+        ui.label("Environment variables of the programs");
+
+        let names: Vec<String> = settings.env.keys().cloned().collect();
+        let mut removed = None;
+
+        egui::Grid::new("env")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for name in names {
+                    let value = settings.env.get_mut(&name).unwrap();
+                    ui.label(&name);
+                    changed |= ui.text_edit_singleline(value).changed();
+                    if ui.button("Remove").clicked() {
+                        removed = Some(name);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(name) = removed {
+            settings.env.remove(&name);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_env_var);
+            let name = self.new_env_var.trim().to_string();
+            if ui.button("Add variable").clicked() && !name.is_empty() {
+                settings.env.entry(name).or_default();
+                self.new_env_var.clear();
+                changed = true;
+            }
+        });
+
+        changed
+    }
+}
+
+fn draw_colors(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let color = &mut settings.code_color;
+    let style = &mut color.style;
+    let mut changed = false;
+
+    let mut fun = egui::Color32::from_rgba_premultiplied(
+        color.fun[0],
+        color.fun[1],
+        color.fun[2],
+        color.fun[3],
+    );
+
+    egui::Grid::new("colors")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            let rows: [(&str, &mut [u8; 3], &mut FontStyle); 12] = [
+                ("Identifiers", &mut color.ident, &mut style.ident),
+                ("Numbers", &mut color.number, &mut style.number),
+                ("Strings", &mut color.string, &mut style.string),
+                ("Symbols", &mut color.symbol, &mut style.symbol),
+                ("Operators", &mut color.operator, &mut style.operator),
+                ("Keywords", &mut color.keyword, &mut style.keyword),
+                (
+                    "Builtin functions",
+                    &mut color.builtin_fn,
+                    &mut style.builtin_fn,
+                ),
+                ("Comments", &mut color.comment, &mut style.comment),
+                ("TODOs", &mut color.todo, &mut style.todo),
+                ("Errors", &mut color.error, &mut style.error),
+                (
+                    "Undefined names",
+                    &mut color.undefined,
+                    &mut style.undefined,
+                ),
+                ("Other", &mut color.other, &mut style.other),
+            ];
+            for (name, rgb, font_style) in rows {
+                ui.label(name);
+                changed |= egui::color_picker::color_edit_button_srgb(ui, rgb).changed();
+                changed |= draw_font_style(ui, font_style);
+                ui.end_row();
+            }
+
+            // Functions have an alpha channel
+            ui.label("Functions");
+            changed |= egui::color_picker::color_edit_button_srgba(
+                ui,
+                &mut fun,
+                egui::color_picker::Alpha::OnlyBlend,
+            )
+            .changed();
+            changed |= draw_font_style(ui, &mut style.fun);
+            ui.end_row();
+        });
+
+    color.fun = fun.to_array();
+
+    // The colors are not the ones of the theme anymore
+    if changed {
+        settings.theme = None;
+    }
+    changed
+}
+
+fn draw_font_style(ui: &mut egui::Ui, font_style: &mut FontStyle) -> bool {
+    ui.checkbox(&mut font_style.bold, "Bold").changed()
+        | ui.checkbox(&mut font_style.italic, "Italic").changed()
+        | ui.checkbox(&mut font_style.underline, "Underline")
+            .changed()
+        | ui.checkbox(&mut font_style.strikethrough, "Strikethrough")
+            .changed()
+}
+
+/// Draw the buttons associating the betty files with the IDE, on Windows. Return
+/// whether they must be associated or not, if a button was clicked.
+fn draw_association(ui: &mut egui::Ui) -> Option<bool> {
+    if !cfg!(windows) {
+        return None;
+    }
+    let mut associate = None;
+    ui.horizontal(|ui| {
+        ui.label("betty files");
+        if ui.button("Open them with Colors").clicked() {
+            associate = Some(true);
+        }
+        if ui.button("Stop opening them with Colors").clicked() {
+            associate = Some(false);
+        }
+    });
+    ui.separator();
+    associate
+}
+
+fn draw_editor(ui: &mut egui::Ui, settings: &mut Settings) -> bool {
+    let mut changed = false;
+
+    egui::Grid::new("editor_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Interface theme");
+            ui.horizontal(|ui| {
+                for (ui_theme, name) in [
+                    (UiTheme::Dark, "Dark"),
+                    (UiTheme::Light, "Light"),
+                    (UiTheme::System, "System"),
+                ] {
+                    changed |= ui
+                        .selectable_value(&mut settings.ui_theme, ui_theme, name)
+                        .changed();
+                }
+            });
+            ui.end_row();
+
+            ui.label("Interface scale");
+            changed |= ui
+                .add(egui::Slider::new(
+                    &mut settings.ui_scale,
+                    MIN_UI_SCALE..=MAX_UI_SCALE,
+                ))
+                .changed();
+            ui.end_row();
+
+            ui.label("Code font size");
+            changed |= ui
+                .add(egui::Slider::new(
+                    &mut settings.code_font_size,
+                    MIN_FONT_SIZE..=MAX_FONT_SIZE,
+                ))
+                .changed();
+            ui.end_row();
+
+            ui.label("Console font size");
+            changed |= ui
+                .add(egui::Slider::new(
+                    &mut settings.console_font_size,
+                    MIN_FONT_SIZE..=MAX_FONT_SIZE,
+                ))
+                .changed();
+            ui.end_row();
+
+            ui.label("Word wrap");
+            changed |= ui.checkbox(&mut settings.editor.word_wrap, "").changed();
+            ui.end_row();
+
+            ui.label("Tab size");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut settings.editor.tab_size)
+                        .clamp_range(1..=MAX_TAB_SIZE),
+                )
+                .changed();
+            ui.end_row();
+
+            ui.label("Show whitespace");
+            changed |= ui
+                .checkbox(&mut settings.editor.show_whitespace, "")
+                .changed();
+            ui.end_row();
+
+            ui.label("Highlight the current line");
+            changed |= ui
+                .checkbox(&mut settings.editor.highlight_current_line, "")
+                .changed();
+            ui.end_row();
+
+            ui.label("Close brackets and quotes");
+            changed |= ui.checkbox(&mut settings.editor.auto_pair, "").changed();
+            ui.end_row();
+
+            ui.label("Start new files with a byte order mark");
+            changed |= ui.checkbox(&mut settings.editor.bom, "").changed();
+            ui.end_row();
+
+            ui.label("Largest file opened (MB, 0 for no limit)");
+            changed |= ui
+                .add(egui::DragValue::new(&mut settings.editor.max_file_size_mb))
+                .changed();
+            ui.end_row();
+
+            ui.label("Autosave after (seconds, 0 to never)");
+            changed |= ui
+                .add(egui::DragValue::new(&mut settings.editor.autosave_interval))
+                .changed();
+            ui.end_row();
+
+            ui.label("Keep the unsaved contents every (seconds, 0 to never)");
+            changed |= ui
+                .add(egui::DragValue::new(&mut settings.editor.recovery_interval))
+                .changed();
+            ui.end_row();
+
+            ui.label("Check the spelling of comments and strings");
+            changed |= ui.checkbox(&mut settings.editor.spell_check, "").changed();
+            ui.end_row();
+
+            ui.label("Word list (empty for the one of the system)");
+            let mut dictionary = settings.spell_dictionary.clone().unwrap_or_default();
+            if ui.text_edit_singleline(&mut dictionary).changed() {
+                settings.spell_dictionary = (!dictionary.is_empty()).then_some(dictionary);
+                changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Show the save button");
+            changed |= ui.checkbox(&mut settings.save_btn, "").changed();
+            ui.end_row();
+
+            ui.label("Save when closing");
+            changed |= ui.checkbox(&mut settings.save_on_close, "").changed();
+            ui.end_row();
+
+            ui.label("Doc stub description");
+            changed |= ui
+                .text_edit_singleline(&mut settings.doc_stub.description)
+                .changed();
+            ui.end_row();
+
+            ui.label("Doc stub parameter");
+            changed |= ui
+                .text_edit_singleline(&mut settings.doc_stub.param)
+                .changed();
+            ui.end_row();
+
+            ui.label("Log");
+            changed |= ui.checkbox(&mut settings.log.enabled, "").changed();
+            ui.end_row();
+
+            ui.label("Least severe level logged");
+            egui::ComboBox::from_id_source("log_level")
+                .selected_text(format!("{}", settings.log.level))
+                .show_ui(ui, |ui| {
+                    for level in [Level::Debug, Level::Info, Level::Warning, Level::Critical] {
+                        changed |= ui
+                            .selectable_value(&mut settings.log.level, level, format!("{}", level))
+                            .changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Log as JSON");
+            let mut json = settings.log.format == Format::Json;
+            if ui.checkbox(&mut json, "").changed() {
+                settings.log.format = if json { Format::Json } else { Format::Text };
+                changed = true;
+            }
+            ui.end_row();
+
+            ui.label("Log file (empty for the default one)");
+            let mut file = settings.log.file.clone().unwrap_or_default();
+            if ui.text_edit_singleline(&mut file).changed() {
+                settings.log.file = (!file.is_empty()).then_some(file);
+                changed = true;
+            }
+            ui.end_row();
+        });
+
+    changed
+}
+
+fn draw_keybindings(ui: &mut egui::Ui) -> bool {
+    egui::Grid::new("keybindings")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (keys, action) in KEYBINDINGS {
+                ui.monospace(keys);
+                ui.label(action);
+                ui.end_row();
+            }
+        });
+    false
+}
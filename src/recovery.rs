@@ -0,0 +1,100 @@
+//! Recovery of the unsaved contents of the editor. While the file has unsaved edits,
+//! its contents are written now and then to the `recovery` folder in the user folder,
+//! one file for each window of the IDE, which is removed when the file is saved or the
+//! window closes normally. A recovery file found on startup whose window is no longer
+//! running was thus left by a window that crashed or was killed.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::paths;
+use super::runner;
+
+/// Start of the names of the recovery files, followed by the ID of the process
+const RECOVERY_PREFIX: &str = "unsaved-";
+
+#[derive(Serialize, Deserialize)]
+pub struct Recovery {
+    pub path: Option<PathBuf>, // None if the file was never saved
+    pub contents: String,
+}
+
+impl Recovery {
+    /// The latest contents left by a window that did not close normally, if any,
+    /// along with their recovery file. The files of the windows still running are
+    /// theirs, and are left alone.
+    pub fn load_orphan() -> Option<(PathBuf, Self)> {
+        let mut files: Vec<_> = fs::read_dir(paths::recovery_dir())
+            .ok()?
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name();
+                let pid = name
+                    .to_str()
+                    .and_then(|name| name.strip_prefix(RECOVERY_PREFIX))
+                    .and_then(|name| name.strip_suffix(".json"))
+                    .and_then(|pid| pid.parse().ok());
+                pid.map_or(false, |pid| !is_running(pid))
+            })
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        files.sort();
+        let (_, path) = files.pop()?;
+        let json = fs::read_to_string(&path).ok()?;
+        Some((path, serde_json::from_str(&json).ok()?))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::recovery_dir())?;
+        fs::write(file(), serde_json::to_string(self)?)
+    }
+
+    /// Forget the contents to recover of this window, as they were saved or discarded
+    pub fn clear() -> io::Result<()> {
+        remove(file())
+    }
+}
+
+/// Remove the recovery file in 'path', if it still exists
+pub fn remove(path: PathBuf) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Is the process with the ID 'pid' running? If that cannot be told, it is taken as
+/// running, so that its recovery file is not taken from it.
+fn is_running(pid: u32) -> bool {
+    if pid == process::id() {
+        return true;
+    }
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("tasklist");
+        command.args(["/NH", "/FO", "CSV", "/FI", &format!("PID eq {}", pid)]);
+        command
+    } else {
+        let mut command = Command::new("kill");
+        command.args(["-0", &pid.to_string()]);
+        command
+    };
+    command.stdin(Stdio::null()).stderr(Stdio::null());
+    match runner::without_window(&mut command).output() {
+        // tasklist succeeds, telling in its output whether it found the process
+        Ok(output) if cfg!(windows) => {
+            let quoted = format!("\"{}\"", pid);
+            !output.status.success() || String::from_utf8_lossy(&output.stdout).contains(&quoted)
+        }
+        Ok(output) => output.status.success(),
+        Err(_) => true,
+    }
+}
+
+/// The recovery file of this window
+fn file() -> PathBuf {
+    paths::recovery_dir().join(format!("{}{}.json", RECOVERY_PREFIX, process::id()))
+}
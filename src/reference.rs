@@ -0,0 +1,106 @@
+//! Reference of the betty language: its builtin functions, keywords and errors, with
+//! examples. It is bundled from `reference/betty.json`, which is updated along with
+//! the language, and is searched in the reference panel of the Help menu. The hover
+//! text and the signature help of the builtins come from it too.
+
+use eframe::egui;
+use serde_derive::Deserialize;
+
+/// The reference of the language, as bundled
+const REFERENCE: &str = include_str!("../reference/betty.json");
+
+/// A documented name of the language
+#[derive(Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub signature: Option<String>, // only for the functions
+    pub description: String,
+    pub example: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Reference {
+    pub builtins: Vec<Entry>,
+    pub keywords: Vec<Entry>,
+    pub errors: Vec<Entry>,
+}
+
+impl Reference {
+    pub fn bundled() -> Self {
+        serde_json::from_str(REFERENCE).expect("The bundled reference is valid JSON")
+    }
+
+    /// The documentation of the builtin function 'name', if it is one
+    pub fn builtin(&self, name: &str) -> Option<&Entry> {
+        self.builtins.iter().find(|entry| entry.name == name)
+    }
+}
+
+/// Side panel listing the reference, filtered by a search
+#[derive(Default)]
+pub struct ReferencePanel {
+    /// Text the names and descriptions shown contain
+    search: String,
+}
+
+impl ReferencePanel {
+    pub fn show(&mut self, ui: &mut egui::Ui, reference: &Reference, font_size: f32) {
+        ui.add(
+            egui::TextEdit::singleline(&mut self.search)
+                .hint_text("Search the reference")
+                .desired_width(f32::INFINITY),
+        );
+        ui.separator();
+
+        let search = self.search.trim().to_lowercase();
+        let font_id = egui::FontId::new(font_size, egui::FontFamily::Monospace);
+        egui::ScrollArea::vertical()
+            .id_source("reference")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (title, entries) in [
+                    ("Builtin functions", &reference.builtins),
+                    ("Keywords", &reference.keywords),
+                    ("Errors", &reference.errors),
+                ] {
+                    let found: Vec<&Entry> = entries
+                        .iter()
+                        .filter(|entry| {
+                            entry.name.to_lowercase().contains(&search)
+                                || entry.description.to_lowercase().contains(&search)
+                        })
+                        .collect();
+                    if found.is_empty() {
+                        continue;
+                    }
+                    // Searching opens the sections with results
+                    egui::CollapsingHeader::new(format!("{} ({})", title, found.len()))
+                        .open((!search.is_empty()).then_some(true))
+                        .show(ui, |ui| {
+                            for entry in found {
+                                draw_entry(ui, entry, &font_id);
+                            }
+                        });
+                }
+            });
+    }
+}
+
+fn draw_entry(ui: &mut egui::Ui, entry: &Entry, font_id: &egui::FontId) {
+    let title = entry.signature.as_deref().unwrap_or(&entry.name);
+    ui.label(
+        egui::RichText::new(title)
+            .font(font_id.clone())
+            .color(ui.visuals().strong_text_color()),
+    );
+    ui.label(&entry.description);
+    if let Some(ref example) = entry.example {
+        egui::Frame::none()
+            .fill(ui.visuals().extreme_bg_color)
+            .inner_margin(egui::style::Margin::same(4.0))
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(example).font(font_id.clone()));
+            });
+    }
+    ui.add_space(6.0);
+}
@@ -0,0 +1,122 @@
+//! Interactive betty session in the console panel. The interpreter is started without
+//! a file, and the lines typed by the user, or sent from the editor, are written to
+//! its stdin. Its output is read as in [`runner`](super::runner), which repaints the
+//! IDE when it comes.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::process::{self, Child, ChildStdin, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use eframe::egui;
+
+use super::runner::{self, Stream};
+
+pub struct Repl {
+    child: Child,
+    stdin: ChildStdin,
+
+    /// Lines of stdout and stderr, in the order they were read
+    lines: Receiver<(Stream, Vec<u8>)>,
+
+    /// Color of the lines of stderr, if they are colored
+    stderr_color: Option<[u8; 3]>,
+
+    /// Everything written to and read from the interpreter
+    pub transcript: String,
+
+    /// Has the interpreter exited?
+    exited: bool,
+
+    /// Have both pipes been read to their end?
+    closed: bool,
+}
+
+impl Repl {
+    /// Start the interpreter in 'betty_exe_path' in interactive mode, with the
+    /// environment variables 'env'. The lines of stderr are given 'stderr_color'.
+    /// 'ctx' is repainted when the interpreter prints.
+    pub fn start(
+        betty_exe_path: &str,
+        env: &BTreeMap<String, String>,
+        stderr_color: Option<[u8; 3]>,
+        ctx: &egui::Context,
+    ) -> io::Result<Self> {
+        let mut child = process::Command::new(betty_exe_path)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "stdin is not piped"))?;
+        let (sender, lines) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            runner::read_in_background(stdout, Stream::Stdout, sender.clone(), ctx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            runner::read_in_background(stderr, Stream::Stderr, sender, ctx.clone());
+        }
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            stderr_color,
+            transcript: String::new(),
+            exited: false,
+            closed: false,
+        })
+    }
+
+    /// Write 'code' to the interpreter, as if it was typed
+    pub fn send(&mut self, code: &str) -> io::Result<()> {
+        for line in code.lines() {
+            self.transcript.push_str(&format!("> {}\n", line));
+        }
+        self.stdin.write_all(code.as_bytes())?;
+        if !code.ends_with('\n') {
+            self.stdin.write_all(b"\n")?;
+        }
+        self.stdin.flush()
+    }
+
+    /// Add the output of the interpreter to the transcript. Return whether the
+    /// interpreter is still running.
+    pub fn poll(&mut self) -> bool {
+        loop {
+            match self.lines.try_recv() {
+                Ok((stream, line)) => {
+                    runner::push_line(&mut self.transcript, stream, &line, self.stderr_color)
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    if !self.exited && matches!(self.child.try_wait(), Ok(Some(_))) {
+                        self.exited = true;
+                        self.transcript
+                            .push_str("--- The interpreter has exited ---\n");
+                    }
+                    break;
+                }
+            }
+        }
+        !self.exited
+    }
+
+    /// Whether all the output of the interpreter has been read, as it is exiting. No
+    /// repaint tells when it has exited, so it must be polled meanwhile.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        // The interpreter is not needed without its session
+        let _ = self.child.kill();
+    }
+}
@@ -0,0 +1,233 @@
+//! Run configurations: named ways of running the programs of a project, each with its
+//! own interpreter, arguments, working directory and environment variables. The
+//! project is the folder of the open file, and its configurations are stored in
+//! `.colors/run.json` inside it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use serde_derive::{Deserialize, Serialize};
+
+use super::jsonedit;
+
+/// File of the configurations, in the project folder
+const CONFIG_FILE: [&str; 2] = [".colors", "run.json"];
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunConfig {
+    pub name: String,
+    pub betty_exe_path: Option<String>, // replaces the one in the settings
+    pub args: String,                   // given after the path of the file
+    pub cwd: Option<String>,            // relative to the project folder
+    pub env: BTreeMap<String, String>,
+}
+
+/// The configurations of a project
+pub struct RunConfigs {
+    /// Project folder
+    dir: PathBuf,
+
+    pub configs: Vec<RunConfig>,
+}
+
+impl RunConfigs {
+    /// No configuration for the project in 'dir'
+    pub fn empty(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            configs: Vec::new(),
+        }
+    }
+
+    /// Load the configurations of the project in 'dir'. Having none is fine.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let mut configs = Self::empty(dir);
+        let path = configs.path();
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(configs),
+            Err(err) => return Err(format!("'{}' could not be read: {}", path.display(), err)),
+        };
+        configs.configs = serde_json::from_str(&jsonedit::strip_comments(&json))
+            .map_err(|err| format!("'{}' is not valid: {}", path.display(), err))?;
+        Ok(configs)
+    }
+
+    /// Write the configurations to the project folder
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+        serde::Serialize::serialize(&self.configs, &mut serializer)?;
+        fs::write(path, json)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self) -> PathBuf {
+        CONFIG_FILE
+            .iter()
+            .fold(self.dir.clone(), |path, name| path.join(name))
+    }
+
+    /// The configuration called 'name'
+    pub fn get(&self, name: &str) -> Option<&RunConfig> {
+        self.configs.iter().find(|config| config.name == name)
+    }
+
+    /// The working directory of 'config', if it has one
+    pub fn cwd(&self, config: &RunConfig) -> Option<PathBuf> {
+        config.cwd.as_ref().map(|cwd| self.dir.join(cwd))
+    }
+}
+
+/// The project folder of the file in 'path'
+pub fn project_dir(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Window to add, edit and remove the configurations of a project
+pub struct RunConfigsWindow {
+    /// Name of the environment variable being added, for each configuration
+    new_vars: Vec<String>,
+
+    /// Is the window still open?
+    open: bool,
+}
+
+impl RunConfigsWindow {
+    pub fn new() -> Self {
+        Self {
+            new_vars: Vec::new(),
+            open: true,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draw the window, editing 'configs' directly. Return whether they must be saved.
+    pub fn show(&mut self, ctx: &egui::Context, configs: &mut RunConfigs) -> bool {
+        let mut save = false;
+        let mut open = self.open;
+        self.new_vars.resize(configs.configs.len(), String::new());
+
+        egui::Window::new("Run configurations")
+            .open(&mut open)
+            .default_size((500.0, 400.0))
+            .show(ctx, |ui| {
+                ui.label(format!("Project: {}", configs.dir().display()));
+                ui.separator();
+
+                let mut removed = None;
+                egui::ScrollArea::vertical()
+                    .id_source("run_configs")
+                    .max_height(ui.available_height() - 40.0)
+                    .show(ui, |ui| {
+                        for (idx, config) in configs.configs.iter_mut().enumerate() {
+                            egui::Grid::new(("run_config", idx))
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    ui.label("Name");
+                                    ui.text_edit_singleline(&mut config.name);
+                                    ui.end_row();
+
+                                    ui.label("betty executable");
+                                    draw_optional(
+                                        ui,
+                                        &mut config.betty_exe_path,
+                                        "From the settings",
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Arguments");
+                                    ui.text_edit_singleline(&mut config.args);
+                                    ui.end_row();
+
+                                    ui.label("Working directory");
+                                    draw_optional(ui, &mut config.cwd, "Unchanged");
+                                    ui.end_row();
+
+                                    ui.label("Environment");
+                                    ui.vertical(|ui| {
+                                        self.draw_env(ui, &mut config.env, idx);
+                                    });
+                                    ui.end_row();
+                                });
+                            if ui.button("Remove").clicked() {
+                                removed = Some(idx);
+                            }
+                            ui.separator();
+                        }
+                    });
+
+                if let Some(idx) = removed {
+                    configs.configs.remove(idx);
+                    self.new_vars.remove(idx);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add configuration").clicked() {
+                        configs.configs.push(RunConfig {
+                            name: format!("Configuration {}", configs.configs.len() + 1),
+                            ..RunConfig::default()
+                        });
+                    }
+                    save = ui.button("Save").clicked();
+                });
+            });
+
+        self.open = open;
+        save
+    }
+
+    /// Draw the environment variables of the configuration at 'idx'
+    fn draw_env(&mut self, ui: &mut egui::Ui, env: &mut BTreeMap<String, String>, idx: usize) {
+        let mut removed = None;
+        for (name, value) in env.iter_mut() {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                ui.text_edit_singleline(value);
+                if ui.button("Remove").clicked() {
+                    removed = Some(name.clone());
+                }
+            });
+        }
+        if let Some(name) = removed {
+            env.remove(&name);
+        }
+
+        ui.horizontal(|ui| {
+            let new_var = &mut self.new_vars[idx];
+            ui.add(egui::TextEdit::singleline(new_var).hint_text("Variable"));
+            let name = new_var.trim();
+            if ui.button("Add").clicked() && !name.is_empty() {
+                env.entry(name.to_string()).or_default();
+                new_var.clear();
+            }
+        });
+    }
+}
+
+/// Draw a text field for 'value', which is [`None`] when the field is empty
+fn draw_optional(ui: &mut egui::Ui, value: &mut Option<String>, hint: &str) {
+    let mut text = value.clone().unwrap_or_default();
+    if ui
+        .add(egui::TextEdit::singleline(&mut text).hint_text(hint))
+        .changed()
+    {
+        *value = (!text.trim().is_empty()).then_some(text);
+    }
+}
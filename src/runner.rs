@@ -0,0 +1,291 @@
+//! Programs run from the IDE. They run in the background, so that the IDE does not
+//! freeze while they run and they can be stopped. The output of each pipe of the
+//! program is read line by line by a thread, and sent to the IDE as it comes, tagged
+//! with its pipe so that the lines of stderr can be told apart. The threads repaint the
+//! IDE when lines come, so that it does not need to poll the program.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter;
+use std::mem;
+use std::process::{self, Child, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+/// The pipe a line of output was read from
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A program that is running, or has exited and whose output is still being read
+pub struct Run {
+    child: Child,
+
+    /// Lines of stdout and stderr, in the order they were read
+    lines: Receiver<(Stream, Vec<u8>)>,
+
+    /// Color of the lines of stderr, if they are colored
+    stderr_color: Option<[u8; 3]>,
+
+    /// Has the user stopped the program?
+    cancelled: bool,
+
+    /// Was the program stopped for running too long?
+    timed_out: bool,
+
+    /// Have both pipes been read to their end?
+    closed: bool,
+
+    /// When the program started
+    started: Instant,
+    started_at: chrono::DateTime<chrono::Local>,
+
+    /// The program and its arguments, separated by spaces
+    command_line: String,
+
+    /// Logs how long the program ran, when the run is dropped
+    _span: tracing::Span,
+}
+
+/// What a run produced since it was last polled
+pub struct Progress {
+    pub output: String,             // new lines of stdout and stderr
+    pub status: Option<ExitStatus>, // set once the program has exited and all its output was read
+}
+
+impl Run {
+    /// Start 'command' with its output piped to the IDE, which 'ctx' repaints. The lines
+    /// of stderr are given 'stderr_color' with ANSI sequences.
+    pub fn start(
+        mut command: process::Command,
+        stderr_color: Option<[u8; 3]>,
+        ctx: &egui::Context,
+    ) -> io::Result<Self> {
+        let command_line = iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, lines) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            read_in_background(stdout, Stream::Stdout, sender.clone(), ctx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            read_in_background(stderr, Stream::Stderr, sender, ctx.clone());
+        }
+
+        Ok(Self {
+            child,
+            lines,
+            stderr_color,
+            cancelled: false,
+            timed_out: false,
+            closed: false,
+            started: Instant::now(),
+            started_at: chrono::Local::now(),
+            _span: tracing::info_span!("run", command = %command_line),
+            command_line,
+        })
+    }
+
+    /// Terminate the program, along with the programs it started
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.cancelled = true;
+        self.kill()
+    }
+
+    /// Terminate the program if it has been running for longer than 'limit'. Return
+    /// whether it was terminated.
+    pub fn stop_after(&mut self, limit: Duration) -> io::Result<bool> {
+        if self.cancelled || self.timed_out || self.elapsed() < limit {
+            return Ok(false);
+        }
+        self.timed_out = true;
+        self.kill().map(|()| true)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        // Killing the program does not stop the programs it started
+        #[cfg(windows)]
+        {
+            let status = without_window(&mut process::Command::new("taskkill"))
+                .args(["/T", "/F", "/PID", &self.child.id().to_string()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            if matches!(status, Ok(status) if status.success()) {
+                return Ok(());
+            }
+        }
+        self.child.kill()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Whether all the output of the program has been read, as it is exiting. No
+    /// repaint tells when it has exited, so it must be polled meanwhile.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn command_line(&self) -> &str {
+        &self.command_line
+    }
+
+    /// Date and time at which the program started
+    pub fn started_at(&self) -> chrono::DateTime<chrono::Local> {
+        self.started_at
+    }
+
+    /// Time since the program started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Return the output read since the last call, without waiting for more
+    pub fn poll(&mut self) -> io::Result<Progress> {
+        let mut output = String::new();
+        loop {
+            match self.lines.try_recv() {
+                Ok((stream, line)) => push_line(&mut output, stream, &line, self.stderr_color),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        let status = if self.closed {
+            self.child.try_wait()?
+        } else {
+            None
+        };
+        Ok(Progress { output, status })
+    }
+}
+
+/// Send each line of 'pipe', the program's 'stream', with 'sender' from a new thread,
+/// until the pipe is closed. 'ctx' is repainted after each line, and once it is closed.
+pub fn read_in_background<R: Read + Send + 'static>(
+    pipe: R,
+    stream: Stream,
+    sender: Sender<(Stream, Vec<u8>)>,
+    ctx: egui::Context,
+) {
+    thread::spawn(move || {
+        let mut pipe = BufReader::new(pipe);
+        loop {
+            let mut line = Vec::new();
+            match pipe.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send((stream, line)).is_err() {
+                        // The run was dropped
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        }
+        drop(sender);
+        ctx.request_repaint();
+    });
+}
+
+/// Add 'line' of 'stream' to 'output'. A line of stderr is given 'stderr_color', if
+/// any, up to its newline.
+pub fn push_line(output: &mut String, stream: Stream, line: &[u8], stderr_color: Option<[u8; 3]>) {
+    let line = String::from_utf8_lossy(line);
+    match (stream, stderr_color) {
+        (Stream::Stderr, Some([r, g, b])) => {
+            let text = line.trim_end_matches(['\r', '\n']);
+            output.push_str(&format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text));
+            output.push_str(&line[text.len()..]);
+        }
+        _ => output.push_str(&line),
+    }
+}
+
+/// Keep 'command' from opening a console window on Windows, as the commands the IDE
+/// runs for itself (such as `taskkill` or `reg`) would otherwise flash one
+pub fn without_window(command: &mut process::Command) -> &mut process::Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// Split the command line arguments 'args' on whitespace, except inside double quotes,
+/// which are removed
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut arg = String::new();
+    let mut quoted = false;
+    let mut started = false; // an argument is being read, maybe empty as ""
+
+    for ch in args.chars() {
+        match ch {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            ch if ch.is_whitespace() && !quoted => {
+                if started {
+                    split.push(mem::take(&mut arg));
+                    started = false;
+                }
+            }
+            ch => {
+                arg.push(ch);
+                started = true;
+            }
+        }
+    }
+    if started {
+        split.push(arg);
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_args_on_whitespace() {
+        assert_eq!(split_args("  a b\tc\n"), ["a", "b", "c"]);
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn split_args_keeps_quoted_spaces() {
+        assert_eq!(
+            split_args(r#"--out "my file.txt" x"y z"w"#),
+            ["--out", "my file.txt", "xy zw"]
+        );
+        // Empty quotes are an empty argument
+        assert_eq!(split_args(r#"a "" b"#), ["a", "", "b"]);
+        // An unclosed quote goes on to the end
+        assert_eq!(split_args(r#"a "b c"#), ["a", "b c"]);
+    }
+}
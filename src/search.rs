@@ -0,0 +1,67 @@
+//! Search of a text shown in a [`LayoutJob`], such as the console: the matches are
+//! highlighted, the current one more than the others.
+
+use std::ops::Range;
+
+use eframe::egui;
+use egui::text::LayoutJob;
+use egui::Color32;
+
+/// The ranges of the occurrences of 'query' in 'text', ignoring the ASCII case
+pub fn find_all(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // The case of ASCII characters is changed without changing the byte offsets
+    let text = text.to_ascii_lowercase();
+    let query = query.to_ascii_lowercase();
+    text.match_indices(&query)
+        .map(|(start, found)| start..start + found.len())
+        .collect()
+}
+
+/// Give a background to the 'matches' in 'job', the one at 'current' in another color
+pub fn highlight(job: &mut LayoutJob, matches: &[Range<usize>], current: Option<usize>) {
+    if matches.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + 2 * matches.len());
+
+    for section in job.sections.drain(..) {
+        let pieces = sections.len();
+        let mut start = section.byte_range.start;
+        let end = section.byte_range.end;
+
+        // The matches overlapping the section are split at its bounds
+        let first = matches.partition_point(|range| range.end <= start);
+        for (idx, range) in matches.iter().enumerate().skip(first) {
+            if range.start >= end {
+                break;
+            }
+            if range.start > start {
+                let mut before = section.clone();
+                before.byte_range = start..range.start;
+                sections.push(before);
+            }
+            let mut found = section.clone();
+            found.byte_range = range.start.max(start)..range.end.min(end);
+            found.format.background = if Some(idx) == current {
+                Color32::from_rgb(200, 120, 0)
+            } else {
+                Color32::from_rgba_unmultiplied(200, 200, 0, 80)
+            };
+            start = found.byte_range.end;
+            sections.push(found);
+        }
+        if start < end {
+            let mut after = section;
+            after.byte_range = start..end;
+            sections.push(after);
+        }
+        // Only the first piece of the section is after its leading space
+        for piece in sections.iter_mut().skip(pieces + 1) {
+            piece.leading_space = 0.0;
+        }
+    }
+    job.sections = sections;
+}
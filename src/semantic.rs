@@ -0,0 +1,256 @@
+//! Semantic analysis of betty code, on top of the [`Token`]s of the [`Highligher`].
+//! Names are collected over the whole buffer, without scopes, so a name is only
+//! reported when it is never defined anywhere in the code. The only scopes that are
+//! tracked are the bodies of the functions, to find their unused variables.
+//!
+//! [`Highligher`]: colors_highlight::Highligher
+
+use std::collections::{HashMap, HashSet};
+
+use colors_highlight::{Token, TokenType};
+
+use super::parser::{Diagnostic, Severity};
+
+/// Names defined in the code: assigned variables, functions and their parameters,
+/// loop variables, caught errors and imported names. 'words' are the literal values
+/// of the tokens of the whole code, in order, along with their types.
+pub fn defined_names<'a>(
+    words: impl IntoIterator<Item = (&'a str, TokenType)>,
+) -> HashSet<&'a str> {
+    // Whitespace and comments never separate a name from its definition
+    let words: Vec<(&str, TokenType)> = words
+        .into_iter()
+        .filter(|(word, typ)| *typ != TokenType::Comment && !word.trim().is_empty())
+        .collect();
+
+    let mut defined = HashSet::new();
+    for (i, &(word, typ)) in words.iter().enumerate() {
+        let next = words.get(i + 1).map(|&(next, _)| next);
+        match (typ, word) {
+            // 'name = ...', but not 'name == ...' nor 'name += ...'
+            (TokenType::Ident | TokenType::Fun, _) if next == Some("=") => {
+                defined.insert(word);
+            }
+
+            // 'fun name(a, b)' and 'fun(a, b)'
+            (TokenType::Kw, "fun") => {
+                let params = words[i + 1..]
+                    .iter()
+                    .skip_while(|&&(word, _)| word != "(")
+                    .take_while(|&&(word, _)| word != ")");
+                let name = words[i + 1..].iter().take_while(|&&(word, _)| word != "(");
+                defined.extend(
+                    name.chain(params)
+                        .filter(|(_, typ)| matches!(typ, TokenType::Ident | TokenType::Fun))
+                        .map(|&(word, _)| word),
+                );
+            }
+
+            // 'for i, x in ...' and 'foreach x in ...'
+            (TokenType::Kw, "for" | "foreach") => defined.extend(
+                words[i + 1..]
+                    .iter()
+                    .take_while(|&&(word, _)| word != "in")
+                    .filter(|(_, typ)| *typ == TokenType::Ident)
+                    .map(|&(word, _)| word),
+            ),
+
+            // 'catch err', 'using name' and 'as name'
+            (TokenType::Kw, "catch" | "using" | "as") => {
+                if let Some(&(next, TokenType::Ident)) = words.get(i + 1) {
+                    defined.insert(next);
+                }
+            }
+            _ => (),
+        }
+    }
+    defined
+}
+
+/// Mark the identifiers of 'line' that are not in 'defined' as [`TokenType::Undefined`].
+/// Return [`None`] if all of them are defined, so that the tokens can be reused.
+pub fn mark_undefined(line: &str, tokens: &[Token], defined: &HashSet<&str>) -> Option<Vec<Token>> {
+    let is_undefined = |i: usize| {
+        let Token(typ, span, _) = &tokens[i];
+        // Fields are not names ('vec.len')
+        let is_field = i > 0 && &line[tokens[i - 1].1.clone()] == ".";
+        matches!(typ, TokenType::Ident | TokenType::Fun)
+            && !is_field
+            && !defined.contains(&line[span.clone()])
+    };
+
+    if !(0..tokens.len()).any(is_undefined) {
+        return None;
+    }
+    Some(
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| {
+                let Token(typ, span, position) = token.clone();
+                if is_undefined(i) {
+                    Token(TokenType::Undefined, span, position)
+                } else {
+                    Token(typ, span, position)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Report the variables that are assigned in the body of a function but never read
+/// there. Names that also appear outside the function may be read elsewhere, so they
+/// are not reported.
+pub fn unused_variables(tokens: &[Token], source: &str) -> Vec<Diagnostic> {
+    let words: Vec<(&Token, &str)> = tokens
+        .iter()
+        .map(|token| (token, &source[token.1.clone()]))
+        .filter(|(Token(typ, ..), word)| *typ != TokenType::Comment && !word.trim().is_empty())
+        .collect();
+    let is_name = |i: usize| {
+        let (Token(typ, ..), _) = words[i];
+        let is_field = i > 0 && words[i - 1].1 == ".";
+        matches!(typ, TokenType::Ident | TokenType::Fun) && !is_field
+    };
+    let is_assigned = |i: usize| words.get(i + 1).map_or(false, |&(_, next)| next == "=");
+
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    for i in (0..words.len()).filter(|&i| is_name(i)) {
+        *occurrences.entry(words[i].1).or_default() += 1;
+    }
+
+    // Find the bodies of the functions, i.e. the words between their 'do' and 'end'
+    let mut bodies = Vec::new();
+    let mut blocks: Vec<Option<usize>> = Vec::new(); // start of the body, if a function
+    let mut is_fun = false;
+    for (i, &(Token(typ, ..), word)) in words.iter().enumerate() {
+        match (typ, word) {
+            (TokenType::Kw, "fun") => is_fun = true,
+            (TokenType::Op, "->") => is_fun = false,
+            (TokenType::Kw, "do") => {
+                blocks.push(is_fun.then_some(i + 1));
+                is_fun = false;
+            }
+            (TokenType::Kw, "end") => {
+                if let Some(Some(start)) = blocks.pop() {
+                    bodies.push(start..i);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut reported = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for body in bodies {
+        let mut assigned: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut read = HashSet::new();
+        for i in body.clone().filter(|&i| is_name(i)) {
+            if is_assigned(i) {
+                assigned.entry(words[i].1).or_default().push(i);
+            } else {
+                read.insert(words[i].1);
+            }
+        }
+
+        for (name, indexes) in assigned {
+            let in_body = body
+                .clone()
+                .filter(|&i| is_name(i) && words[i].1 == name)
+                .count();
+            if read.contains(name) || occurrences[name] > in_body {
+                continue;
+            }
+            for i in indexes.into_iter().filter(|&i| reported.insert(i)) {
+                let Token(_, span, position) = words[i].0;
+                diagnostics.push(Diagnostic {
+                    span: span.clone(),
+                    position: *position,
+                    message: format!("'{}' is assigned but never read", name),
+                    severity: Severity::Warning,
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colors_highlight::{Config, Highligher};
+
+    fn tokens(source: &str) -> Vec<Token> {
+        let config = Config {
+            name: "betty".into(),
+            betty: true,
+            line_comment: Some("|".into()),
+            string_delimiters: vec!['"'],
+            ..Config::default()
+        };
+        Highligher::new(source, &config).make_tokens().0
+    }
+
+    fn defined(source: &str) -> Vec<&str> {
+        let tokens = tokens(source);
+        let words = tokens
+            .iter()
+            .map(|Token(typ, span, _)| (&source[span.clone()], *typ));
+        let mut names: Vec<&str> = defined_names(words).into_iter().collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn names_are_defined_by_their_statements() {
+        let source = "x = 1\n\
+                      fun add(a, b) do return a + b end\n\
+                      for i, v in vec do end\n\
+                      foreach w in vec do end\n\
+                      try do catch err do end\n\
+                      using math as m\n\
+                      y == 2 | z = 3";
+        assert_eq!(
+            defined(source),
+            ["a", "add", "b", "err", "i", "m", "math", "v", "w", "x"]
+        );
+    }
+
+    #[test]
+    fn only_the_undefined_names_are_marked() {
+        let source = "x = y + vec.len";
+        let tokens = tokens(source);
+        let defined = HashSet::from(["x"]);
+        let marked = mark_undefined(source, &tokens, &defined).unwrap();
+        let undefined: Vec<&str> = marked
+            .iter()
+            .filter(|Token(typ, ..)| *typ == TokenType::Undefined)
+            .map(|Token(_, span, _)| &source[span.clone()])
+            .collect();
+        // 'len' is a field
+        assert_eq!(undefined, ["y", "vec"]);
+
+        let defined = HashSet::from(["x", "y", "vec"]);
+        assert!(mark_undefined(source, &tokens, &defined).is_none());
+    }
+
+    #[test]
+    fn variables_assigned_but_never_read_are_reported() {
+        let source = "fun f(a) do\n    unused = 1\n    used = a\n    return used\nend\n";
+        let diagnostics = unused_variables(&tokens(source), source);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(&source[diagnostic.span.clone()], "unused");
+        assert_eq!((diagnostic.position.line, diagnostic.position.col), (1, 4));
+        assert!(diagnostic.severity == Severity::Warning);
+    }
+
+    #[test]
+    fn variables_used_outside_the_function_are_not_reported() {
+        // Outside of a function, or read elsewhere
+        let source = "total = 0\nfun f() do\n    total = 1\nend\nprint(total)\n";
+        assert!(unused_variables(&tokens(source), source).is_empty());
+        let source = "g = fun(x) -> x\nif true do\n    kept = 1\nend\n";
+        assert!(unused_variables(&tokens(source), source).is_empty());
+    }
+}
@@ -1,71 +1,615 @@
 ///! Importing user settings from `settings.json`.
 ///! The following `struct`s represent the deserialization of the JSON file into
 ///! Rust elements.
+use std::collections::BTreeMap;
 use std::fs;
+use std::io;
+use std::path::Path;
 
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
 
+use super::jsonedit;
 use super::log;
-
-const SETTINGS_PATH: &str = "settings\\settings.json";
+use super::paths;
 
 /// Representation of the color of code elements in the editor. Colors are
 /// defined as arrays of three [`u8`], as per RGB standard.
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
 pub struct CodeColor {
     pub ident: [u8; 3],
     pub number: [u8; 3],
     pub string: [u8; 3],
     pub symbol: [u8; 3],
+    pub operator: [u8; 3],
     pub keyword: [u8; 3],
     pub builtin_fn: [u8; 3],
     pub fun: [u8; 4],
     pub comment: [u8; 3],
+    pub todo: [u8; 3], // TODO, FIXME and NOTE in comments
     pub error: [u8; 3],
+    pub undefined: [u8; 3],
     pub other: [u8; 3],
+    pub style: CodeStyle,
+}
+
+impl Default for CodeColor {
+    fn default() -> Self {
+        let italic = FontStyle {
+            italic: true,
+            ..FontStyle::default()
+        };
+        Self {
+            ident: [174, 214, 241],
+            number: [187, 143, 206],
+            string: [0, 255, 0],
+            symbol: [255, 128, 128],
+            operator: [255, 170, 100],
+            keyword: [255, 123, 114],
+            builtin_fn: [33, 97, 140],
+            fun: [218, 219, 153, 255],
+            comment: [160, 160, 160],
+            todo: [255, 215, 0],
+            error: [144, 238, 144],
+            undefined: [230, 180, 80],
+            other: [255, 255, 255],
+            style: CodeStyle {
+                keyword: FontStyle {
+                    bold: true,
+                    ..FontStyle::default()
+                },
+                comment: italic,
+                undefined: italic,
+                ..CodeStyle::default()
+            },
+        }
+    }
+}
+
+/// Font style of a code element. The bold text is drawn with the bold monospace font
+/// of [`crate::fonts`].
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct FontStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// Font style of each code element, named as in [`CodeColor`]. The elements that
+/// are not listed are plain.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct CodeStyle {
+    pub ident: FontStyle,
+    pub number: FontStyle,
+    pub string: FontStyle,
+    pub symbol: FontStyle,
+    pub operator: FontStyle,
+    pub keyword: FontStyle,
+    pub builtin_fn: FontStyle,
+    pub fun: FontStyle,
+    pub comment: FontStyle,
+    pub todo: FontStyle,
+    pub error: FontStyle,
+    pub undefined: FontStyle,
+    pub other: FontStyle,
+}
+
+/// Visuals of the widgets around the code. With `system`, they follow the dark or
+/// light mode of the operating system.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+/// Behavior of the code editor
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct EditorSettings {
+    pub word_wrap: bool,
+    pub autosave_interval: u64, // seconds after the last edit, 0 to never autosave
+    pub recovery_interval: u64, // seconds between copies of the unsaved contents, 0 to never
+    pub tab_size: usize,        // spaces inserted by Tab
+    pub show_whitespace: bool,
+    pub highlight_current_line: bool,
+    pub auto_pair: bool,       // close brackets and quotes when they are typed
+    pub spell_check: bool,     // mark the misspelled words of the comments and strings?
+    pub bom: bool,             // start the new files with a UTF-8 byte order mark?
+    pub max_file_size_mb: u64, // larger files are not opened, 0 for no limit
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            word_wrap: false,
+            autosave_interval: 0,
+            recovery_interval: 10,
+            tab_size: 4,
+            show_whitespace: false,
+            highlight_current_line: true,
+            auto_pair: true,
+            spell_check: true,
+            bom: false,
+            max_file_size_mb: 100,
+        }
+    }
+}
+
+/// Format of the documentation stubs generated above function declarations.
+/// `{name}` is replaced by the function name and `{param}` by each parameter.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocStub {
+    pub description: String, // first line of the block
+    pub param: String,       // one line per parameter
+}
+
+impl Default for DocStub {
+    fn default() -> Self {
+        Self {
+            description: "| {name}: description".into(),
+            param: "|   {param}: ".into(),
+        }
+    }
+}
+
+/// Names added by the user to the betty ones, e.g. for a custom betty library
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExtraWords {
+    pub keywords: Vec<String>,
+    pub builtins: Vec<String>,
+    pub errors: Vec<String>,
 }
 
-/// Represent the whole file `settings.json`
-#[derive(Deserialize)]
+/// How the contents of a file are loaded in the editor
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenAs {
+    Text,
+    Hex, // in the read-only hex view
+}
+
+/// Behavior of the IDE for the files with a given extension. The syntax is `betty`,
+/// `plain` (no highlighting) or the name of a syntax definition. The run command is a
+/// template where `{betty}` is replaced by the path to the betty executable and
+/// `{file}` by the path to the file. Files that cannot be run have no command. The
+/// language server is the command line of a server of the Language Server Protocol,
+/// the debugger the one of a debug adapter of the Debug Adapter Protocol, and the
+/// formatter the one of a program formatting the code read on stdin, where `{file}` is
+/// replaced by the path to the file. Without formatter, betty code is only reindented.
+/// The linter is a template like the run command, run after each save.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FileType {
+    pub syntax: String,
+    pub run: Option<String>,
+    pub open_as: OpenAs,
+    pub language_server: Option<String>,
+    pub debugger: Option<String>,
+    pub formatter: Option<String>,
+    pub linter: Option<String>,
+}
+
+impl Default for FileType {
+    /// The IDE is made for betty, so unknown files are treated as betty files
+    fn default() -> Self {
+        Self {
+            syntax: "betty".into(),
+            run: Some("{betty} {file}".into()),
+            open_as: OpenAs::Text,
+            language_server: None,
+            debugger: None,
+            formatter: None,
+            linter: None,
+        }
+    }
+}
+
+/// What the IDE logs, and where
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LogSettings {
+    pub enabled: bool,
+    pub level: log::Level,    // less severe records are ignored
+    pub file: Option<String>, // replaces `log/log.log` in the user folder
+    pub format: log::Format,
+    pub modules: BTreeMap<String, log::Level>, // replace 'level' for these modules
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: log::Level::Warning,
+            file: None,
+            format: log::Format::Text,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+/// Paste services the code can be shared on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareService {
+    #[default]
+    Gist,
+    Pastebin,
+}
+
+/// Where the code is shared. The token of the service is not a setting, and is kept
+/// apart in `share_token`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ShareSettings {
+    pub service: ShareService,
+    pub url: Option<String>, // replaces the address the snippets are posted to
+    pub public: bool,        // listed publicly, or only reachable by their URL?
+}
+
+/// Represent the whole file `settings.json`. The settings that are missing get
+/// their default value.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
+    pub ui_theme: UiTheme,
+    pub ui_scale: f32, // size of the whole interface, 1 being the size set by the system
+    pub theme: Option<String>, // replaces 'code_color' with the colors of a theme
     pub code_color: CodeColor,
     pub save_btn: bool,      // enable the save button?
     pub save_and_run: bool,  // save the file before running it?
     pub save_on_close: bool, // save the current file before closing the IDE?
     pub code_font_size: f32,
     pub console_font_size: f32,
+    pub console_max_bytes: usize, // larger outputs are truncated
+    pub console_max_lines: usize, // older lines are dropped, 0 for no limit
+    pub console_word_wrap: bool,
+    pub clear_console_on_run: bool, // empty the console before each run?
+    pub strip_ansi: bool, // remove the ANSI colors from the output instead of showing them?
+    pub stderr_color: Option<[u8; 3]>, // of the errors of the programs, in the console
+    pub run_timeout_secs: u64, // programs running longer are stopped, 0 for no limit
+    pub editor: EditorSettings,
     pub betty_exe_path: String,
+    pub doc_stub: DocStub,
+    pub block_comment: Option<(String, String)>, // multi-line comment delimiters
+    pub extra_words: ExtraWords,
+    pub file_types: BTreeMap<String, FileType>, // by extension, without the dot
+    pub run_args: BTreeMap<String, String>,     // arguments of the programs, by path
+    pub env: BTreeMap<String, String>,          // environment variables of the programs
+    pub spell_dictionary: Option<String>,       // word list, None for the one of the system
+    pub script_shortcuts: BTreeMap<String, String>, // scripts by shortcut, e.g. Ctrl+Alt+U
+    pub share: ShareSettings,
+    pub log: LogSettings,
 }
 
+impl Default for Settings {
+    fn default() -> Self {
+        let plain = FileType {
+            syntax: "plain".into(),
+            run: None,
+            open_as: OpenAs::Text,
+            language_server: None,
+            debugger: None,
+            formatter: None,
+            linter: None,
+        };
+        Self {
+            ui_theme: UiTheme::Dark,
+            ui_scale: 1.0,
+            theme: Some("dark".into()),
+            code_color: CodeColor::default(),
+            save_btn: false,
+            save_and_run: true,
+            save_on_close: true,
+            code_font_size: 17.0,
+            console_font_size: 15.0,
+            console_max_bytes: 100_000,
+            console_max_lines: 0,
+            console_word_wrap: false,
+            clear_console_on_run: true,
+            strip_ansi: false,
+            stderr_color: Some(DEFAULT_STDERR_COLOR),
+            run_timeout_secs: 0,
+            editor: EditorSettings::default(),
+            betty_exe_path: format!("betty{}", std::env::consts::EXE_SUFFIX),
+            doc_stub: DocStub::default(),
+            block_comment: Some(("|*".into(), "*|".into())),
+            extra_words: ExtraWords::default(),
+            file_types: BTreeMap::from([
+                ("betty".into(), FileType::default()),
+                ("txt".into(), plain.clone()),
+                ("md".into(), plain),
+            ]),
+            run_args: BTreeMap::new(),
+            env: BTreeMap::new(),
+            spell_dictionary: None,
+            script_shortcuts: BTreeMap::new(),
+            share: ShareSettings::default(),
+            log: LogSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Configuration of the [`colors_highlight::Highligher`]
+    pub fn highlighter_config(&self) -> colors_highlight::Config {
+        colors_highlight::Config {
+            name: "betty".into(),
+            betty: true,
+            line_comment: Some("|".into()),
+            block_comment: self.block_comment.clone(),
+            string_delimiters: vec!['"'],
+            keywords: self.extra_words.keywords.clone(),
+            builtins: self.extra_words.builtins.clone(),
+            errors: self.extra_words.errors.clone(),
+            operators: Vec::new(),
+        }
+    }
+
+    /// Return the [`FileType`] matching the extension of 'path'. Files without a
+    /// path or whose extension is not configured get the default one.
+    pub fn file_type(&self, path: Option<&Path>) -> FileType {
+        path.and_then(Path::extension)
+            .and_then(|ext| self.file_types.get(&ext.to_string_lossy().to_lowercase()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The arguments given to the program in 'path' when it runs
+    pub fn run_args(&self, path: &Path) -> &str {
+        self.run_args
+            .get(&*path.to_string_lossy())
+            .map_or("", String::as_str)
+    }
+
+    /// Set the arguments given to the program in 'path', forgetting them if empty
+    pub fn set_run_args(&mut self, path: &Path, args: String) {
+        let path = path.to_string_lossy().into_owned();
+        if args.trim().is_empty() {
+            self.run_args.remove(&path);
+        } else {
+            self.run_args.insert(path, args);
+        }
+    }
+}
+
+/// Range of the font sizes
+pub const MIN_FONT_SIZE: f32 = 6.0;
+pub const MAX_FONT_SIZE: f32 = 72.0;
+
+/// Range of the interface scales
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+/// Color of the lines of stderr, red
+pub const DEFAULT_STDERR_COLOR: [u8; 3] = [255, 85, 85];
+
+/// Largest number of spaces inserted by Tab
+pub const MAX_TAB_SIZE: usize = 16;
+
+/// Settings that are maps from names to values, rather than `struct`s, by their keys
+/// joined with dots. Their keys are not checked one by one.
+const MAP_KEYS: [&str; 5] = [
+    "file_types",
+    "run_args",
+    "env",
+    "script_shortcuts",
+    "log.modules",
+];
+
 /// Try to retrieve the JSON contents in the settings file, and try to deserialize
-/// the data as a [`Settings`] `struct`. If it is not possible to do any of them,
-/// log the error and return [`None`].
+/// the data as a [`Settings`] `struct`, one key at a time. Each key that is unknown
+/// or has an invalid value is reported along with its line, and the default value is
+/// used in its place. If the file cannot be read or is not valid JSON, the default
+/// settings are used. The problems are logged and returned.
 impl Settings {
-    pub fn get() -> Option<Self> {
-        let file = match fs::OpenOptions::new().read(true).open(SETTINGS_PATH) {
-            Ok(file) => file,
+    pub fn get() -> (Self, Vec<String>) {
+        let path = paths::settings_file();
+        let parsed = fs::read_to_string(&path)
+            .map_err(|err| format!("it could not be read: {}", err))
+            .map(|text| jsonedit::strip_comments(&text))
+            .and_then(|text| match serde_json::from_str::<Value>(&text) {
+                Ok(Value::Object(user)) => Ok((text, user)),
+                Ok(_) => Err("it does not contain an object".into()),
+                Err(err) => Err(format!("it is not valid JSON: {}", err)),
+            });
+        let (text, user) = match parsed {
+            Ok(parsed) => parsed,
             Err(err) => {
-                log::critical(format!(
-                    "An error occurred while accessing '{}'.
-                        The IDE will rely on its default settings. 
-                        Reason: {}",
-                    SETTINGS_PATH, err
-                ));
-                return None;
+                let problem = format!(
+                    "The default settings are used, because of '{}': {}",
+                    path.display(),
+                    err
+                );
+                log::critical!(problem.clone());
+                return (Self::default(), vec![problem]);
             }
         };
 
-        match serde_json::from_reader(file) {
-            Ok(settings) => Some(settings),
-            Err(err) => {
-                log::critical(format!(
-                    "An error occurred while parsing '{}'. 
-                        The IDE will rely on its default settings. 
-                        Details: {}",
-                    SETTINGS_PATH, err
-                ));
-                None
+        let mut merged =
+            serde_json::to_value(Self::default()).expect("Settings are always serializable");
+        let mut leaves = Vec::new();
+        let mut problems = Vec::new();
+        find_leaves(
+            &merged,
+            &user,
+            &mut Vec::new(),
+            &mut leaves,
+            &mut problems,
+            &text,
+        );
+
+        // Each value must be valid along with the ones accepted before it
+        for (keys, value) in leaves {
+            let Some(slot) = keys
+                .iter()
+                .try_fold(&mut merged, |value, key| value.get_mut(key))
+            else {
+                continue;
+            };
+            let default = std::mem::replace(slot, value);
+            if let Err(err) = serde_json::from_value::<Self>(merged.clone()) {
+                problems.push(problem(&text, &keys, &format!("is invalid ({})", err)));
+                if let Some(slot) = keys
+                    .iter()
+                    .try_fold(&mut merged, |value, key| value.get_mut(key))
+                {
+                    *slot = default;
+                }
             }
         }
+
+        let mut settings: Self = serde_json::from_value(merged).unwrap_or_default();
+        settings.validate(&text, &mut problems);
+
+        log::configure(&settings.log);
+        for problem in &problems {
+            log::warning!(problem.clone());
+        }
+        (settings, problems)
+    }
+
+    /// Check the values that are well-formed but unusable, resetting them
+    fn validate(&mut self, text: &str, problems: &mut Vec<String>) {
+        let defaults = Self::default();
+        for (key, size, default) in [
+            (
+                "code_font_size",
+                &mut self.code_font_size,
+                defaults.code_font_size,
+            ),
+            (
+                "console_font_size",
+                &mut self.console_font_size,
+                defaults.console_font_size,
+            ),
+        ] {
+            if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(size) {
+                let descr = format!("must be between {} and {}", MIN_FONT_SIZE, MAX_FONT_SIZE);
+                problems.push(problem(text, &[key.to_string()], &descr));
+                *size = default;
+            }
+        }
+
+        if !(1..=MAX_TAB_SIZE).contains(&self.editor.tab_size) {
+            let descr = format!("must be between 1 and {}", MAX_TAB_SIZE);
+            let keys = ["editor".to_string(), "tab_size".to_string()];
+            problems.push(problem(text, &keys, &descr));
+            self.editor.tab_size = defaults.editor.tab_size;
+        }
+
+        if !(MIN_UI_SCALE..=MAX_UI_SCALE).contains(&self.ui_scale) {
+            let descr = format!("must be between {} and {}", MIN_UI_SCALE, MAX_UI_SCALE);
+            problems.push(problem(text, &["ui_scale".to_string()], &descr));
+            self.ui_scale = defaults.ui_scale;
+        }
+
+        // An empty delimiter would match everywhere
+        let empty = self
+            .block_comment
+            .as_ref()
+            .map_or(false, |(open, close)| open.is_empty() || close.is_empty());
+        if empty {
+            let descr = "must not have an empty delimiter";
+            problems.push(problem(text, &["block_comment".to_string()], descr));
+            self.block_comment = defaults.block_comment;
+        }
+    }
+}
+
+/// Collect the values of 'user' to be checked, with the path of their keys. Objects
+/// are entered when they are `struct`s in 'default', and unknown keys are reported.
+fn find_leaves(
+    default: &Value,
+    user: &serde_json::Map<String, Value>,
+    keys: &mut Vec<String>,
+    leaves: &mut Vec<(Vec<String>, Value)>,
+    problems: &mut Vec<String>,
+    text: &str,
+) {
+    for (key, value) in user {
+        keys.push(key.clone());
+        match (default.get(key), value) {
+            (None, _) => problems.push(problem(text, keys, "is not a setting, and is ignored")),
+            (Some(Value::Object(_)), Value::Object(user))
+                if !MAP_KEYS.contains(&keys.join(".").as_str()) =>
+            {
+                find_leaves(&default[key], user, keys, leaves, problems, text)
+            }
+            _ => leaves.push((keys.clone(), value.clone())),
+        }
+        keys.pop();
+    }
+}
+
+/// Describe a problem with the setting at 'keys', along with its line in 'text'
+fn problem(text: &str, keys: &[String], descr: &str) -> String {
+    let name = keys.join(".");
+    match key_line(text, keys) {
+        Some(line) => format!("Line {}: '{}' {}", line, name, descr),
+        None => format!("'{}' {}", name, descr),
+    }
+}
+
+/// Find the line of the last key in 'keys', looking for each key after the previous
+/// one. The first occurrence that is followed by ':' is taken.
+fn key_line(text: &str, keys: &[String]) -> Option<usize> {
+    let mut start = 0;
+    for key in keys {
+        let quoted = format!("\"{}\"", key);
+        start += text[start..].match_indices(&quoted).find_map(|(idx, _)| {
+            let after = text[start + idx + quoted.len()..].trim_start();
+            after.starts_with(':').then_some(idx)
+        })?;
+    }
+    Some(text[..start].matches('\n').count() + 1)
+}
+
+impl Settings {
+    /// Write the settings back to the settings file. Only the values that changed
+    /// are replaced, keeping the order and formatting of the file. If the file is
+    /// missing or invalid, it is written again with the same indentation.
+    pub fn save(&self) -> io::Result<()> {
+        let path = paths::settings_file();
+        let new = serde_json::to_value(self)?;
+        let defaults = serde_json::to_value(Self::default())?;
+        if let Some(json) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| jsonedit::update(&text, &new, &defaults, &MAP_KEYS))
+        {
+            return fs::write(path, json);
+        }
+
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+        serde::Serialize::serialize(self, &mut serializer)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_block_comment_delimiters_fall_back_to_the_default() {
+        let text = "{\n    \"block_comment\": [\"\", \"*|\"]\n}";
+        let mut settings = Settings {
+            block_comment: Some((String::new(), "*|".into())),
+            ..Settings::default()
+        };
+        let mut problems = Vec::new();
+        settings.validate(text, &mut problems);
+        assert_eq!(settings.block_comment, Settings::default().block_comment);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Line 2: 'block_comment'"));
     }
 }
@@ -0,0 +1,240 @@
+//! Sharing of the code on a paste service, a GitHub gist or Pastebin, which returns the
+//! URL of the snippet. The snippet is uploaded in the background by `curl`, so that
+//! the IDE needs no HTTP client of its own. The token of the service is kept in
+//! `share_token` next to the settings rather than in `settings.json`, readable by the
+//! user only, and it is given to `curl` through its standard input rather than on its
+//! command line, where other programs could see it.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use super::paths;
+use super::settings::{ShareService, ShareSettings};
+
+/// Program uploading the snippets
+const CURL: &str = "curl";
+
+/// Where the snippets are posted when the `url` setting is not set
+const GIST_URL: &str = "https://api.github.com/gists";
+const PASTEBIN_URL: &str = "https://pastebin.com/api/api_post.php";
+
+/// An upload running in the background
+pub struct Share {
+    /// URL of the snippet, or why it could not be shared, once `curl` exits
+    receiver: Receiver<Result<String, String>>,
+}
+
+impl Share {
+    /// Upload 'code', named 'name', with the service of 'settings' and its 'token'
+    pub fn start(settings: &ShareSettings, token: &str, name: &str, code: &str) -> Self {
+        let config = curl_config(settings, token, name, code);
+        let service = settings.service;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(upload(&config).and_then(|body| snippet_url(service, &body)));
+        });
+        Self { receiver }
+    }
+
+    /// The URL of the snippet, or why it could not be shared, if the upload ended
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the upload stopped unexpectedly".into())),
+        }
+    }
+}
+
+/// Run `curl` with 'config', and return the body of the response if it succeeded
+fn upload(config: &str) -> Result<String, String> {
+    let mut child = Command::new(CURL)
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("'{}' could not be started: {}", CURL, err))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    // The status code of the response is written after its body
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    match status.trim().parse::<u16>() {
+        Ok(200..=299) => Ok(body.to_string()),
+        Ok(status) => Err(format!(
+            "the service answered with the status {}: {}",
+            status,
+            error_message(body)
+        )),
+        Err(_) => Err(format!("unexpected answer of the service: {}", stdout)),
+    }
+}
+
+/// The message of an error answered by the service, which is JSON for the gists
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|json| json.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+/// The URL of the snippet in the answer 'body' of 'service'
+fn snippet_url(service: ShareService, body: &str) -> Result<String, String> {
+    match service {
+        ShareService::Gist => serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|json| json.get("html_url")?.as_str().map(str::to_string))
+            .ok_or_else(|| format!("unexpected answer of the service: {}", body)),
+        // Pastebin answers errors with the status 200 as well
+        ShareService::Pastebin if body.trim().starts_with("http") => Ok(body.trim().to_string()),
+        ShareService::Pastebin => Err(body.trim().to_string()),
+    }
+}
+
+/// The options of `curl`, in the format of its config files, posting 'code' to the
+/// service of 'settings'
+fn curl_config(settings: &ShareSettings, token: &str, name: &str, code: &str) -> String {
+    let mut options = vec![
+        ("silent", None),
+        ("show-error", None),
+        ("write-out", Some("\n%{http_code}".to_string())),
+    ];
+    match settings.service {
+        ShareService::Gist => {
+            let body = json!({
+                "description": name,
+                "public": settings.public,
+                "files": { name: { "content": code } },
+            });
+            options.extend([
+                (
+                    "url",
+                    Some(settings.url.as_deref().unwrap_or(GIST_URL).into()),
+                ),
+                ("header", Some(format!("Authorization: Bearer {}", token))),
+                ("header", Some("Accept: application/vnd.github+json".into())),
+                ("header", Some("Content-Type: application/json".into())),
+                ("data-binary", Some(body.to_string())),
+            ]);
+        }
+        ShareService::Pastebin => {
+            // Private pastes need a user key, so the others are unlisted
+            let private = if settings.public { "0" } else { "1" };
+            options.extend([
+                (
+                    "url",
+                    Some(settings.url.as_deref().unwrap_or(PASTEBIN_URL).into()),
+                ),
+                ("data-urlencode", Some(format!("api_dev_key={}", token))),
+                ("data-urlencode", Some("api_option=paste".into())),
+                (
+                    "data-urlencode",
+                    Some(format!("api_paste_private={}", private)),
+                ),
+                ("data-urlencode", Some(format!("api_paste_name={}", name))),
+                ("data-urlencode", Some(format!("api_paste_code={}", code))),
+            ]);
+        }
+    }
+
+    options
+        .into_iter()
+        .map(|(option, value)| match value {
+            Some(value) => format!("{} = \"{}\"\n", option, quote(&value)),
+            None => format!("{}\n", option),
+        })
+        .collect()
+}
+
+/// 'value' with the characters that end a quoted value of a `curl` config escaped
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted
+}
+
+/// The file holding the token of the paste service, next to the settings
+fn token_file() -> PathBuf {
+    paths::settings_file().with_file_name("share_token")
+}
+
+/// The token of the paste service, empty if there is none
+pub fn load_token() -> String {
+    fs::read_to_string(token_file())
+        .map(|token| token.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Keep 'token' for the next uploads, in a file that only the user can read
+pub fn save_token(token: &str) -> io::Result<()> {
+    let path = token_file();
+    if token.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        };
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&path)?;
+    // The mode is only given to new files
+    #[cfg(unix)]
+    fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    // Windows has no modes: the file is restricted before the token is written
+    #[cfg(windows)]
+    restrict_to_user(&path)?;
+    file.write_all(token.as_bytes())
+}
+
+/// Give the current user alone access to 'path', replacing the permissions it
+/// inherits from its folder, with `icacls`
+#[cfg(windows)]
+fn restrict_to_user(path: &std::path::Path) -> io::Result<()> {
+    let user = std::env::var("USERNAME")
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    let mut command = Command::new("icacls");
+    command
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{}:F", user))
+        .stdin(Stdio::null());
+    let output = super::runner::without_window(&mut command).output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    // icacls prints some of its errors to stdout
+    let message = if output.stderr.is_empty() {
+        &output.stdout
+    } else {
+        &output.stderr
+    };
+    let message = String::from_utf8_lossy(message).trim().to_string();
+    Err(io::Error::new(io::ErrorKind::Other, message))
+}
@@ -0,0 +1,116 @@
+//! Signature help: the parameters of the function whose call is being typed, with
+//! the one the cursor is in, for the builtins of betty (from the [`Reference`]) and
+//! the functions declared in the code. Only the line of the cursor is read, so a call spanning many lines gets
+//! no help after its first line.
+
+use std::ops::Range;
+
+use colors_highlight::{Config, Highligher, Token, TokenType};
+
+use super::docstub;
+use super::reference::Reference;
+
+/// Parameter standing for any number of arguments, in the signatures of the builtins
+const VARIADIC: &str = "...";
+
+/// The signature of a function being called
+pub struct Signature {
+    pub label: String,             // e.g. `vpush_at(vec, index, value)`
+    pub params: Vec<Range<usize>>, // bytes of the parameters in the label
+    pub active: Option<usize>,     // parameter of the argument being typed
+}
+
+/// The signature of the function called at the byte 'cursor' of 'text', highlighted
+/// with 'config', if the cursor is between the parentheses of a call
+pub fn at_cursor(
+    text: &str,
+    cursor: usize,
+    config: &Config,
+    reference: &Reference,
+) -> Option<Signature> {
+    let before = text.get(..cursor)?;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let (name, commas) = open_call(&before[line_start..], config)?;
+
+    let label = match reference
+        .builtin(name)
+        .and_then(|entry| entry.signature.clone())
+    {
+        Some(signature) => signature,
+        None => {
+            let decl = text
+                .lines()
+                .filter_map(docstub::parse_fun_decl)
+                .find(|decl| decl.name == name)?;
+            format!("{}({})", decl.name, decl.params.join(", "))
+        }
+    };
+    let params = param_spans(&label);
+    let active = if commas < params.len() {
+        Some(commas)
+    } else {
+        // Any further argument belongs to the variadic parameter
+        params
+            .last()
+            .filter(|last| &label[(*last).clone()] == VARIADIC)
+            .map(|_| params.len() - 1)
+    };
+    Some(Signature {
+        label,
+        params,
+        active,
+    })
+}
+
+/// The name of the innermost function whose call is still open at the end of 'line',
+/// and the number of its arguments already typed, i.e. of the commas at its level.
+/// Commas in strings, comments, vectors and inner calls are not counted.
+fn open_call<'a>(line: &'a str, config: &Config) -> Option<(&'a str, usize)> {
+    let (tokens, _) = Highligher::new(line, config).make_tokens();
+    // One item per open bracket, with the name and the commas of the calls
+    let mut open: Vec<Option<(&str, usize)>> = Vec::new();
+    let mut callee = None;
+    for Token(typ, span, _) in tokens {
+        let word = &line[span];
+        if word.trim().is_empty() {
+            continue;
+        }
+        match (typ, word) {
+            (TokenType::Other, "(") => open.push(callee.map(|name| (name, 0))),
+            (TokenType::Other, "[" | "{") => open.push(None),
+            (TokenType::Other, ")" | "]" | "}") => {
+                open.pop();
+            }
+            (TokenType::Other, ",") => {
+                if let Some(Some((_, commas))) = open.last_mut() {
+                    *commas += 1;
+                }
+            }
+            _ => (),
+        }
+        callee = matches!(typ, TokenType::Fun | TokenType::BuiltinFun).then_some(word);
+    }
+    open.into_iter().rev().flatten().next()
+}
+
+/// The bytes of the parameters of 'label', between its parentheses
+fn param_spans(label: &str) -> Vec<Range<usize>> {
+    let Some(open) = label.find('(') else {
+        return Vec::new();
+    };
+    let close = label[open..]
+        .find(')')
+        .map_or(label.len(), |idx| open + idx);
+    let mut spans = Vec::new();
+    let mut start = open + 1;
+    for part in label[open + 1..close].split(',') {
+        let trimmed = part.trim_start();
+        let param_start = start + part.len() - trimmed.len();
+        let param = trimmed.trim_end();
+        if !param.is_empty() {
+            spans.push(param_start..param_start + param.len());
+        }
+        start += part.len() + 1;
+    }
+    spans
+}
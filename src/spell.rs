@@ -0,0 +1,242 @@
+//! Spell checking of the comments and strings of the code. The words are looked up in
+//! a word list (one word per line, as in `/usr/share/dict/words` or a Hunspell `.dic`
+//! file) and in the user dictionary, `dictionary.txt` next to the settings, where the
+//! words added from the editor are written.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use colors_highlight::{Config, Highligher, Token, TokenType};
+
+use super::log;
+use super::paths;
+
+/// Word lists looked for when none is set
+const SYSTEM_DICTIONARIES: [&str; 4] = [
+    "/usr/share/dict/words",
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/Library/Spelling/en_US.dic",
+];
+
+/// Suggestions offered for a misspelled word, at most
+const MAX_SUGGESTIONS: usize = 8;
+
+/// A misspelled word of the code
+#[derive(Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub span: Range<usize>,  // bytes
+    pub chars: Range<usize>, // characters
+}
+
+pub struct SpellChecker {
+    /// Word list the words were loaded from, as set
+    dictionary: Option<String>,
+
+    /// Known words, in lowercase
+    words: HashSet<String>,
+}
+
+impl SpellChecker {
+    /// Load the words of the word list 'dictionary', or of the system one if it is
+    /// None, and of the user dictionary. Without word list, no word is misspelled.
+    pub fn load(dictionary: Option<&str>) -> Self {
+        let path = match dictionary {
+            Some(path) => Some(PathBuf::from(path)),
+            None => SYSTEM_DICTIONARIES
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.is_file()),
+        };
+        let mut words = HashSet::new();
+        match path {
+            Some(ref path) => match fs::read_to_string(path) {
+                Ok(text) => words.extend(parse_words(&text)),
+                Err(err) => log::warning!(format!(
+                    "The dictionary '{}' could not be read: {}",
+                    path.display(),
+                    err
+                )),
+            },
+            None => log::info!("No dictionary was found, the spelling is not checked"),
+        }
+        // The user words alone would mark every other word
+        if !words.is_empty() {
+            if let Ok(text) = fs::read_to_string(user_dictionary()) {
+                words.extend(parse_words(&text));
+            }
+        }
+        Self {
+            dictionary: dictionary.map(str::to_string),
+            words,
+        }
+    }
+
+    /// Word list the words were loaded from, as set
+    pub fn dictionary(&self) -> Option<&str> {
+        self.dictionary.as_deref()
+    }
+
+    fn is_known(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+
+    /// The misspelled words of the comments and strings of 'text', highlighted with
+    /// 'config'
+    pub fn check(&self, text: &str, config: &Config) -> Vec<Misspelling> {
+        if self.words.is_empty() {
+            return Vec::new();
+        }
+        let (tokens, _) = Highligher::new(text, config).make_tokens();
+        let mut spans = Vec::new();
+        for Token(typ, span, _) in tokens {
+            if !matches!(typ, TokenType::Comment | TokenType::Str) {
+                continue;
+            }
+            let token = &text[span.clone()];
+            spans.extend(
+                words(token)
+                    .filter(|word| !self.is_known(&token[word.clone()]))
+                    .map(|word| span.start + word.start..span.start + word.end),
+            );
+        }
+
+        // The characters of the spans, found in one pass over the text
+        let mut misspellings = Vec::with_capacity(spans.len());
+        let mut chars = 0;
+        let mut offset = 0;
+        for span in spans {
+            chars += text[offset..span.start].chars().count();
+            let len = text[span.clone()].chars().count();
+            misspellings.push(Misspelling {
+                chars: chars..chars + len,
+                span: span.clone(),
+            });
+            chars += len;
+            offset = span.end;
+        }
+        misspellings
+    }
+
+    /// Known words one edit away from 'word' (a letter removed, added, replaced or
+    /// swapped with the next one), capitalized like it
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower: Vec<char> = word.to_lowercase().chars().collect();
+        let mut edits = Vec::new();
+        for idx in 0..=lower.len() {
+            let (before, after) = lower.split_at(idx);
+            let join = |middle: &[char], rest: &[char]| -> String {
+                before.iter().chain(middle).chain(rest).collect()
+            };
+            if let Some((_, rest)) = after.split_first() {
+                edits.push(join(&[], rest));
+                if let Some((second, rest)) = rest.split_first() {
+                    edits.push(join(&[*second, after[0]], rest));
+                }
+            }
+            for letter in 'a'..='z' {
+                edits.push(join(&[letter], after));
+                if let Some((_, rest)) = after.split_first() {
+                    edits.push(join(&[letter], rest));
+                }
+            }
+        }
+
+        let mut suggestions: Vec<String> = edits
+            .into_iter()
+            .filter(|edit| self.words.contains(edit))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        suggestions.sort();
+        suggestions.truncate(MAX_SUGGESTIONS);
+        if word.starts_with(char::is_uppercase) {
+            for suggestion in &mut suggestions {
+                *suggestion = capitalize(suggestion);
+            }
+        }
+        suggestions
+    }
+
+    /// Add 'word' to the user dictionary, so that it is no longer misspelled
+    pub fn add_word(&mut self, word: &str) -> io::Result<()> {
+        let path = user_dictionary();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        writeln!(file, "{}", word)?;
+        self.words.insert(word.to_lowercase());
+        Ok(())
+    }
+}
+
+/// The user dictionary, next to the settings
+fn user_dictionary() -> PathBuf {
+    paths::settings_file().with_file_name("dictionary.txt")
+}
+
+/// The words of a word list, in lowercase. The first line of a Hunspell file is the
+/// number of words, and the flags of its words follow a slash.
+fn parse_words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .filter_map(|line| line.split('/').next())
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+}
+
+/// The bytes of the words of 'text' worth checking. Words with digits or underscores,
+/// in camel case or in capitals are names rather than words, and addresses are skipped.
+fn words(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = None;
+    let mut words = Vec::new();
+    for (idx, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+        let in_word = ch.is_alphanumeric() || ch == '_' || ch == '\'';
+        match (in_word, start) {
+            (true, None) => start = Some(idx),
+            (false, Some(word_start)) => {
+                words.push(word_start..idx);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    words.into_iter().filter_map(move |mut word| {
+        // The letter of an escape sequence, e.g. '\n', is not part of the word
+        if text[..word.start].ends_with('\\') {
+            word.start += text[word.clone()].chars().next()?.len_utf8();
+        }
+        let trimmed = text[word.clone()].trim_start_matches('\'');
+        word.start = word.end - trimmed.len();
+        word.end = word.start + trimmed.trim_end_matches('\'').len();
+
+        let chunk_start = text[..word.start]
+            .rfind(char::is_whitespace)
+            .map_or(0, |idx| idx + 1);
+        let chunk_end = text[word.end..]
+            .find(char::is_whitespace)
+            .map_or(text.len(), |idx| word.end + idx);
+        let chunk = &text[chunk_start..chunk_end];
+
+        let word_text = &text[word.clone()];
+        let is_name = word_text.contains(|c: char| c.is_ascii_digit() || c == '_')
+            || word_text.chars().skip(1).any(char::is_uppercase);
+        let is_address = chunk.contains("://") || chunk.contains('@');
+        (word_text.chars().count() > 1 && !is_name && !is_address).then_some(word)
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
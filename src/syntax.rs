@@ -0,0 +1,240 @@
+//! Syntax definitions of languages other than betty, loaded from the JSON files in the
+//! `syntaxes` folder. Each file describes a language (keywords, comment markers,
+//! string delimiters and operators) and the extensions of its files.
+//! With the `syntect` feature, the Sublime Text grammars in the same folder are
+//! used for the languages that have no definition. They are slow to build, so they are
+//! loaded in the background, and their files are plain text until then.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "syntect")]
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+#[cfg(feature = "syntect")]
+use std::thread;
+
+use colors_highlight::Config;
+use serde_derive::Deserialize;
+#[cfg(feature = "syntect")]
+use syntect::parsing::SyntaxReference;
+
+#[cfg(feature = "syntect")]
+use super::grammar::Grammars;
+use super::jsonedit;
+use super::log;
+use super::paths;
+use super::settings::Settings;
+
+/// Representation of a syntax definition file
+#[derive(Deserialize)]
+struct SyntaxDef {
+    name: String,
+    extensions: Vec<String>, // without the dot
+    keywords: Vec<String>,
+    builtins: Vec<String>,
+    errors: Vec<String>,
+    line_comment: Option<String>,
+    block_comment: Option<(String, String)>,
+    string_delimiters: Vec<char>,
+    operators: Vec<String>,
+}
+
+impl SyntaxDef {
+    /// Reject the empty comment delimiters, which would match everywhere
+    fn validate(self) -> Result<Self, String> {
+        if self.line_comment.as_deref() == Some("") {
+            return Err("'line_comment' is empty".into());
+        }
+        if let Some((open, close)) = &self.block_comment {
+            if open.is_empty() || close.is_empty() {
+                return Err("a delimiter of 'block_comment' is empty".into());
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl From<SyntaxDef> for Config {
+    fn from(def: SyntaxDef) -> Self {
+        Self {
+            name: def.name,
+            betty: false,
+            line_comment: def.line_comment,
+            block_comment: def.block_comment,
+            string_delimiters: def.string_delimiters,
+            keywords: def.keywords,
+            builtins: def.builtins,
+            errors: def.errors,
+            operators: def.operators,
+        }
+    }
+}
+
+/// How the code of a file is highlighted
+pub enum Highlighting<'a> {
+    /// With the [`colors_highlight::Highligher`] configured for the language
+    Tokens(&'a Config),
+
+    /// With a Sublime Text grammar
+    #[cfg(feature = "syntect")]
+    Grammar(&'a Grammars, &'a SyntaxReference),
+
+    /// Not at all
+    Plain,
+}
+
+/// All the known languages, betty included
+pub struct Syntaxes {
+    /// Highlighter configuration by language name
+    configs: HashMap<String, Config>,
+
+    /// Language name by extension
+    extensions: HashMap<String, String>,
+
+    /// Sublime Text grammars, for the languages with no definition
+    #[cfg(feature = "syntect")]
+    grammars: Option<Grammars>,
+
+    /// The grammars being loaded
+    #[cfg(feature = "syntect")]
+    loading: Option<Receiver<Grammars>>,
+}
+
+impl Syntaxes {
+    /// Load the definitions in the syntaxes folder, along with the betty one built
+    /// from 'settings'. Definitions that cannot be read or parsed are logged and skipped.
+    pub fn load(settings: &Settings) -> Self {
+        let dir = paths::syntaxes_dir();
+        let betty = settings.highlighter_config();
+        let mut syntaxes = Self {
+            configs: HashMap::from([(betty.name.clone(), betty)]),
+            extensions: HashMap::new(),
+            #[cfg(feature = "syntect")]
+            grammars: None,
+            #[cfg(feature = "syntect")]
+            loading: {
+                let (sender, receiver) = mpsc::channel();
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    let _ = sender.send(Grammars::load(&dir));
+                    log::milestone!("grammars loaded");
+                });
+                Some(receiver)
+            },
+        };
+
+        // Having no other language is fine
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return syntaxes;
+        };
+
+        for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+
+            let def = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|json| {
+                    serde_json::from_str::<SyntaxDef>(&jsonedit::strip_comments(&json))
+                        .map_err(|err| err.to_string())
+                })
+                .and_then(SyntaxDef::validate);
+            match def {
+                Ok(def) => {
+                    for ext in &def.extensions {
+                        syntaxes
+                            .extensions
+                            .insert(ext.to_lowercase(), def.name.clone());
+                    }
+                    syntaxes.configs.insert(def.name.clone(), def.into());
+                }
+                Err(err) => log::warning!(format!(
+                    "The syntax definition '{}' could not be loaded: {}",
+                    path.display(),
+                    err
+                )),
+            }
+        }
+        syntaxes
+    }
+
+    /// Return how the file in 'path' should be highlighted. The file types in 'settings'
+    /// come first, then the extensions of the syntax definitions and of the grammars.
+    pub fn highlighting_for(&self, path: Option<&Path>, settings: &Settings) -> Highlighting<'_> {
+        let ext = path
+            .and_then(Path::extension)
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        let name = match ext {
+            Some(ext) if !settings.file_types.contains_key(&ext) => {
+                match self.extensions.get(&ext) {
+                    Some(name) => name.clone(),
+                    None => {
+                        #[cfg(feature = "syntect")]
+                        if let Some(ref grammars) = self.grammars {
+                            if let Some(syntax) = grammars.by_extension(&ext) {
+                                return Highlighting::Grammar(grammars, syntax);
+                            }
+                        }
+                        settings.file_type(path).syntax
+                    }
+                }
+            }
+            _ => settings.file_type(path).syntax,
+        };
+
+        if let Some(config) = self.configs.get(&name) {
+            return Highlighting::Tokens(config);
+        }
+        #[cfg(feature = "syntect")]
+        if let Some(ref grammars) = self.grammars {
+            if let Some(syntax) = grammars.by_name(&name) {
+                return Highlighting::Grammar(grammars, syntax);
+            }
+        }
+        Highlighting::Plain
+    }
+
+    /// Take the grammars once they are loaded. Return whether they are still loading.
+    #[cfg(feature = "syntect")]
+    pub fn poll_grammars(&mut self) -> bool {
+        let Some(ref loading) = self.loading else {
+            return false;
+        };
+        match loading.try_recv() {
+            Ok(grammars) => self.grammars = Some(grammars),
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => log::warning!("The grammars could not be loaded"),
+        }
+        self.loading = None;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(line_comment: &str, block_comment: (&str, &str)) -> SyntaxDef {
+        SyntaxDef {
+            name: "test".into(),
+            extensions: vec!["t".into()],
+            keywords: Vec::new(),
+            builtins: Vec::new(),
+            errors: Vec::new(),
+            line_comment: Some(line_comment.into()),
+            block_comment: Some((block_comment.0.into(), block_comment.1.into())),
+            string_delimiters: vec!['"'],
+            operators: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn empty_comment_delimiters_are_rejected() {
+        assert!(def("#", ("/*", "*/")).validate().is_ok());
+        assert!(def("", ("/*", "*/")).validate().is_err());
+        assert!(def("#", ("", "*/")).validate().is_err());
+        assert!(def("#", ("/*", "")).validate().is_err());
+    }
+}
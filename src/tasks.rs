@@ -0,0 +1,103 @@
+//! Tasks: shell commands of a project, such as building, testing or packaging it. The
+//! project is the folder of the open file, and its tasks are configured in
+//! `.colors/tasks.json` inside it, e.g.
+//!
+//! ```json
+//! {
+//!     "tasks": [
+//!         {"label": "test", "command": "betty tests/all.betty", "cwd": "tests"}
+//!     ]
+//! }
+//! ```
+//!
+//! They are run from the command palette, each one with its own page in the console.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_derive::Deserialize;
+
+use super::jsonedit;
+use super::runner::Run;
+
+/// File of the tasks, in the project folder
+const TASKS_FILE: [&str; 2] = [".colors", "tasks.json"];
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Task {
+    pub label: String,
+    pub command: String,     // run by the shell of the system
+    pub cwd: Option<String>, // relative to the project folder, which is the default
+    pub env: BTreeMap<String, String>,
+}
+
+/// The contents of the tasks file
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TasksFile {
+    tasks: Vec<Task>,
+}
+
+/// The tasks of a project
+pub struct Tasks {
+    /// Project folder
+    dir: PathBuf,
+
+    pub tasks: Vec<Task>,
+}
+
+impl Tasks {
+    /// Load the tasks of the project in 'dir'. Having none is fine.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = TASKS_FILE
+            .iter()
+            .fold(dir.to_path_buf(), |path, name| path.join(name));
+        let tasks = match fs::read_to_string(&path) {
+            Ok(json) => {
+                serde_json::from_str::<TasksFile>(&jsonedit::strip_comments(&json))
+                    .map_err(|err| format!("'{}' is not valid: {}", path.display(), err))?
+                    .tasks
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(format!("'{}' could not be read: {}", path.display(), err)),
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            tasks,
+        })
+    }
+
+    /// The command running 'task' with the shell, in its folder, with the environment
+    /// variables 'env' and then its own
+    pub fn command(&self, task: &Task, env: &BTreeMap<String, String>) -> Command {
+        #[cfg(windows)]
+        let mut command = {
+            let mut command = Command::new("cmd");
+            command.args(["/C", &task.command]);
+            command
+        };
+        #[cfg(not(windows))]
+        let mut command = {
+            let mut command = Command::new("sh");
+            command.args(["-c", &task.command]);
+            command
+        };
+        let cwd = task
+            .cwd
+            .as_ref()
+            .map_or_else(|| self.dir.clone(), |cwd| self.dir.join(cwd));
+        command.current_dir(cwd).envs(env).envs(&task.env);
+        command
+    }
+}
+
+/// A task run from the IDE, shown in its page of the console
+pub struct TaskRun {
+    pub label: String,
+    pub run: Option<Run>, // None once the task has exited
+    pub output: String,
+}
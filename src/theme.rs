@@ -0,0 +1,205 @@
+//! Presets of the code colors, selected by name with the `theme` setting. The
+//! built-in themes are followed by the ones in the `themes` folder, where each JSON
+//! file holds the code colors of a theme named after the file.
+
+use std::fs;
+use std::io;
+
+use super::jsonedit;
+use super::log;
+use super::paths;
+use super::settings::{CodeColor, CodeStyle, FontStyle};
+
+/// Font style shared by the built-in themes: keywords in bold, comments and undefined
+/// names in italic
+fn builtin_style() -> CodeStyle {
+    let italic = FontStyle {
+        italic: true,
+        ..FontStyle::default()
+    };
+    CodeStyle {
+        keyword: FontStyle {
+            bold: true,
+            ..FontStyle::default()
+        },
+        comment: italic,
+        undefined: italic,
+        ..CodeStyle::default()
+    }
+}
+
+/// The original colors of the IDE, which are the default ones
+fn dark() -> CodeColor {
+    CodeColor::default()
+}
+
+fn light() -> CodeColor {
+    CodeColor {
+        ident: [0, 16, 128],
+        number: [9, 134, 88],
+        string: [163, 21, 21],
+        symbol: [128, 0, 128],
+        operator: [60, 60, 60],
+        keyword: [0, 0, 255],
+        builtin_fn: [38, 127, 153],
+        fun: [121, 94, 38, 255],
+        comment: [0, 128, 0],
+        todo: [200, 120, 0],
+        error: [205, 49, 49],
+        undefined: [175, 0, 219],
+        other: [0, 0, 0],
+        style: builtin_style(),
+    }
+}
+
+fn solarized() -> CodeColor {
+    CodeColor {
+        ident: [131, 148, 150],
+        number: [211, 54, 130],
+        string: [42, 161, 152],
+        symbol: [108, 113, 196],
+        operator: [147, 161, 161],
+        keyword: [133, 153, 0],
+        builtin_fn: [38, 139, 210],
+        fun: [181, 137, 0, 255],
+        comment: [88, 110, 117],
+        todo: [203, 75, 22],
+        error: [220, 50, 47],
+        undefined: [203, 75, 22],
+        other: [131, 148, 150],
+        style: builtin_style(),
+    }
+}
+
+fn monokai() -> CodeColor {
+    CodeColor {
+        ident: [248, 248, 242],
+        number: [174, 129, 255],
+        string: [230, 219, 116],
+        symbol: [248, 248, 242],
+        operator: [249, 38, 114],
+        keyword: [249, 38, 114],
+        builtin_fn: [102, 217, 239],
+        fun: [166, 226, 46, 255],
+        comment: [117, 113, 94],
+        todo: [255, 215, 0],
+        error: [102, 217, 239],
+        undefined: [253, 151, 31],
+        other: [248, 248, 242],
+        style: builtin_style(),
+    }
+}
+
+/// 'name' made fit to name a theme and its file: in lowercase, with dashes instead
+/// of spaces and punctuation
+pub fn theme_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|ch| match ch {
+            ch if ch.is_alphanumeric() || ch == '-' || ch == '_' => ch.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect();
+    name.trim_matches('-').to_string()
+}
+
+/// All the known themes
+pub struct Themes {
+    /// Themes by name, the built-in ones first
+    themes: Vec<(String, CodeColor)>,
+}
+
+impl Themes {
+    /// Load the themes in the themes folder, after the built-in ones. A theme with
+    /// the name of a built-in one replaces it. Themes that cannot be read or parsed
+    /// are logged and skipped.
+    pub fn load() -> Self {
+        let mut themes = Self {
+            themes: vec![
+                ("dark".into(), dark()),
+                ("light".into(), light()),
+                ("solarized".into(), solarized()),
+                ("monokai".into(), monokai()),
+            ],
+        };
+
+        // Having no user theme is fine
+        let Ok(entries) = fs::read_dir(paths::themes_dir()) else {
+            return themes;
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let Some(name) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+            else {
+                continue;
+            };
+
+            let color = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|json| {
+                    serde_json::from_str::<CodeColor>(&jsonedit::strip_comments(&json))
+                        .map_err(|err| err.to_string())
+                });
+            match color {
+                Ok(color) => match themes.themes.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, theme)) => *theme = color,
+                    None => themes.themes.push((name, color)),
+                },
+                Err(err) => log::warning!(format!(
+                    "The theme '{}' could not be loaded: {}",
+                    path.display(),
+                    err
+                )),
+            }
+        }
+        themes
+    }
+
+    /// The names of the themes, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Save 'color' as the theme called 'name' in the themes folder, replacing the
+    /// theme with the same name
+    pub fn add(&mut self, name: &str, color: CodeColor) -> io::Result<()> {
+        let dir = paths::themes_dir();
+        fs::create_dir_all(&dir)?;
+
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+        serde::Serialize::serialize(&color, &mut serializer)?;
+        fs::write(dir.join(format!("{}.json", name)), json)?;
+
+        match self
+            .themes
+            .iter_mut()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some((_, theme)) => *theme = color,
+            None => self.themes.push((name.to_string(), color)),
+        }
+        Ok(())
+    }
+
+    /// The code colors of the theme called 'name', ignoring the case
+    pub fn get(&self, name: &str) -> Option<CodeColor> {
+        self.themes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, color)| *color)
+    }
+}
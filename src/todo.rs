@@ -0,0 +1,69 @@
+//! Tags such as `TODO` and `FIXME` written in the comments of the code. They are
+//! given an accent color in the editor and listed in the TODO panel.
+
+use std::ops::Range;
+
+use colors_highlight::Position;
+
+const TAGS: [&str; 3] = ["TODO", "FIXME", "NOTE"];
+
+/// A tag found in a comment, with the rest of its line
+#[derive(Clone, PartialEq)]
+pub struct Todo {
+    pub tag: &'static str,
+    pub text: String,
+    pub start: usize, // byte of the tag in the code
+    pub position: Position,
+}
+
+/// Return the spans of the tags in 'comment', ignoring the ones that are part of
+/// another word (e.g. `TODOS`)
+pub fn find_tags(comment: &str) -> Vec<(&'static str, Range<usize>)> {
+    let is_word_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+
+    let mut tags: Vec<(&str, Range<usize>)> = TAGS
+        .iter()
+        .flat_map(|&tag| {
+            comment.match_indices(tag).filter_map(move |(idx, _)| {
+                let (before, after) = (&comment[..idx], &comment[idx + tag.len()..]);
+                let is_word = !before.ends_with(is_word_char) && !after.starts_with(is_word_char);
+                is_word.then_some((tag, idx..idx + tag.len()))
+            })
+        })
+        .collect();
+    tags.sort_by_key(|(_, span)| span.start);
+    tags
+}
+
+/// Return the [`Todo`]s in 'comment', which starts at 'start' in the code and at
+/// 'position'. Block comments may span many lines, so positions are computed here.
+pub fn find_todos(comment: &str, start: usize, position: Position) -> Vec<Todo> {
+    find_tags(comment)
+        .into_iter()
+        .map(|(tag, span)| {
+            let before = &comment[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+            let line_end = comment[span.end..]
+                .find('\n')
+                .map_or(comment.len(), |idx| span.end + idx);
+            let col = before[line_start..].chars().count();
+
+            Todo {
+                tag,
+                text: comment[span.end..line_end]
+                    .trim_start_matches(':')
+                    .trim()
+                    .to_string(),
+                start: start + span.start,
+                position: Position {
+                    line: position.line + before.matches('\n').count(),
+                    col: if line_start == 0 {
+                        position.col + col
+                    } else {
+                        col
+                    },
+                },
+            }
+        })
+        .collect()
+}
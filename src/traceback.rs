@@ -0,0 +1,84 @@
+//! Errors found in the output of a failed run, to mark the lines they come from in the
+//! editor. The traceback gives the lines as `line 3` (in any case), or as `file:3`
+//! and `file:3:5`; the error message is the last line of the output.
+
+use std::ops::Range;
+
+/// An error reported by a run, on a line of the file
+pub struct RunError {
+    pub span: Range<usize>, // bytes of the line in the file, as it was run
+    pub message: String,
+}
+
+/// Find the lines of 'code' (the file 'file_name') that the traceback in 'output'
+/// points to. Lines of the traceback that name another file are ignored.
+pub fn parse(output: &str, file_name: &str, code: &str) -> Vec<RunError> {
+    let Some(message) = output
+        .lines()
+        .map(str::trim)
+        .rev()
+        .find(|line| !line.is_empty())
+    else {
+        return Vec::new();
+    };
+
+    let mut lines: Vec<usize> = Vec::new();
+    for line in output.lines() {
+        let Some(number) = line_number(line, file_name) else {
+            continue;
+        };
+        if !lines.contains(&number) {
+            lines.push(number);
+        }
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|number| {
+            let span = line_span(code, number)?;
+            Some(RunError {
+                span,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The number of the line of 'file_name' that 'line' of a traceback points to
+fn line_number(line: &str, file_name: &str) -> Option<usize> {
+    // `file:3` or `file:3:5`
+    if let Some(idx) = line.find(&format!("{}:", file_name)) {
+        if let Some(number) = leading_number(&line[idx + file_name.len() + 1..]) {
+            return Some(number);
+        }
+    }
+
+    // Another file is named on the line
+    let names_file = line.contains(file_name);
+    if !names_file && line.contains(".betty") {
+        return None;
+    }
+
+    let lower = line.to_lowercase();
+    let idx = lower.find("line ")?;
+    leading_number(&lower[idx + "line ".len()..])
+}
+
+/// The number at the start of 'text'
+fn leading_number(text: &str) -> Option<usize> {
+    let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// The bytes of the line 'number' (from 1) of 'code', without its newline
+fn line_span(code: &str, number: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    for (idx, line) in code.split('\n').enumerate() {
+        if idx + 1 == number {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            return Some(start..start + line.len());
+        }
+        start += line.len() + 1;
+    }
+    None
+}
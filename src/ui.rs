@@ -1,14 +1,125 @@
 ///! CodeEditor and its implementations, with some helper functions.
 ///! The CodeEditor is reponsible for rendering and handling events and keyboard inputs.
 use eframe::egui;
+use std::collections::BTreeSet;
+use std::env;
 use std::ffi;
 use std::fs;
-use std::io::{self, Write};
+use std::io;
+use std::iter;
+use std::mem;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::process;
+use std::process::{self, ExitStatus};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use super::highligher::{Highligher, Token, TokenType};
-use super::settings::{CodeColor, Settings};
+use colors_highlight::{Balance, Position, Token, TokenType};
+
+use super::ansi;
+use super::association;
+use super::cache::{self, Analysis, HighlightCache};
+use super::debugger::{Debugger, State as DebugState};
+use super::docstub;
+use super::editing::{self, Pairing};
+use super::fileio::{self, Contents, FileIo, Operation};
+use super::fonts;
+use super::format;
+use super::geometry::Geometry;
+use super::git::{self, GitWindow, LineChange};
+#[cfg(feature = "syntect")]
+use super::grammar::GrammarCache;
+use super::hexview::HexView;
+use super::history::{RunHistory, RunRecord};
+use super::html;
+use super::instance::Instance;
+use super::lint::{self, Lint};
+use super::log;
+use super::lsp::{self, Completion, LanguageServer};
+use super::macros::{self, Buffer, KeyMacro};
+use super::palette::{CommandPalette, PaletteAction};
+use super::parser::{Diagnostic, Severity};
+use super::paths;
+use super::perf::PerfOverlay;
+use super::preferences::Preferences;
+use super::recovery::{self, Recovery};
+use super::reference::{Reference, ReferencePanel};
+use super::repl::Repl;
+use super::runconfig::{self, RunConfig, RunConfigs, RunConfigsWindow};
+use super::runner::{self, Progress, Run};
+use super::search;
+use super::settings::{CodeColor, OpenAs, Settings, UiTheme, MAX_FONT_SIZE, MIN_FONT_SIZE};
+use super::share::{self, Share};
+use super::signature::{self, Signature};
+use super::spell::{Misspelling, SpellChecker};
+use super::syntax::{Highlighting, Syntaxes};
+use super::tasks::{TaskRun, Tasks};
+use super::theme::Themes;
+use super::todo;
+use super::traceback::{self, RunError};
+use super::viewer::OutputViewer;
+use super::vscode;
+use super::welcome::{RecentFiles, Welcome, WelcomeAction};
+
+/// Source of the [`egui::Id`] of the code editor, used to access its cursor
+const EDITOR_ID: &str = "code_editor";
+
+/// A command moving the program being debugged
+type DebugStep = fn(&mut Debugger) -> io::Result<()>;
+
+/// The commands moving the program being debugged, with their shortcut
+const DEBUG_STEPS: [(&str, &str, DebugStep); 4] = [
+    ("Continue", "F5", Debugger::resume),
+    ("Step over", "F10", Debugger::step_over),
+    ("Step into", "F11", Debugger::step_into),
+    ("Step out", "Shift+F11", Debugger::step_out),
+];
+
+/// Width of the marks of the lines changed since the last commit
+const GIT_MARK_WIDTH: f32 = 3.0;
+
+/// Color of the wavy lines under the misspelled words, and the width of their waves
+const SPELL_COLOR: egui::Color32 = egui::Color32::from_rgb(90, 150, 255);
+const SPELL_WAVE_WIDTH: f32 = 2.0;
+
+/// Height of the list of completions, beyond which it scrolls
+const MAX_COMPLETIONS_HEIGHT: f32 = 200.0;
+
+/// Where the betty language is documented
+const BETTY_DOCS_URL: &str = "https://github.com/lowfrank/betty";
+
+/// How often a running program is checked for its exit
+const RUN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Name of the temporary file holding the output that does not fit in the console
+const FULL_OUTPUT_FILE: &str = "colors_full_output.txt";
+
+/// Name of the temporary page printing the code, opened in the browser
+const PRINT_FILE: &str = "colors_print.html";
+
+/// The lines colored in the editor change by blocks of this many lines, so that the
+/// code is not laid out again at each step of the scrolling
+const HIGHLIGHT_BLOCK_LINES: usize = 100;
+
+/// Width of the progress bar of the file being opened or saved, in points
+const FILE_IO_BAR_WIDTH: f32 = 160.0;
+
+/// Pages of the console panel
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConsoleTab {
+    Output, // of the programs run
+    Repl,
+    Problems,    // in the code
+    Task(usize), // output of a task, by its index in the task runs
+}
+
+/// Numbers of the lines on the left of the editor, kept until the lines change
+#[derive(Default)]
+struct Gutter {
+    line_count: usize,
+    galley: Option<Arc<egui::Galley>>, // the numbers are laid out for, when wrapped
+    text: String,
+}
 
 pub struct CodeEditor {
     /// Code contents
@@ -20,24 +131,343 @@ pub struct CodeEditor {
     /// Console contents
     console: String,
 
+    /// Temporary file holding the whole output of the last run, if it was too
+    /// large for the console
+    full_output: Option<PathBuf>,
+
+    /// Window showing the whole output of the last run
+    output_viewer: Option<OutputViewer>,
+
+    /// Window showing the bytes of a file that is not text
+    hex_view: Option<HexView>,
+
+    /// Program running in the background, if any
+    run: Option<Run>,
+
+    /// Whole output of the running program, of which the console may show the end
+    run_output: String,
+
+    /// Run the file again each time it is saved?
+    watch: bool,
+
+    /// Must the file run again, because it was saved while watched?
+    rerun: bool,
+
+    /// Text searched in the console, and the match that is shown
+    console_search: String,
+    console_match: usize,
+
+    /// Must the console scroll to the match that is shown?
+    scroll_to_match: bool,
+
+    /// Errors of the last run, marked in the editor until the code is edited
+    run_errors: Vec<RunError>,
+
+    /// Page shown in the console panel
+    console_tab: ConsoleTab,
+
+    /// Interactive betty session, started when the REPL is first shown
+    repl: Option<Repl>,
+
+    /// Tasks run from the command palette, each with its page in the console
+    task_runs: Vec<TaskRun>,
+
+    /// Command palette, with the tasks of the project loaded when it was opened
+    palette: Option<(CommandPalette, Tasks)>,
+
+    /// Code being typed for the REPL
+    repl_input: String,
+
+    /// Run configurations of the project of the open file
+    run_configs: Option<RunConfigs>,
+
+    /// Name of the run configuration in use, or [`None`] to run as in the settings
+    run_config: Option<String>,
+
+    /// Window editing the run configurations
+    run_configs_window: Option<RunConfigsWindow>,
+
+    /// Where the output of the running program starts in the console, after the
+    /// output of the previous runs if the console is not cleared before each run
+    console_start: usize,
+
+    /// Window editing the settings
+    preferences: Option<Preferences>,
+
     /// Has the file been saved?
     saved: bool,
 
+    /// Did the open file start with a byte order mark, written back when it is saved?
+    /// None for the contents not read from a file, saved as the settings tell.
+    bom: Option<bool>,
+
+    /// File being opened or saved in the background
+    file_io: Option<FileIo>,
+
+    /// Program being debugged, if any
+    debugger: Option<Debugger>,
+
+    /// Lines of the breakpoints in the open file, from 0
+    breakpoints: BTreeSet<usize>,
+
+    /// Contents of the open file in the last commit, if it is in a git repository
+    git_head: Option<String>,
+
+    /// Window staging and committing the open file
+    git_window: Option<GitWindow>,
+
+    /// Lines changed since the last commit, and when they were computed
+    git_changes: Vec<(usize, LineChange)>,
+    git_diffed: Option<Instant>,
+
+    /// Language server of the open file, if its file type has one
+    language_server: Option<LanguageServer>,
+
+    /// Command of the language server that could not be started, not to try it again
+    failed_language_server: Option<String>,
+
+    /// Problems in the code reported by the language server
+    server_diagnostics: Vec<Diagnostic>,
+
+    /// Linter of the saved file running in the background, if any
+    lint: Option<Lint>,
+
+    /// Problems in the saved file reported by the linter, until the code is edited
+    lint_diagnostics: Vec<Diagnostic>,
+
+    /// Code being uploaded to the paste service, if any
+    share: Option<Share>,
+
+    /// Byte index whose hover text was asked to the language server, and the text
+    hover_asked: Option<usize>,
+    hover_text: Option<String>,
+
+    /// Byte index where the completions were asked, and the completions
+    completion_at: Option<usize>,
+    completions: Vec<Completion>,
+
+    /// Word list of the spell checker, loaded when the spelling is first checked
+    spell_checker: Option<SpellChecker>,
+
+    /// Misspelled words of the comments and strings, and when they were found
+    misspellings: Vec<Misspelling>,
+    spell_checked: Option<Instant>,
+
+    /// Bytes of the misspelled word the context menu of the editor is about
+    spell_menu: Option<Range<usize>>,
+
+    /// Keystrokes recorded in the editor, and must they be typed again at the next frame?
+    key_macro: KeyMacro,
+    play_macro: bool,
+
+    /// Page shown in place of the editor while no file is loaded
+    welcome: Option<Welcome>,
+
+    /// Files opened lately, listed in the welcome page
+    recent: RecentFiles,
+
     /// User settings
     settings: Settings,
+
+    /// Brackets and blocks left open, problems and TODOs in the code, as computed
+    /// along with the highlighting
+    analysis: Analysis,
+
+    /// Is the list of problems shown?
+    show_problems: bool,
+
+    /// Is the list of TODOs shown?
+    show_todos: bool,
+
+    /// Show the history of the runs?
+    show_history: bool,
+
+    /// Reference of the language, and the panel showing it if it is open
+    reference: Reference,
+    reference_panel: Option<ReferencePanel>,
+
+    /// Past runs, with their output
+    history: RunHistory,
+
+    /// Highlighted lines of code, to avoid analysing the whole code every frame
+    highlight_cache: HighlightCache,
+
+    /// Last code highlighted with a Sublime Text grammar
+    #[cfg(feature = "syntect")]
+    grammar_cache: GrammarCache,
+
+    /// Languages that can be highlighted
+    syntaxes: Syntaxes,
+
+    /// Presets of the code colors
+    themes: Themes,
+
+    /// Dark or light mode of the operating system, if known
+    system_theme: Option<eframe::Theme>,
+
+    /// Visuals in use, which are set on the first frame
+    visuals: Option<eframe::Theme>,
+
+    /// Pixels per point set by the system, if known
+    native_pixels_per_point: Option<f32>,
+
+    /// Scale of the interface in use, which is set on the first frame
+    ui_scale: Option<f32>,
+
+    /// Layout of the code in the last frame
+    editor_galley: Option<Arc<egui::Galley>>,
+
+    gutter: Gutter,
+
+    /// When the code was last modified, for the autosave
+    last_edit: Instant,
+
+    /// When the unsaved contents were last kept for recovery
+    last_recovery: Instant,
+
+    /// Paths of the files sent by the IDEs started later
+    instance: Option<Instance>,
+
+    /// Title of the window, set when it changes
+    window_title: String,
+
+    /// Size and position of the window in the last frame, saved when the IDE closes
+    geometry: Option<Geometry>,
+
+    /// Must the window be put back below the other windows, after it was raised to
+    /// show a file sent by another IDE?
+    raised: bool,
+
+    /// Has the first frame been drawn?
+    drawn: bool,
+
+    /// The performance overlay, if shown
+    perf: Option<PerfOverlay>,
 }
 impl CodeEditor {
-    pub fn new() -> Option<Self> {
-        let Some(settings) = Settings::get() else {
-            return None;  // Could not load settings
-        };
-        Some(Self {
+    pub fn new() -> Self {
+        let (mut settings, problems) = Settings::get();
+        log::start_session(&settings);
+        if !problems.is_empty() {
+            msgbox(
+                "Some settings could not be used",
+                problems.join("\n").as_str(),
+                rfd::MessageLevel::Warning,
+            );
+        }
+        log::milestone!("settings loaded");
+        let syntaxes = Syntaxes::load(&settings);
+        let themes = Themes::load();
+
+        if let Some(ref name) = settings.theme {
+            match themes.get(name) {
+                Some(color) => settings.code_color = color,
+                None => log::warning!(format!(
+                    "The theme '{}' does not exist. The colors in the settings are used.",
+                    name
+                )),
+            }
+        }
+        let mut editor = Self {
             contents: String::new(),
             path: None,
             console: String::new(),
+            full_output: None,
+            output_viewer: None,
+            hex_view: None,
+            run: None,
+            run_output: String::new(),
+            watch: false,
+            rerun: false,
+            console_search: String::new(),
+            console_match: 0,
+            scroll_to_match: false,
+            run_errors: Vec::new(),
+            console_tab: ConsoleTab::Output,
+            task_runs: Vec::new(),
+            palette: None,
+            repl: None,
+            repl_input: String::new(),
+            run_configs: None,
+            run_config: None,
+            run_configs_window: None,
+            console_start: 0,
+            preferences: None,
             saved: false,
+            bom: None,
+            file_io: None,
+            debugger: None,
+            breakpoints: BTreeSet::new(),
+            git_head: None,
+            git_window: None,
+            git_changes: Vec::new(),
+            git_diffed: None,
+            language_server: None,
+            failed_language_server: None,
+            server_diagnostics: Vec::new(),
+            lint: None,
+            lint_diagnostics: Vec::new(),
+            share: None,
+            hover_asked: None,
+            hover_text: None,
+            completion_at: None,
+            completions: Vec::new(),
+            spell_checker: None,
+            misspellings: Vec::new(),
+            spell_checked: None,
+            spell_menu: None,
+            key_macro: KeyMacro::default(),
+            play_macro: false,
+            welcome: Some(Welcome::default()),
+            recent: RecentFiles::load(),
             settings,
-        })
+            analysis: Analysis::default(),
+            show_problems: false,
+            show_todos: false,
+            show_history: false,
+            reference: Reference::bundled(),
+            reference_panel: None,
+            history: RunHistory::default(),
+            highlight_cache: HighlightCache::default(),
+            #[cfg(feature = "syntect")]
+            grammar_cache: GrammarCache::default(),
+            syntaxes,
+            themes,
+            system_theme: None,
+            visuals: None,
+            native_pixels_per_point: None,
+            ui_scale: None,
+            editor_galley: None,
+            gutter: Gutter::default(),
+            last_edit: Instant::now(),
+            last_recovery: Instant::now(),
+            instance: None,
+            window_title: String::new(),
+            geometry: None,
+            raised: false,
+            drawn: false,
+            perf: None,
+        };
+
+        // The other windows may still be running, so their contents are not offered
+        if !paths::is_new_window() {
+            if let Some((file, recovery)) = Recovery::load_orphan() {
+                editor.offer_recovery(file, recovery);
+            }
+        }
+
+        // The editor holds a single file, so the others are not opened
+        let mut files = paths::file_args().into_iter();
+        if let Some(path) = files.next() {
+            editor.open_path(path);
+        }
+        for path in files {
+            log::warning!(format!(
+                "'{}' is not opened, as only one file can be open",
+                path.display()
+            ));
+        }
+        editor
     }
 }
 
@@ -49,13 +479,168 @@ impl eframe::App for CodeEditor {
         if self.settings.save_on_close && !self.contents.is_empty() {
             self.save_file();
         }
+        self.wait_file_io();
+        // The unsaved contents are discarded on purpose
+        self.clear_recovery();
+        if let Some(geometry) = self.geometry {
+            if let Err(err) = geometry.save() {
+                log::warning!(format!(
+                    "The size of the window could not be saved: {}",
+                    err
+                ));
+            }
+        }
         true // A return value of 'true' means we accept the event
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if let Some(ref mut perf) = self.perf {
+            perf.start_frame(frame.info().cpu_usage);
+        }
+        self.open_sent_files(ctx, frame);
+        self.update_window_title(frame);
+        self.geometry = Some(Geometry::of(&frame.info().window_info));
+        self.system_theme = frame.info().system_theme;
+        self.native_pixels_per_point = frame.info().native_pixels_per_point;
+        self.update_visuals(ctx);
+        self.update_scale(ctx);
+        self.autosave(ctx);
+        self.keep_for_recovery(ctx);
+        self.poll_run(ctx);
+        self.poll_debugger();
+        self.poll_lint(ctx);
+        self.poll_share(ctx);
+        self.poll_file_io(ctx);
+        self.poll_tasks(ctx);
+        self.rerun_watched(ctx);
+        #[cfg(feature = "syntect")]
+        if self.syntaxes.poll_grammars() {
+            ctx.request_repaint_after(RUN_POLL_INTERVAL);
+        }
+        self.sync_language_server(ctx);
+        self.update_git_changes();
+        self.update_misspellings();
+
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            self.draw_menu_bar(ui, frame);
+        });
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            self.draw_status_bar(ui);
+        });
+
+        if self.show_todos {
+            egui::TopBottomPanel::bottom("todos")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    self.draw_todos(ui);
+                });
+        }
+
+        if self.show_history {
+            egui::TopBottomPanel::bottom("history")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    self.history.show(ui, self.settings.console_font_size);
+                });
+        }
+
+        if self.show_problems {
+            egui::TopBottomPanel::bottom("problems")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    self.draw_problems(ui, "problems");
+                });
+        }
+
+        if let Some((ref mut palette, ref tasks)) = self.palette {
+            let commands: Vec<String> = tasks
+                .tasks
+                .iter()
+                .map(|task| format!("Run task: {}", task.label))
+                .collect();
+            match palette.show(ctx, &commands) {
+                PaletteAction::None => (),
+                PaletteAction::Chosen(idx) => {
+                    let task = &tasks.tasks[idx];
+                    let label = task.label.clone();
+                    let command = tasks.command(task, &self.settings.env);
+                    self.palette = None;
+                    self.start_task(ctx, label, command);
+                }
+                PaletteAction::Closed => self.palette = None,
+            }
+        }
+
+        if let Some(ref mut panel) = self.reference_panel {
+            egui::SidePanel::right("reference")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    panel.show(ui, &self.reference, self.settings.console_font_size);
+                });
+        }
+
+        if let Some(ref debugger) = self.debugger {
+            egui::SidePanel::right("variables")
+                .resizable(true)
+                .show(ctx, |ui| {
+                    draw_variables(ui, debugger, self.settings.console_font_size);
+                });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.handle_ctrl_s(ui.input().events.iter());
-            self.handle_ctrl_r(ui.input().events.iter());
+            self.handle_ctrl_r(ui.ctx(), ui.input().events.iter());
+            if is_ctrl_shift_c(ui.input().events.iter()) {
+                self.stop_run();
+            }
+            if is_ctrl_l(ui.input().events.iter()) {
+                self.clear_console();
+            }
+            if is_ctrl_p(ui.input().events.iter()) {
+                self.print_file(ui.ctx(), ui.visuals().extreme_bg_color);
+            }
+            if is_ctrl_shift_p(ui.input().events.iter()) {
+                self.open_palette();
+            }
+            if take_ctrl_enter(&mut ui.input_mut().events) {
+                self.send_selection_to_repl(ui.ctx());
+            }
+            self.handle_zoom(ui.input().events.iter());
+            if is_ctrl_shift_d(ui.input().events.iter()) {
+                self.insert_doc_stub(ui.ctx());
+            }
+            if is_ctrl_shift_f12(ui.input().events.iter()) {
+                self.perf = match self.perf {
+                    Some(_) => None,
+                    None => Some(PerfOverlay::new()),
+                };
+            }
+            if is_ctrl_shift_f(ui.input().events.iter()) {
+                self.format_document(ui.ctx());
+            }
+            if is_ctrl_shift_m(ui.input().events.iter()) {
+                self.key_macro.toggle_recording();
+            } else if is_ctrl_m(ui.input().events.iter()) {
+                self.play_key_macro(ui.ctx());
+            }
+            let script = self
+                .settings
+                .script_shortcuts
+                .iter()
+                .find(|(shortcut, _)| macros::is_pressed(shortcut, ui.input().events.iter()))
+                .map(|(_, name)| paths::scripts_dir().join(name));
+            if let Some(script) = script {
+                self.run_script(ui.ctx(), &script);
+            }
+            self.handle_debug_keys(ui.ctx());
+            if is_ctrl_space(ui.input().events.iter()) {
+                self.ask_completions(ui.ctx());
+            }
+            if ui.input().key_pressed(egui::Key::Escape) {
+                self.completions.clear();
+                self.completion_at = None;
+            }
 
             self.draw_top_section(ui);
 
@@ -64,275 +649,3294 @@ impl eframe::App for CodeEditor {
             // Remove highlight of widget when hovered
             ui.visuals_mut().widgets.hovered = ui.visuals_mut().widgets.inactive;
 
-            self.draw_code_editor(ui);
+            if let Some(ref mut welcome) = self.welcome {
+                match welcome.show(ui, &self.recent) {
+                    Some(WelcomeAction::NewFile) => self.welcome = None,
+                    Some(WelcomeAction::OpenFile) => self.open_file(),
+                    Some(WelcomeAction::Open(path)) => self.open_path(path),
+                    None => {}
+                }
+            } else {
+                self.draw_code_editor(ui);
+            }
 
             ui.separator();
 
             self.draw_console(ui);
         });
+
+        if let Some(ref mut viewer) = self.output_viewer {
+            viewer.show(ctx, self.settings.console_font_size);
+            if !viewer.is_open() {
+                self.output_viewer = None;
+            }
+        }
+
+        if let Some(ref mut view) = self.hex_view {
+            view.show(ctx, self.settings.code_font_size);
+            if !view.is_open() {
+                self.hex_view = None;
+            }
+        }
+
+        if let Some(ref mut preferences) = self.preferences {
+            let output = preferences.show(ctx, &mut self.settings);
+            if !preferences.is_open() {
+                self.preferences = None;
+            }
+
+            if output.changed {
+                self.clear_highlight_cache();
+                log::configure(&self.settings.log);
+            }
+            if output.save {
+                self.save_settings();
+            }
+            if let Some(name) = output.save_theme {
+                self.save_theme(name, self.settings.code_color);
+            }
+            if let Some(associate) = output.associate {
+                associate_files(associate);
+            }
+            if let Some(token) = output.save_token {
+                if let Err(err) = share::save_token(&token) {
+                    msgbox(
+                        "Error in saving the token",
+                        err.to_string().as_str(),
+                        rfd::MessageLevel::Error,
+                    );
+                }
+            }
+        }
+
+        if let (Some(window), Some(path)) = (&mut self.git_window, &self.path) {
+            if window.show(ctx, path, self.saved) {
+                self.git_head = git::head_contents(path);
+                self.git_diffed = None;
+            }
+            if !window.is_open() {
+                self.git_window = None;
+            }
+        }
+
+        if let (Some(window), Some(configs)) = (&mut self.run_configs_window, &mut self.run_configs)
+        {
+            if window.show(ctx, configs) {
+                if let Err(err) = configs.save() {
+                    msgbox(
+                        "Error in saving the run configurations",
+                        err.to_string().as_str(),
+                        rfd::MessageLevel::Error,
+                    );
+                }
+            }
+            if !window.is_open() {
+                self.run_configs_window = None;
+            }
+        }
+
+        if let Some(ref perf) = self.perf {
+            perf.show(ctx, self.contents.len(), self.contents.lines().count());
+        }
+
+        if !self.drawn {
+            self.drawn = true;
+            log::milestone!("first frame drawn");
+        }
     }
 }
 
 impl CodeEditor {
-    fn draw_top_section(&mut self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
-                // Title label
-                ui.label(
-                    egui::RichText::new(self.set_title())
-                        .size(17.0)
-                        .monospace()
-                        .strong()
-                        .color(egui::Color32::WHITE),
-                );
+    /// Open the files sent by the IDEs started later, repainting 'ctx' when they arrive
+    pub fn listen(&mut self, ctx: egui::Context) {
+        match Instance::listen(ctx) {
+            Ok(instance) => self.instance = Some(instance),
+            Err(err) => log::warning!(format!(
+                "The files opened later will open in another window: {}",
+                err
+            )),
+        }
+    }
+
+    /// Open the file sent by another IDE, if any, and bring the window to the front.
+    /// There is no way to focus the window, so it is raised above the others for a
+    /// frame.
+    fn open_sent_files(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if mem::take(&mut self.raised) {
+            frame.set_always_on_top(false);
+        }
+        let Some(ref instance) = self.instance else {
+            return;
+        };
+        // The editor holds a single file, so the last one is kept
+        let Some(path) = iter::from_fn(|| instance.poll()).last() else {
+            return;
+        };
+        self.open_path(path);
+        frame.set_always_on_top(true);
+        self.raised = true;
+        ctx.request_repaint();
+    }
+
+    /// Draw the menus with all the commands of the IDE, along with their shortcuts
+    fn draw_menu_bar(&mut self, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if menu_item(ui, "New window", "") {
+                    self.new_window();
+                }
+                ui.separator();
+                if menu_item(ui, "Open...", "") {
+                    self.open_file();
+                }
+                if menu_item(ui, "Save", "Ctrl+S") {
+                    self.save_file();
+                }
+                if menu_item(ui, "Export as HTML...", "") {
+                    self.export_html(ui.visuals().extreme_bg_color);
+                }
+                if menu_item(ui, "Print...", "Ctrl+P") {
+                    self.print_file(ui.ctx(), ui.visuals().extreme_bg_color);
+                }
+                if ui
+                    .add_enabled(self.share.is_none(), egui::Button::new("Share..."))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.share_code(ui.ctx());
+                }
+                ui.separator();
+                if menu_item(ui, "Settings...", "") {
+                    self.preferences = Some(Preferences::new());
+                }
+                ui.separator();
+                if menu_item(ui, "Quit", "") {
+                    frame.close();
+                }
             });
-            ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-                // Run button
+
+            ui.menu_button("Edit", |ui| {
+                if menu_item(ui, "Insert documentation stub", "Ctrl+Shift+D") {
+                    self.insert_doc_stub(ui.ctx());
+                }
+                if menu_item(ui, "Send to the REPL", "Ctrl+Enter") {
+                    self.send_selection_to_repl(ui.ctx());
+                }
+                if menu_item(ui, "Format document", "Ctrl+Shift+F") {
+                    self.format_document(ui.ctx());
+                }
+                ui.separator();
+                let record = if self.key_macro.is_recording() {
+                    "Stop recording the macro"
+                } else {
+                    "Record a macro"
+                };
+                if menu_item(ui, record, "Ctrl+Shift+M") {
+                    self.key_macro.toggle_recording();
+                }
                 if ui
-                    .button(
-                        egui::RichText::new("Run")
-                            .size(15.0)
-                            .monospace()
-                            .color(egui::Color32::WHITE),
+                    .add_enabled(
+                        self.key_macro.can_play(),
+                        egui::Button::new("Play the macro").shortcut_text("Ctrl+M"),
                     )
                     .clicked()
                 {
-                    self.run_file()
+                    ui.close_menu();
+                    self.play_key_macro(ui.ctx());
                 }
+                ui.menu_button("Scripts", |ui| {
+                    let scripts = macros::list_scripts();
+                    if scripts.is_empty() {
+                        ui.label(format!("No scripts in {}", paths::scripts_dir().display()));
+                    }
+                    for script in scripts {
+                        let name = macros::script_name(&script);
+                        let shortcut = self
+                            .settings
+                            .script_shortcuts
+                            .iter()
+                            .find(|(_, script)| **script == name)
+                            .map_or("", |(shortcut, _)| shortcut.as_str());
+                        if menu_item(ui, &name, shortcut) {
+                            self.run_script(ui.ctx(), &script);
+                        }
+                    }
+                });
+            });
 
-                // Save button
-                if self.settings.save_btn {
-                    if ui
-                        .button(
-                            egui::RichText::new("Save")
-                                .size(15.0)
-                                .monospace()
-                                .color(egui::Color32::WHITE),
-                        )
-                        .clicked()
-                    {
-                        self.save_file()
+            ui.menu_button("View", |ui| {
+                ui.label("Interface");
+                for (ui_theme, name) in [
+                    (UiTheme::Dark, "dark"),
+                    (UiTheme::Light, "light"),
+                    (UiTheme::System, "system"),
+                ] {
+                    if ui.radio(self.settings.ui_theme == ui_theme, name).clicked() {
+                        self.settings.ui_theme = ui_theme;
+                        self.update_visuals(ui.ctx());
+                        self.save_settings();
+                        ui.close_menu();
                     }
                 }
 
-                // Open button
+                ui.separator();
+                ui.label("Theme");
+                let mut selected = None;
+                for name in self.themes.names() {
+                    let current = self.settings.theme.as_deref() == Some(name);
+                    if ui.radio(current, name).clicked() {
+                        selected = Some(name.to_string());
+                    }
+                }
+                if let Some(name) = selected {
+                    self.set_theme(name);
+                    self.save_settings();
+                    ui.close_menu();
+                }
+                if menu_item(ui, "Import VS Code theme...", "") {
+                    self.import_theme();
+                }
+
+                ui.separator();
+                if menu_item(ui, "Zoom in", "Ctrl+=") {
+                    self.set_code_font_size(self.settings.code_font_size + 1.0);
+                }
+                if menu_item(ui, "Zoom out", "Ctrl+-") {
+                    self.set_code_font_size(self.settings.code_font_size - 1.0);
+                }
+                if menu_item(ui, "Reset zoom", "Ctrl+0") {
+                    self.set_code_font_size(Settings::default().code_font_size);
+                }
+
+                ui.separator();
                 if ui
-                    .button(
-                        egui::RichText::new("Open")
-                            .size(15.0)
-                            .monospace()
-                            .color(egui::Color32::WHITE),
+                    .add_enabled(
+                        self.git_head.is_some(),
+                        egui::Button::new("Source control..."),
                     )
                     .clicked()
                 {
-                    self.open_file()
+                    ui.close_menu();
+                    self.git_window = self.path.as_deref().map(GitWindow::new);
                 }
+                ui.checkbox(&mut self.show_problems, "Problems");
+                ui.checkbox(&mut self.show_todos, "TODOs");
+                ui.checkbox(&mut self.show_history, "Run history");
             });
-        });
-    }
 
-    /// Leave 15% space for console
-    fn draw_code_editor(&mut self, ui: &mut egui::Ui) {
-        egui::Resize::default()
-            .fixed_size((ui.available_width(), ui.available_height() * 0.85))
-            .show(ui, |ui| {
-                egui::ScrollArea::both()
-                    .id_source("vscroll1")
-                    .show(ui, |ui| {
-                        // Remove highlight of widget when ckicked (0.0) but leave the text cursor as white
-                        ui.visuals_mut().selection.stroke =
-                            egui::Stroke::new(0.0, egui::Color32::WHITE);
-                        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
-                            // Add code lines
-                            ui.add_sized(
-                                (ui.available_width() * 0.03, ui.available_height()),
-                                egui::Label::new(
-                                    egui::RichText::new(self.lines())
-                                        .color(egui::Color32::WHITE)
-                                        .font(egui::FontId::new(
-                                            self.settings.code_font_size,
-                                            egui::FontFamily::Monospace,
-                                        )),
-                                ),
-                            );
-                            let mut layouter =
-                                &mut |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-                                    let layout_job = highlight_text(
-                                        string,
-                                        self.settings.code_color,
-                                        self.settings.code_font_size,
-                                    );
+            ui.menu_button("Run", |ui| {
+                let running = self.run.is_some();
+                if ui
+                    .add_enabled(!running, egui::Button::new("Run").shortcut_text("Ctrl+R"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.run_file(ui.ctx());
+                }
+                if ui
+                    .add_enabled(
+                        running,
+                        egui::Button::new("Stop").shortcut_text("Ctrl+Shift+C"),
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.stop_run();
+                }
+                ui.checkbox(&mut self.watch, "Run again on save");
+                ui.separator();
+                if ui
+                    .add_enabled(
+                        self.path.is_some(),
+                        egui::Button::new("Edit configurations..."),
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.run_configs_window = Some(RunConfigsWindow::new());
+                }
+                if menu_item(ui, "Clear the console", "Ctrl+L") {
+                    self.clear_console();
+                }
+                if menu_item(ui, "Run a task...", "Ctrl+Shift+P") {
+                    ui.close_menu();
+                    self.open_palette();
+                }
+
+                ui.separator();
+                let debugging = self.debugger.is_some();
+                let stopped = self
+                    .debugger
+                    .as_ref()
+                    .map_or(false, |debugger| debugger.state() == DebugState::Stopped);
+                if ui
+                    .add_enabled(!debugging, egui::Button::new("Debug").shortcut_text("F5"))
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.start_debugging(ui.ctx());
+                }
+                for (text, shortcut, step) in DEBUG_STEPS {
+                    if ui
+                        .add_enabled(stopped, egui::Button::new(text).shortcut_text(shortcut))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.debug_step(step);
+                    }
+                }
+                if ui
+                    .add_enabled(
+                        debugging,
+                        egui::Button::new("Stop debugging").shortcut_text("Shift+F5"),
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.debugger = None;
+                }
+            });
+
+            ui.menu_button("Help", |ui| {
+                if menu_item(ui, "Keybindings", "") {
+                    self.preferences = Some(Preferences::keybindings());
+                }
+                let mut show_reference = self.reference_panel.is_some();
+                if ui
+                    .checkbox(&mut show_reference, "Language reference")
+                    .changed()
+                {
+                    self.reference_panel = show_reference.then(ReferencePanel::default);
+                }
+                ui.hyperlink_to("betty documentation", BETTY_DOCS_URL);
+                if menu_item(ui, "About Colors", "") {
+                    msgbox(
+                        "About Colors",
+                        &format!(
+                            "Colors {}, an IDE for the betty programming language",
+                            env!("CARGO_PKG_VERSION")
+                        ),
+                        rfd::MessageLevel::Info,
+                    );
+                }
+            });
+        });
+    }
+
+    fn draw_top_section(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                // Title label
+                ui.label(
+                    egui::RichText::new(self.set_title())
+                        .size(17.0)
+                        .monospace()
+                        .strong()
+                        .color(ui.visuals().strong_text_color()),
+                );
+            });
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+                // Run button, which stops the program while it runs
+                let running = self.run.is_some();
+                if ui
+                    .button(
+                        egui::RichText::new(if running { "Stop" } else { "Run" })
+                            .size(15.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    if running {
+                        self.stop_run()
+                    } else {
+                        self.run_file(ui.ctx())
+                    }
+                }
+
+                // Watch mode, running the file again on each save
+                ui.toggle_value(
+                    &mut self.watch,
+                    egui::RichText::new("Watch")
+                        .size(15.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .on_hover_text("Run the file again each time it is saved");
+
+                self.draw_debug_controls(ui);
+
+                self.draw_run_config(ui);
+
+                // Arguments of the program, remembered for each file, unless they
+                // come from the run configuration
+                if let (Some(path), None) = (self.path.clone(), self.selected_run_config()) {
+                    let mut args = self.settings.run_args(&path).to_string();
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut args)
+                            .hint_text("Arguments")
+                            .desired_width(150.0),
+                    );
+                    if response.changed() {
+                        self.settings.set_run_args(&path, args);
+                    }
+                    if response.lost_focus() {
+                        self.save_settings();
+                    }
+                }
+
+                // Save button
+                if self.settings.save_btn {
+                    if ui
+                        .button(
+                            egui::RichText::new("Save")
+                                .size(15.0)
+                                .monospace()
+                                .color(ui.visuals().strong_text_color()),
+                        )
+                        .clicked()
+                    {
+                        self.save_file()
+                    }
+                }
+            });
+        });
+    }
+
+    /// Leave 15% space for console
+    fn draw_code_editor(&mut self, ui: &mut egui::Ui) {
+        let editor = self.settings.editor;
+        let scroll_area = if editor.word_wrap {
+            egui::ScrollArea::vertical()
+        } else {
+            egui::ScrollArea::both()
+        };
+
+        egui::Resize::default()
+            .fixed_size((ui.available_width(), ui.available_height() * 0.85))
+            .show(ui, |ui| {
+                scroll_area
+                    .id_source("vscroll1")
+                    .show_viewport(ui, |ui, viewport| {
+                        // Remove highlight of widget when ckicked (0.0) but leave the text cursor visible
+                        let cursor_color = ui.visuals().strong_text_color();
+                        ui.visuals_mut().selection.stroke = egui::Stroke::new(0.0, cursor_color);
+                        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                            // Add code lines, where clicks toggle the breakpoints
+                            let gutter = ui.add_sized(
+                                (ui.available_width() * 0.03, ui.available_height()),
+                                egui::Label::new(
+                                    egui::RichText::new(self.lines())
+                                        .color(ui.visuals().strong_text_color())
+                                        .font(egui::FontId::new(
+                                            self.settings.code_font_size,
+                                            egui::FontFamily::Monospace,
+                                        )),
+                                )
+                                .sense(egui::Sense::click()),
+                            );
+                            let id = egui::Id::new(EDITOR_ID);
+                            let pairing = self.handle_editor_input(ui, id);
+
+                            let highlighting = self
+                                .syntaxes
+                                .highlighting_for(self.path.as_deref(), &self.settings);
+                            let shown = shown_lines(self.editor_galley.as_deref(), viewport);
+                            let mut analysis = None;
+                            let mut highlight_time = None;
+                            let mut layouter =
+                                &mut |ui: &egui::Ui, string: &str, wrap_width: f32| {
+                                    let highlight_start = Instant::now();
+                                    let (mut layout_job, new_analysis) = match highlighting {
+                                        Highlighting::Tokens(config) => {
+                                            let layout = self.highlight_cache.layout_in_background(
+                                                string,
+                                                config,
+                                                shown.clone(),
+                                                |line, tokens| {
+                                                    highlight_tokens(
+                                                        line,
+                                                        tokens,
+                                                        self.settings.code_color,
+                                                        self.settings.code_font_size,
+                                                    )
+                                                },
+                                            );
+                                            // Show the colors of the worker once it is done
+                                            if self.highlight_cache.is_pending() {
+                                                ui.ctx().request_repaint_after(RUN_POLL_INTERVAL);
+                                            }
+                                            layout
+                                        }
+                                        #[cfg(feature = "syntect")]
+                                        Highlighting::Grammar(grammars, syntax) => (
+                                            self.grammar_cache.layout(
+                                                string,
+                                                grammars,
+                                                syntax,
+                                                |text, tokens| {
+                                                    highlight_tokens(
+                                                        text,
+                                                        tokens,
+                                                        self.settings.code_color,
+                                                        self.settings.code_font_size,
+                                                    )
+                                                },
+                                            ),
+                                            Analysis::default(),
+                                        ),
+                                        // The whole text is a single token
+                                        Highlighting::Plain => (
+                                            highlight_tokens(
+                                                string,
+                                                vec![Token(
+                                                    TokenType::Other,
+                                                    0..string.len(),
+                                                    Position::default(),
+                                                )],
+                                                self.settings.code_color,
+                                                self.settings.code_font_size,
+                                            ),
+                                            Analysis::default(),
+                                        ),
+                                    };
+
+                                    // Underline the code of each error and dim the
+                                    // code of each warning
+                                    for section in &mut layout_job.sections {
+                                        let range = &section.byte_range;
+                                        let severity = new_analysis
+                                            .diagnostics
+                                            .iter()
+                                            .chain(&self.server_diagnostics)
+                                            .chain(&self.lint_diagnostics)
+                                            .find(|diagnostic| {
+                                                diagnostic.span.start < range.end
+                                                    && range.start < diagnostic.span.end
+                                            })
+                                            .map(|diagnostic| diagnostic.severity);
+                                        match severity {
+                                            Some(Severity::Error) => {
+                                                section.format.underline =
+                                                    egui::Stroke::new(1.5, egui::Color32::RED)
+                                            }
+                                            Some(Severity::Warning) => {
+                                                section.format.color =
+                                                    section.format.color.linear_multiply(0.45)
+                                            }
+                                            None => (),
+                                        }
+
+                                        // The lines where the last run failed
+                                        if self.run_errors.iter().any(|error| {
+                                            error.span.start < range.end
+                                                && range.start < error.span.end
+                                        }) {
+                                            section.format.underline =
+                                                egui::Stroke::new(1.5, egui::Color32::RED)
+                                        }
+                                    }
+                                    layout_job.wrap.max_width = if editor.word_wrap {
+                                        wrap_width
+                                    } else {
+                                        f32::INFINITY
+                                    };
+                                    analysis = Some(new_analysis);
+                                    highlight_time = Some(highlight_start.elapsed());
                                     ui.fonts().layout_job(layout_job)
                                 };
 
-                            // Add code editor
+                            // Add code editor, keeping its galley to find the text
+                            // under the mouse
+                            let mut galley = None;
                             let response = ui.add_sized(
                                 (ui.available_width(), ui.available_height()),
-                                egui::widgets::TextEdit::multiline(&mut self.contents)
-                                    .code_editor()
-                                    .layouter(&mut layouter)
-                                    .font(egui::TextStyle::Monospace),
+                                |ui: &mut egui::Ui| {
+                                    let mut output =
+                                        egui::widgets::TextEdit::multiline(&mut self.contents)
+                                            .id(id)
+                                            .code_editor()
+                                            .layouter(&mut layouter)
+                                            .font(egui::TextStyle::Monospace)
+                                            .show(ui);
+
+                                    if let Some(Pairing::Close(ccursor)) = pairing {
+                                        output.state.set_ccursor_range(Some(
+                                            egui::text::CCursorRange::one(ccursor),
+                                        ));
+                                        egui::TextEdit::store_state(ui.ctx(), id, output.state);
+                                    }
+
+                                    // Drawn over the text, so they must be faint
+                                    let painter = ui.painter_at(output.text_clip_rect);
+                                    let faint = ui.visuals().text_color().linear_multiply(0.06);
+                                    if let (true, Some(cursor)) =
+                                        (editor.highlight_current_line, output.cursor_range)
+                                    {
+                                        let rect = editing::current_line_rect(
+                                            &output.galley,
+                                            output.text_draw_pos,
+                                            &cursor,
+                                            output.text_clip_rect.width(),
+                                        );
+                                        painter.rect_filled(rect, 0.0, faint);
+                                    }
+                                    if editor.show_whitespace {
+                                        editing::paint_whitespace(
+                                            &painter,
+                                            &output.galley,
+                                            output.text_draw_pos,
+                                            ui.visuals().text_color().linear_multiply(0.4),
+                                        );
+                                    }
+
+                                    galley = Some((output.galley, output.text_draw_pos));
+                                    output.response
+                                },
                             );
                             if response.changed() {
                                 // The source has been modified
                                 self.saved = false;
+                                self.last_edit = Instant::now();
+                                self.run_errors.clear();
+                                self.lint_diagnostics.clear();
+                                self.completions.clear();
+                                self.completion_at = None;
+                            }
+                            self.editor_galley = galley.as_ref().map(|(galley, _)| galley.clone());
+
+                            if let (Some(perf), Some(time)) = (&mut self.perf, highlight_time) {
+                                perf.record_highlight(time);
+                            }
+
+                            // The status bar has already been drawn in this frame
+                            if let Some(analysis) = analysis {
+                                if analysis != self.analysis {
+                                    self.analysis = analysis;
+                                    ui.ctx().request_repaint();
+                                }
+                            }
+
+                            let Some((galley, text_pos)) = galley else {
+                                return;
+                            };
+                            self.draw_breakpoints(ui, &gutter, &galley, text_pos);
+                            self.draw_git_changes(ui, &gutter, &galley, text_pos);
+                            self.draw_misspellings(ui, &galley, text_pos);
+                            if response.secondary_clicked() {
+                                self.spell_menu =
+                                    response.interact_pointer_pos().and_then(|pointer| {
+                                        let cursor = galley.cursor_from_pos(pointer - text_pos);
+                                        let index = char_to_byte_index(
+                                            &self.contents,
+                                            cursor.ccursor.index,
+                                        );
+                                        self.misspellings
+                                            .iter()
+                                            .find(|misspelling| misspelling.span.contains(&index))
+                                            .map(|misspelling| misspelling.span.clone())
+                                    });
+                            }
+                            response.clone().context_menu(|ui| self.draw_spell_menu(ui));
+                            // Explain the problem under the mouse, or else the code
+                            if let Some(pointer) = response.hover_pos() {
+                                let cursor = galley.cursor_from_pos(pointer - text_pos);
+                                let index =
+                                    char_to_byte_index(&self.contents, cursor.ccursor.index);
+                                if let Some(diagnostic) = self
+                                    .analysis
+                                    .diagnostics
+                                    .iter()
+                                    .chain(&self.server_diagnostics)
+                                    .chain(&self.lint_diagnostics)
+                                    .find(|diagnostic| diagnostic.span.contains(&index))
+                                {
+                                    response.clone().on_hover_text(&diagnostic.message);
+                                } else if let Some(error) = self
+                                    .run_errors
+                                    .iter()
+                                    .find(|error| error.span.contains(&index))
+                                {
+                                    response.clone().on_hover_text(&error.message);
+                                } else if let Some(builtin) = self
+                                    .analysis
+                                    .builtins
+                                    .iter()
+                                    .find(|span| span.contains(&index))
+                                    .and_then(|span| self.contents.get(span.clone()))
+                                    .and_then(|name| self.reference.builtin(name))
+                                {
+                                    response.clone().on_hover_ui(|ui| {
+                                        ui.monospace(
+                                            builtin.signature.as_deref().unwrap_or(&builtin.name),
+                                        );
+                                        ui.label(&builtin.description);
+                                    });
+                                } else {
+                                    self.hover_code(&response, index);
+                                }
+                            }
+                            // The index may be stale if the code changed elsewhere
+                            let completion_at = self.completion_at.and_then(|index| {
+                                byte_to_char_index(&self.contents, index)
+                                    .map(|char_index| (index, char_index))
+                            });
+                            if self.completion_at.is_some() && completion_at.is_none() {
+                                self.completions.clear();
+                                self.completion_at = None;
+                            }
+                            if let Some((index, char_index)) = completion_at {
+                                let cursor =
+                                    galley.from_ccursor(egui::text::CCursor::new(char_index));
+                                let rect = galley
+                                    .pos_from_cursor(&cursor)
+                                    .translate(text_pos.to_vec2());
+                                self.draw_completions(ui.ctx(), rect.left_bottom(), index);
+                            } else if response.has_focus() {
+                                self.draw_signature_help(ui.ctx(), &galley, text_pos);
                             }
                         });
                     })
             });
     }
 
-    fn draw_console(&mut self, ui: &mut egui::Ui) {
-        egui::ScrollArea::both()
-            .id_source("vscroll2")
-            .show(ui, |ui| {
-                // Remove white border from console
-                ui.visuals_mut().widgets.noninteractive.bg_stroke = egui::Stroke::NONE;
-                ui.add_sized(
-                    ui.available_size(),
-                    egui::TextEdit::multiline(&mut self.console)
-                        .code_editor()
-                        .font(egui::FontId::new(
-                            self.settings.console_font_size,
-                            egui::FontFamily::Monospace,
-                        ))
-                        .interactive(false),
-                );
-            });
+    /// Show the brackets and blocks left open, in red if any of them is unbalanced
+    fn draw_status_bar(&mut self, ui: &mut egui::Ui) {
+        let Balance {
+            parens,
+            brackets,
+            blocks,
+        } = self.analysis.balance;
+        let color = if self.analysis.balance.is_balanced() {
+            ui.visuals().strong_text_color()
+        } else {
+            egui::Color32::RED
+        };
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if let Some(ref file_io) = self.file_io {
+                let verb = match file_io.operation() {
+                    Operation::Open => "Opening",
+                    Operation::Save => "Saving",
+                };
+                ui.add(
+                    egui::ProgressBar::new(file_io.progress())
+                        .desired_width(FILE_IO_BAR_WIDTH)
+                        .text(format!("{} {}", verb, path_name_as_string(file_io.path()))),
+                );
+            }
+            ui.label(
+                egui::RichText::new(format!(
+                    "( ) {}   [ ] {}   do/end {}",
+                    parens, brackets, blocks
+                ))
+                .size(13.0)
+                .monospace()
+                .color(color),
+            );
+
+            // Toggle the list of problems
+            let count = self.analysis.diagnostics.len()
+                + self.server_diagnostics.len()
+                + self.lint_diagnostics.len();
+            let problems = match count {
+                1 => "1 problem".to_string(),
+                count => format!("{} problems", count),
+            };
+            if ui
+                .selectable_label(
+                    self.show_problems,
+                    egui::RichText::new(problems)
+                        .size(13.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .clicked()
+            {
+                self.show_problems = !self.show_problems;
+            }
+
+            // Toggle the list of TODOs
+            let todos = match self.analysis.todos.len() {
+                1 => "1 TODO".to_string(),
+                count => format!("{} TODOs", count),
+            };
+            if ui
+                .selectable_label(
+                    self.show_todos,
+                    egui::RichText::new(todos)
+                        .size(13.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .clicked()
+            {
+                self.show_todos = !self.show_todos;
+            }
+
+            // Toggle the history of the runs
+            let runs = match self.history.len() {
+                1 => "1 run".to_string(),
+                count => format!("{} runs", count),
+            };
+            if ui
+                .selectable_label(
+                    self.show_history,
+                    egui::RichText::new(runs)
+                        .size(13.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .clicked()
+            {
+                self.show_history = !self.show_history;
+            }
+        });
+    }
+
+    /// List the tags found in the comments, with the file and the line they are in.
+    /// Clicking on one of them moves the cursor there.
+    fn draw_todos(&mut self, ui: &mut egui::Ui) {
+        let file = self
+            .path
+            .as_deref()
+            .map_or_else(|| "untitled".to_string(), path_name_as_string);
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_source("todos")
+            .auto_shrink([false, true])
+            .max_height(ui.available_height())
+            .show(ui, |ui| {
+                if self.analysis.todos.is_empty() {
+                    ui.label(egui::RichText::new("No TODOs").monospace());
+                }
+                for todo in &self.analysis.todos {
+                    let text = format!(
+                        "{}:{}  {} {}",
+                        file,
+                        todo.position.line + 1,
+                        todo.tag,
+                        todo.text
+                    );
+                    if ui
+                        .selectable_label(
+                            false,
+                            egui::RichText::new(text)
+                                .size(self.settings.console_font_size)
+                                .monospace()
+                                .color(egui::Color32::from_code_color(
+                                    self.settings.code_color.todo,
+                                )),
+                        )
+                        .clicked()
+                    {
+                        clicked = Some(todo.start);
+                    }
+                }
+            });
+
+        if let Some(byte_index) = clicked {
+            self.move_cursor(ui.ctx(), byte_index);
+        }
+    }
+
+    /// List the problems found in the code, in the scroll area 'id'. Clicking on one of
+    /// them moves the cursor there.
+    fn draw_problems(&mut self, ui: &mut egui::Ui, id: &str) {
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_source(id)
+            .auto_shrink([false, true])
+            .max_height(ui.available_height())
+            .show(ui, |ui| {
+                let mut diagnostics = self
+                    .analysis
+                    .diagnostics
+                    .iter()
+                    .chain(&self.server_diagnostics)
+                    .chain(&self.lint_diagnostics)
+                    .peekable();
+                if diagnostics.peek().is_none() {
+                    ui.label(egui::RichText::new("No problems").monospace());
+                }
+                for diagnostic in diagnostics {
+                    let (kind, color) = match diagnostic.severity {
+                        Severity::Error => ("error", egui::Color32::RED),
+                        Severity::Warning => ("warning", egui::Color32::YELLOW),
+                    };
+                    let text = format!(
+                        "{}:{}  {}: {}",
+                        diagnostic.position.line + 1,
+                        diagnostic.position.col + 1,
+                        kind,
+                        diagnostic.message
+                    );
+                    if ui
+                        .selectable_label(
+                            false,
+                            egui::RichText::new(text)
+                                .size(self.settings.console_font_size)
+                                .monospace()
+                                .color(color),
+                        )
+                        .clicked()
+                    {
+                        clicked = Some(diagnostic.span.start);
+                    }
+                }
+            });
+
+        if let Some(byte_index) = clicked {
+            self.move_cursor(ui.ctx(), byte_index);
+        }
+    }
+
+    /// Move the cursor of the code editor to 'byte_index' and focus the editor
+    fn move_cursor(&self, ctx: &egui::Context, byte_index: usize) {
+        // The contents may have changed since 'byte_index' was computed
+        let Some(before) = self.contents.get(..byte_index) else {
+            return;
+        };
+        let id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+
+        // The cursor is expressed in characters, not in bytes
+        let index = before.chars().count();
+        state.set_ccursor_range(Some(egui::text::CCursorRange::one(
+            egui::text::CCursor::new(index),
+        )));
+        egui::TextEdit::store_state(ctx, id, state);
+        ctx.memory().request_focus(id);
+    }
+
+    fn draw_console(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            for (tab, name) in [
+                (ConsoleTab::Output, "Output"),
+                (ConsoleTab::Repl, "REPL"),
+                (ConsoleTab::Problems, "Problems"),
+            ] {
+                if ui
+                    .selectable_label(
+                        self.console_tab == tab,
+                        egui::RichText::new(name)
+                            .size(13.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    self.console_tab = tab;
+                }
+            }
+            for (idx, task_run) in self.task_runs.iter().enumerate() {
+                let tab = ConsoleTab::Task(idx);
+                if ui
+                    .selectable_label(
+                        self.console_tab == tab,
+                        egui::RichText::new(&task_run.label)
+                            .size(13.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    self.console_tab = tab;
+                }
+            }
+            ui.separator();
+            if let ConsoleTab::Task(idx) = self.console_tab {
+                self.draw_task_buttons(ui, idx);
+                return;
+            }
+            if self.console_tab == ConsoleTab::Repl {
+                if ui
+                    .button(
+                        egui::RichText::new("Restart")
+                            .size(13.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    self.repl = None;
+                    self.start_repl(ui.ctx());
+                }
+                return;
+            }
+            if self.console_tab == ConsoleTab::Problems {
+                return;
+            }
+
+            if ui
+                .button(
+                    egui::RichText::new("Clear")
+                        .size(13.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .clicked()
+            {
+                self.clear_console();
+            }
+
+            // The text is copied without its ANSI sequences
+            if ui
+                .button(
+                    egui::RichText::new("Copy output")
+                        .size(13.0)
+                        .monospace()
+                        .color(ui.visuals().strong_text_color()),
+                )
+                .clicked()
+            {
+                let (text, _) = ansi::parse(&self.console);
+                ui.output().copied_text = text;
+            }
+
+            // Font size of the console, apart from the one of the code
+            for (label, step) in [("-", -1.0), ("+", 1.0)] {
+                if ui
+                    .button(
+                        egui::RichText::new(label)
+                            .size(13.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    let size = self.settings.console_font_size + step;
+                    self.settings.console_font_size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+                    self.save_settings();
+                }
+            }
+
+            if let Some(ref path) = self.full_output {
+                if ui
+                    .button(
+                        egui::RichText::new("Open full output")
+                            .size(13.0)
+                            .monospace()
+                            .color(ui.visuals().strong_text_color()),
+                    )
+                    .clicked()
+                {
+                    match OutputViewer::load(path) {
+                        Ok(viewer) => self.output_viewer = Some(viewer),
+                        Err(err) => msgbox(
+                            "Error in opening the full output",
+                            err.to_string().as_str(),
+                            rfd::MessageLevel::Error,
+                        ),
+                    }
+                }
+            }
+        });
+
+        if self.console_tab == ConsoleTab::Repl {
+            self.draw_repl(ui);
+            return;
+        }
+        if self.console_tab == ConsoleTab::Problems {
+            self.draw_problems(ui, "console_problems");
+            return;
+        }
+        if let ConsoleTab::Task(idx) = self.console_tab {
+            self.draw_task_output(ui, idx);
+            return;
+        }
+
+        let (mut text, spans) = ansi::parse(&self.console);
+        let matches = search::find_all(&text, &self.console_search);
+        self.draw_console_search(ui, matches.len());
+        let current = (!matches.is_empty()).then_some(self.console_match % matches.len().max(1));
+
+        let word_wrap = self.settings.console_word_wrap;
+        let scroll_area = if word_wrap {
+            egui::ScrollArea::vertical()
+        } else {
+            egui::ScrollArea::both()
+        };
+
+        // Follow the output of the running program
+        scroll_area
+            .id_source("vscroll2")
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                // Remove white border from console
+                ui.visuals_mut().widgets.noninteractive.bg_stroke = egui::Stroke::NONE;
+                // The escape sequences are colored, or just removed
+                let font_id =
+                    egui::FontId::new(self.settings.console_font_size, egui::FontFamily::Monospace);
+                let color = ui.visuals().text_color();
+                let strong_color = ui.visuals().strong_text_color();
+                let strip_ansi = self.settings.strip_ansi;
+                let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                    let spans = if strip_ansi { &[][..] } else { &spans[..] };
+                    let mut job =
+                        ansi::layout_job(text, spans, font_id.clone(), color, strong_color);
+                    search::highlight(&mut job, &matches, current);
+                    job.wrap.max_width = if word_wrap { wrap_width } else { f32::INFINITY };
+                    ui.fonts().layout_job(job)
+                };
+                let scroll_to_match = mem::take(&mut self.scroll_to_match);
+                ui.add_sized(ui.available_size(), |ui: &mut egui::Ui| {
+                    let output = egui::TextEdit::multiline(&mut text)
+                        .code_editor()
+                        .layouter(&mut layouter)
+                        .interactive(false)
+                        .show(ui);
+
+                    // Bring the current match into view
+                    if let (true, Some(current)) = (scroll_to_match, current) {
+                        let index = text[..matches[current].start].chars().count();
+                        let cursor = output.galley.from_ccursor(egui::text::CCursor::new(index));
+                        let rect = output
+                            .galley
+                            .pos_from_cursor(&cursor)
+                            .translate(output.text_draw_pos.to_vec2());
+                        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                    }
+                    output.response
+                });
+            });
+    }
+
+    /// Draw the find bar of the console, for 'count' matches
+    fn draw_console_search(&mut self, ui: &mut egui::Ui, count: usize) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.console_search)
+                    .hint_text("Find in the console")
+                    .desired_width(200.0),
+            );
+            let mut step = None;
+            if response.changed() {
+                self.console_match = 0;
+                self.scroll_to_match = true;
+            }
+            if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                step = Some(if ui.input().modifiers.shift {
+                    count.saturating_sub(1)
+                } else {
+                    1
+                });
+                response.request_focus();
+            }
+
+            if !self.console_search.is_empty() {
+                let position = match count {
+                    0 => "No results".to_string(),
+                    count => format!("{} of {}", self.console_match % count + 1, count),
+                };
+                ui.label(egui::RichText::new(position).size(13.0).monospace());
+                if ui
+                    .add_enabled(count > 0, egui::Button::new("Previous"))
+                    .clicked()
+                {
+                    step = Some(count.saturating_sub(1));
+                }
+                if ui
+                    .add_enabled(count > 0, egui::Button::new("Next"))
+                    .clicked()
+                {
+                    step = Some(1);
+                }
+            }
+
+            if let (Some(step), true) = (step, count > 0) {
+                self.console_match = (self.console_match % count + step) % count;
+                self.scroll_to_match = true;
+            }
+        });
+    }
+
+    /// Draw the buttons of the page of the task run 'idx', which stop it or close it
+    fn draw_task_buttons(&mut self, ui: &mut egui::Ui, idx: usize) {
+        let Some(task_run) = self.task_runs.get_mut(idx) else {
+            return;
+        };
+        let text = |label: &str| {
+            egui::RichText::new(label)
+                .size(13.0)
+                .monospace()
+                .color(ui.visuals().strong_text_color())
+        };
+        let (stop, close) = (text("Stop"), text("Close"));
+        let stop = ui.add_enabled(task_run.run.is_some(), egui::Button::new(stop));
+        let close = ui.button(close);
+        if stop.clicked() || close.clicked() {
+            if let Some(ref mut run) = task_run.run {
+                if let Err(err) = run.stop() {
+                    msgbox(
+                        "Error in stopping the task",
+                        err.to_string().as_str(),
+                        rfd::MessageLevel::Error,
+                    );
+                }
+            }
+        }
+        if close.clicked() {
+            self.task_runs.remove(idx);
+            self.console_tab = ConsoleTab::Output;
+        }
+    }
+
+    /// Draw the output of the task run 'idx'
+    fn draw_task_output(&mut self, ui: &mut egui::Ui, idx: usize) {
+        let Some(task_run) = self.task_runs.get(idx) else {
+            return;
+        };
+        let font_id =
+            egui::FontId::new(self.settings.console_font_size, egui::FontFamily::Monospace);
+        let (text, spans) = ansi::parse(&task_run.output);
+        let spans = if self.settings.strip_ansi {
+            &[][..]
+        } else {
+            &spans[..]
+        };
+        let job = ansi::layout_job(
+            &text,
+            spans,
+            font_id,
+            ui.visuals().text_color(),
+            ui.visuals().strong_text_color(),
+        );
+        egui::ScrollArea::both()
+            .id_source(("task", idx))
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.label(job);
+            });
+    }
+
+    /// Draw the transcript of the REPL, above the line where code is typed for it
+    fn draw_repl(&mut self, ui: &mut egui::Ui) {
+        if self.repl.is_none() {
+            self.start_repl(ui.ctx());
+        }
+        let Some(ref mut repl) = self.repl else {
+            return;
+        };
+        let running = repl.poll();
+        if running && repl.is_closed() {
+            ui.ctx().request_repaint_after(RUN_POLL_INTERVAL);
+        }
+
+        let font_id =
+            egui::FontId::new(self.settings.console_font_size, egui::FontFamily::Monospace);
+        let input_height = ui.fonts().row_height(&font_id) + 2.0 * ui.spacing().item_spacing.y;
+        let (text, spans) = ansi::parse(&repl.transcript);
+        let spans = if self.settings.strip_ansi {
+            &[][..]
+        } else {
+            &spans[..]
+        };
+        let job = ansi::layout_job(
+            &text,
+            spans,
+            font_id.clone(),
+            ui.visuals().text_color(),
+            ui.visuals().strong_text_color(),
+        );
+        egui::ScrollArea::both()
+            .id_source("repl")
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - input_height)
+            .show(ui, |ui| {
+                ui.label(job);
+            });
+
+        let response = ui.add_enabled(
+            running,
+            egui::TextEdit::singleline(&mut self.repl_input)
+                .font(font_id)
+                .hint_text("betty code")
+                .desired_width(f32::INFINITY),
+        );
+        if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+            let code = mem::take(&mut self.repl_input);
+            self.send_to_repl(ui.ctx(), &code);
+            response.request_focus();
+        }
+    }
+
+    /// Start the betty interpreter for the REPL
+    fn start_repl(&mut self, ctx: &egui::Context) {
+        if !self.locate_betty() {
+            self.console_tab = ConsoleTab::Output;
+            return;
+        }
+        match Repl::start(
+            &self.settings.betty_exe_path,
+            &self.settings.env,
+            self.settings.stderr_color,
+            ctx,
+        ) {
+            Ok(repl) => self.repl = Some(repl),
+            Err(err) => {
+                // Not trying again on each frame
+                self.console_tab = ConsoleTab::Output;
+                msgbox(
+                    "Error in starting the REPL",
+                    err.to_string().as_str(),
+                    rfd::MessageLevel::Error,
+                );
+            }
+        }
+    }
+
+    /// Write 'code' to the REPL, starting it if needed
+    fn send_to_repl(&mut self, ctx: &egui::Context, code: &str) {
+        self.console_tab = ConsoleTab::Repl;
+        if self.repl.is_none() {
+            self.start_repl(ctx);
+        }
+        let Some(ref mut repl) = self.repl else {
+            return;
+        };
+        if let Err(err) = repl.send(code) {
+            msgbox(
+                "Error in sending code to the REPL",
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            );
+        }
+    }
+
+    /// Send the code selected in the editor to the REPL, or the line with the cursor
+    /// if nothing is selected
+    fn send_selection_to_repl(&mut self, ctx: &egui::Context) {
+        let Some(range) = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.ccursor_range())
+        else {
+            return;
+        };
+        let [start, end] = range.sorted();
+        let start = char_to_byte_index(&self.contents, start.index);
+        let end = char_to_byte_index(&self.contents, end.index);
+
+        let code = if start == end {
+            let line_start = self.contents[..start].rfind('\n').map_or(0, |idx| idx + 1);
+            let line_end = self.contents[start..]
+                .find('\n')
+                .map_or(self.contents.len(), |idx| start + idx);
+            self.contents[line_start..line_end].to_string()
+        } else {
+            self.contents[start..end].to_string()
+        };
+        if !code.trim().is_empty() {
+            self.send_to_repl(ctx, &code);
+        }
+    }
+}
+
+impl CodeEditor {
+    /// Return the numbers of the lines on the top left of the editor. They are built
+    /// again only when the number of lines, or the wrapping of the lines, changes.
+    fn lines(&mut self) -> String {
+        // + 1 because we add one newline at least
+        let line_count = self.contents.matches('\n').count() + 1;
+        let galley = self
+            .editor_galley
+            .clone()
+            .filter(|_| self.settings.editor.word_wrap);
+        let same_galley = match (&galley, &self.gutter.galley) {
+            (Some(galley), Some(old_galley)) => Arc::ptr_eq(galley, old_galley),
+            (None, None) => true,
+            _ => false,
+        };
+        if same_galley && line_count == self.gutter.line_count {
+            return self.gutter.text.clone();
+        }
+
+        let (row_count, mut lines) = match galley {
+            // Wrapped lines take many rows
+            Some(ref galley) => (galley.rows.len(), editing::wrapped_line_numbers(galley)),
+            None => (line_count, editing::line_numbers(line_count)),
+        };
+
+        // If we don't do this shitty thing, the label gets pushed in the middle.
+        // Therefore, we add as many newlines as we need to fill the ui (empirical count)
+        // It looks weird but at least it works :(
+        if row_count < 35 {
+            let delta = 35 - row_count;
+            for _ in 0..delta {
+                lines.push('\n');
+            }
+        }
+        self.gutter = Gutter {
+            line_count,
+            galley,
+            text: lines.clone(),
+        };
+        lines
+    }
+
+    /// Apply the editor settings to the keys typed in the code editor, before it
+    /// handles them. Return how the cursor moves because of a bracket or a quote.
+    fn handle_editor_input(&mut self, ui: &egui::Ui, id: egui::Id) -> Option<Pairing> {
+        if !ui.memory().has_focus(id) {
+            return None;
+        }
+        let editor = self.settings.editor;
+        let mut state = egui::TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+        let cursor = state.ccursor_range();
+
+        let pairing = {
+            let mut input = ui.input_mut();
+            if mem::take(&mut self.play_macro) {
+                self.key_macro.play(&mut input.events);
+            } else {
+                self.key_macro.record(&input.events);
+            }
+            editing::tabs_to_spaces(&mut input.events, editor.tab_size);
+            match cursor {
+                Some(cursor) if editor.auto_pair => {
+                    editing::auto_pair(&mut input.events, &self.contents, cursor)
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(Pairing::Skip(ccursor)) = pairing {
+            state.set_ccursor_range(Some(egui::text::CCursorRange::one(ccursor)));
+            egui::TextEdit::store_state(ui.ctx(), id, state);
+        }
+        pairing
+    }
+
+    /// Save the file if it has not been edited for the autosave interval. Files that
+    /// have never been saved, and read-only files, are not saved automatically.
+    fn autosave(&mut self, ctx: &egui::Context) {
+        let interval = Duration::from_secs(self.settings.editor.autosave_interval);
+        let Some(ref path) = self.path else {
+            return;
+        };
+        if interval.is_zero() || self.saved || self.file_io.is_some() {
+            return;
+        }
+        let elapsed = self.last_edit.elapsed();
+        if elapsed >= interval {
+            if !fileio::is_read_only(path) {
+                self.save_file();
+            }
+        } else {
+            ctx.request_repaint_after(interval - elapsed);
+        }
+    }
+
+    /// Write the unsaved contents to the recovery folder, at most once in the recovery
+    /// interval, so that they are not lost if the IDE crashes
+    fn keep_for_recovery(&mut self, ctx: &egui::Context) {
+        let interval = Duration::from_secs(self.settings.editor.recovery_interval);
+        if interval.is_zero() || self.saved || self.last_recovery > self.last_edit {
+            return;
+        }
+        let elapsed = self.last_recovery.elapsed();
+        if elapsed < interval {
+            ctx.request_repaint_after(interval - elapsed);
+            return;
+        }
+        let recovery = Recovery {
+            path: self.path.clone(),
+            contents: self.contents.clone(),
+        };
+        if let Err(err) = recovery.save() {
+            log::warning!(format!(
+                "The unsaved contents could not be kept for recovery: {}",
+                err
+            ));
+        }
+        self.last_recovery = Instant::now();
+    }
+
+    /// Forget the contents kept for recovery
+    fn clear_recovery(&mut self) {
+        if let Err(err) = Recovery::clear() {
+            log::warning!(format!("The recovery file could not be removed: {}", err));
+        }
+        self.last_recovery = Instant::now();
+    }
+
+    /// Ask whether to restore the contents left unsaved in the recovery file 'file'
+    /// when a window of the IDE last crashed
+    fn offer_recovery(&mut self, file: PathBuf, recovery: Recovery) {
+        let name = match recovery.path {
+            Some(ref path) => format!("'{}'", path.display()),
+            None => "a new file".to_string(),
+        };
+        let restore = rfd::MessageDialog::new()
+            .set_title("Unsaved contents found")
+            .set_description(&format!(
+                "Colors did not close properly while {} had unsaved changes. Do you want to restore them?",
+                name
+            ))
+            .set_level(rfd::MessageLevel::Warning)
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if restore {
+            log::info!(format!("Restored the unsaved contents of {}", name));
+            // They are kept as the ones of this window until they are saved
+            if let Err(err) = recovery.save() {
+                log::warning!(format!(
+                    "The unsaved contents could not be kept for recovery: {}",
+                    err
+                ));
+            }
+            self.git_head = recovery.path.as_deref().and_then(git::head_contents);
+            self.path = recovery.path;
+            self.contents = recovery.contents;
+            self.bom = None;
+            self.saved = false;
+            self.welcome = None;
+        }
+        if let Err(err) = recovery::remove(file) {
+            log::warning!(format!("The recovery file could not be removed: {}", err));
+        }
+    }
+
+    /// Open another window of the IDE, with the same settings and recent files
+    fn new_window(&self) {
+        let started = env::current_exe().and_then(|exe| {
+            process::Command::new(exe)
+                .args(paths::new_window_args())
+                .spawn()
+        });
+        if let Err(err) = started {
+            msgbox(
+                "Error in opening a new window",
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            );
+        }
+    }
+
+    /// Start the language server of the file type of the open file, or stop it if it
+    /// has none, tell it about the edits and handle what it sent
+    fn sync_language_server(&mut self, ctx: &egui::Context) {
+        let command = self
+            .path
+            .as_deref()
+            .and_then(|path| self.settings.file_type(Some(path)).language_server);
+        if self.language_server.as_ref().map(LanguageServer::command) != command.as_deref() {
+            self.language_server = None;
+            self.server_diagnostics.clear();
+            self.completions.clear();
+            self.completion_at = None;
+        }
+        let (Some(command), Some(path)) = (command, self.path.clone()) else {
+            return;
+        };
+
+        if self.language_server.is_none() {
+            if self.failed_language_server.as_ref() == Some(&command) {
+                return;
+            }
+            let root = runconfig::project_dir(&path);
+            match LanguageServer::start(&command, &root, ctx.clone()) {
+                Ok(server) => self.language_server = Some(server),
+                Err(err) => {
+                    log::warning!(format!(
+                        "The language server '{}' could not be started: {}",
+                        command, err
+                    ));
+                    self.failed_language_server = Some(command);
+                    return;
+                }
+            }
+        }
+        let Some(ref mut server) = self.language_server else {
+            return;
+        };
+
+        let told = if server.path() != Some(&path) {
+            self.server_diagnostics.clear();
+            self.hover_asked = None;
+            self.completion_at = None;
+            let syntax = self.settings.file_type(Some(&path)).syntax;
+            server.open(&path, &lsp::language_id(&path, &syntax), &self.contents)
+        } else {
+            server.change(&self.contents)
+        };
+        if let Err(err) = told {
+            log::warning!(format!("The language server could not be told: {}", err));
+        }
+
+        for event in server.poll() {
+            match event {
+                lsp::Event::Diagnostics(diagnostics) => self.server_diagnostics = diagnostics,
+                lsp::Event::Hover(text) => self.hover_text = Some(text),
+                lsp::Event::Completion(completions) => self.completions = completions,
+            }
+        }
+    }
+
+    /// Show the text explaining the code at 'byte_index', under the mouse, asking the
+    /// language server for it first
+    fn hover_code(&mut self, response: &egui::Response, byte_index: usize) {
+        let Some(ref mut server) = self.language_server else {
+            return;
+        };
+        if self.hover_asked != Some(byte_index) {
+            self.hover_asked = Some(byte_index);
+            self.hover_text = None;
+            if let Err(err) = server.hover(byte_index) {
+                log::warning!(format!("The language server could not be asked: {}", err));
+            }
+        } else if let Some(ref text) = self.hover_text {
+            response.clone().on_hover_text(text);
+        }
+    }
+
+    /// Ask the language server for the completions at the cursor of the editor
+    fn ask_completions(&mut self, ctx: &egui::Context) {
+        let Some(ref mut server) = self.language_server else {
+            return;
+        };
+        let Some(range) = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.ccursor_range())
+        else {
+            return;
+        };
+        let index = char_to_byte_index(&self.contents, range.primary.index);
+        self.completions.clear();
+        self.completion_at = Some(index);
+        if let Err(err) = server.complete(index) {
+            log::warning!(format!("The language server could not be asked: {}", err));
+        }
+    }
+
+    /// List the completions at 'byte_index' of the code, below 'pos'. Clicking on one
+    /// of them replaces the word before 'byte_index' with it.
+    fn draw_completions(&mut self, ctx: &egui::Context, pos: egui::Pos2, byte_index: usize) {
+        if self.completions.is_empty() {
+            return;
+        }
+        let mut chosen = None;
+        egui::Area::new("completions")
+            .fixed_pos(pos)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(MAX_COMPLETIONS_HEIGHT)
+                        .show(ui, |ui| {
+                            for completion in &self.completions {
+                                let label =
+                                    egui::RichText::new(&completion.label).font(egui::FontId::new(
+                                        self.settings.code_font_size,
+                                        egui::FontFamily::Monospace,
+                                    ));
+                                if ui.selectable_label(false, label).clicked() {
+                                    chosen = Some(completion.insert_text.clone());
+                                }
+                            }
+                        });
+                });
+            });
+
+        let Some(text) = chosen else {
+            return;
+        };
+        self.completions.clear();
+        self.completion_at = None;
+        let Some(before) = self.contents.get(..byte_index) else {
+            return;
+        };
+        let start = before
+            .rfind(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .map_or(0, |idx| {
+                idx + before[idx..].chars().next().map_or(1, char::len_utf8)
+            });
+        self.contents.replace_range(start..byte_index, &text);
+        self.saved = false;
+        self.last_edit = Instant::now();
+        self.move_cursor(ctx, start + text.len());
+    }
+
+    /// Show the signature of the function whose call is being typed above the cursor,
+    /// with the parameter of the argument being typed underlined
+    fn draw_signature_help(
+        &self,
+        ctx: &egui::Context,
+        galley: &egui::Galley,
+        text_pos: egui::Pos2,
+    ) {
+        let Some(cursor) = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.ccursor_range())
+            .filter(|range| range.primary == range.secondary)
+            .map(|range| range.primary)
+        else {
+            return;
+        };
+        let Highlighting::Tokens(config) = self
+            .syntaxes
+            .highlighting_for(self.path.as_deref(), &self.settings)
+        else {
+            return;
+        };
+        if !config.betty {
+            return;
+        }
+        let byte_index = char_to_byte_index(&self.contents, cursor.index);
+        let Some(Signature {
+            label,
+            params,
+            active,
+        }) = signature::at_cursor(&self.contents, byte_index, config, &self.reference)
+        else {
+            return;
+        };
+
+        let rect = galley
+            .pos_from_cursor(&galley.from_ccursor(cursor))
+            .translate(text_pos.to_vec2());
+        let font_id = egui::FontId::new(self.settings.code_font_size, egui::FontFamily::Monospace);
+        egui::Area::new("signature_help")
+            .fixed_pos(rect.left_top())
+            .pivot(egui::Align2::LEFT_BOTTOM)
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let (before, param, after) = match active {
+                        Some(idx) => {
+                            let span = params[idx].clone();
+                            (
+                                &label[..span.start],
+                                &label[span.clone()],
+                                &label[span.end..],
+                            )
+                        }
+                        None => (label.as_str(), "", ""),
+                    };
+                    let plain = egui::TextFormat {
+                        font_id,
+                        color: ui.visuals().text_color(),
+                        ..Default::default()
+                    };
+                    let strong_color = ui.visuals().strong_text_color();
+                    let strong = egui::TextFormat {
+                        color: strong_color,
+                        underline: egui::Stroke::new(1.0, strong_color),
+                        ..plain.clone()
+                    };
+                    let mut job = egui::text::LayoutJob::default();
+                    job.append(before, 0.0, plain.clone());
+                    job.append(param, 0.0, strong);
+                    job.append(after, 0.0, plain);
+                    ui.label(job);
+                });
+            });
+    }
+
+    /// Use the code colors of the theme called 'name', and remember it in the settings
+    fn set_theme(&mut self, name: String) {
+        if let Some(color) = self.themes.get(&name) {
+            self.settings.code_color = color;
+            self.settings.theme = Some(name);
+            self.clear_highlight_cache();
+        }
+    }
+
+    /// Add a VS Code color theme chosen by the user to the themes, and use it
+    fn import_theme(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("VS Code theme", &["json"])
+            .pick_file()
+        else {
+            // The user exited the file dialog
+            return;
+        };
+
+        let (name, color) = match vscode::import(&path) {
+            Ok(theme) => theme,
+            Err(err) => {
+                msgbox(
+                    "Error in importing the theme",
+                    &err,
+                    rfd::MessageLevel::Error,
+                );
+                return;
+            }
+        };
+        self.save_theme(name, color);
+    }
+
+    /// Save 'color' as the theme called 'name', and use it
+    fn save_theme(&mut self, name: String, color: CodeColor) {
+        if let Err(err) = self.themes.add(&name, color) {
+            msgbox(
+                &format!("Error in saving the theme '{}'", name),
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            );
+            return;
+        }
+        self.set_theme(name);
+        self.save_settings();
+    }
+
+    /// Scale the interface as in the settings, if it is not already. The scale is not
+    /// changed while the pointer is in use, e.g. dragging the slider of the scale.
+    fn update_scale(&mut self, ctx: &egui::Context) {
+        if self.ui_scale == Some(self.settings.ui_scale) || ctx.is_using_pointer() {
+            return;
+        }
+        let native = self.native_pixels_per_point.unwrap_or(1.0);
+        ctx.set_pixels_per_point(native * self.settings.ui_scale);
+        self.ui_scale = Some(self.settings.ui_scale);
+    }
+
+    /// Switch to the visuals of the UI theme in the settings, if they are not in use.
+    /// The code colors follow them when they come from the built-in dark or light theme.
+    fn update_visuals(&mut self, ctx: &egui::Context) {
+        let visuals = match self.settings.ui_theme {
+            UiTheme::Dark => eframe::Theme::Dark,
+            UiTheme::Light => eframe::Theme::Light,
+            UiTheme::System => self.system_theme.unwrap_or(eframe::Theme::Dark),
+        };
+        if self.visuals == Some(visuals) {
+            return;
+        }
+        ctx.set_visuals(visuals.egui_visuals());
+        self.visuals = Some(visuals);
+
+        let paired = match visuals {
+            eframe::Theme::Dark => "dark",
+            eframe::Theme::Light => "light",
+        };
+        if matches!(self.settings.theme.as_deref(), Some("dark" | "light")) {
+            self.set_theme(paired.to_string());
+        }
+    }
+
+    /// Forget the highlighted code, e.g. because it was highlighted with old colors
+    fn clear_highlight_cache(&mut self) {
+        self.highlight_cache = HighlightCache::default();
+        #[cfg(feature = "syntect")]
+        {
+            self.grammar_cache = GrammarCache::default();
+        }
+    }
+
+    /// Write the settings to the settings file, telling the user if it fails
+    fn save_settings(&self) {
+        if let Err(err) = self.settings.save() {
+            msgbox(
+                "Error in saving the settings",
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            );
+        }
+    }
+
+    /// If there is a file loaded, we want to show whether the path was saved or not.
+    /// Add a '+' if the file has been saved or '-' if not.
+    /// Show the name of the file in the title of the window, and in the taskbar, with
+    /// a `*` if it has unsaved changes
+    fn update_window_title(&mut self, frame: &mut eframe::Frame) {
+        let title = match self.path {
+            Some(ref path) => format!(
+                "{}{} \u{2014} Colors",
+                path_name_as_string(path),
+                if self.saved { "" } else { "*" }
+            ),
+            None => "Colors".to_string(),
+        };
+        if title != self.window_title {
+            frame.set_window_title(&title);
+            self.window_title = title;
+        }
+    }
+
+    fn set_title(&self) -> String {
+        match self.path {
+            Some(ref path) if self.saved => format!("+ {}", path_name_as_string(path)),
+            Some(ref path) if !self.saved => format!("- {}", path_name_as_string(path)),
+            _ => "No file loaded".into(),
+        }
+    }
+
+    /// A Ctrl+S event is accepted if:
+    ///     - Ctrl is pressed
+    ///     - S is pressed
+    ///     - The current file is not saved
+    fn handle_ctrl_s(&mut self, events: std::slice::Iter<'_, egui::Event>) {
+        for event in events {
+            if matches!(event, egui::Event::Key { key, pressed, modifiers }
+            if *pressed
+                && matches!(key, egui::Key::S)
+                && modifiers.ctrl
+                && !self.saved
+            ) {
+                self.save_file();
+            }
+        }
+    }
+    /// Ctrl+Plus and Ctrl+Minus change the size of the code by one point, and Ctrl+0
+    /// resets it. The size is saved in the settings.
+    fn handle_zoom(&mut self, events: std::slice::Iter<'_, egui::Event>) {
+        let default_size = Settings::default().code_font_size;
+        for event in events {
+            let egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            } = event
+            else {
+                continue;
+            };
+            if !modifiers.ctrl {
+                continue;
+            }
+            let size = match key {
+                egui::Key::PlusEquals => self.settings.code_font_size + 1.0,
+                egui::Key::Minus => self.settings.code_font_size - 1.0,
+                egui::Key::Num0 => default_size,
+                _ => continue,
+            };
+            self.set_code_font_size(size);
+        }
+    }
+
+    /// Change the font size of the code to 'size', within the allowed range
+    fn set_code_font_size(&mut self, size: f32) {
+        self.settings.code_font_size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.clear_highlight_cache();
+        self.save_settings();
+    }
+
+    /// A Ctrl+R event is accepted if:
+    ///     - Ctrl is pressed
+    ///     - R is pressed
+    ///     - The current file is not saved
+    fn handle_ctrl_r(&mut self, ctx: &egui::Context, events: std::slice::Iter<'_, egui::Event>) {
+        for event in events {
+            if matches!(event, egui::Event::Key { key, pressed, modifiers }
+            if *pressed
+                && matches!(key, egui::Key::R)
+                && modifiers.ctrl
+                && !self.saved
+            ) {
+                self.run_file(ctx);
+            }
+        }
+    }
+
+    /// If the cursor is on a function declaration, insert a documentation stub above
+    /// it, formatted as per user settings. The cursor stays on the declaration.
+    fn insert_doc_stub(&mut self, ctx: &egui::Context) {
+        let id = egui::Id::new(EDITOR_ID);
+        let Some(mut state) = egui::TextEdit::load_state(ctx, id) else {
+            return;
+        };
+        let Some(range) = state.ccursor_range() else {
+            return;
+        };
+
+        // The cursor is expressed in characters, not in bytes
+        let cursor = char_to_byte_index(&self.contents, range.primary.index);
+        let line_start = self.contents[..cursor].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = self.contents[cursor..]
+            .find('\n')
+            .map_or(self.contents.len(), |idx| cursor + idx);
+
+        let Some(stub) = docstub::make_stub(
+            &self.contents[line_start..line_end],
+            &self.settings.doc_stub,
+        ) else {
+            return;
+        };
+        self.contents.insert_str(line_start, &stub);
+        self.saved = false;
+        self.last_edit = Instant::now();
+
+        // Shift the cursor by the inserted characters
+        let shift = stub.chars().count();
+        state.set_ccursor_range(Some(egui::text::CCursorRange::two(
+            egui::text::CCursor::new(range.secondary.index + shift),
+            egui::text::CCursor::new(range.primary.index + shift),
+        )));
+        egui::TextEdit::store_state(ctx, id, state);
+    }
+
+    /// Format the code with the formatter of its file type, or reindent it if it is
+    /// highlighted by tokens. The cursor stays at its place in the code.
+    fn format_document(&mut self, ctx: &egui::Context) {
+        let file_type = self.settings.file_type(self.path.as_deref());
+        let formatted = match file_type.formatter {
+            Some(ref command) => {
+                match format::run_formatter(command, self.path.as_deref(), &self.contents) {
+                    Ok(formatted) => formatted,
+                    Err(err) => {
+                        msgbox(
+                            "Error in formatting the document",
+                            &err,
+                            rfd::MessageLevel::Error,
+                        );
+                        return;
+                    }
+                }
+            }
+            None => match self
+                .syntaxes
+                .highlighting_for(self.path.as_deref(), &self.settings)
+            {
+                Highlighting::Tokens(config) => {
+                    format::reindent(&self.contents, config, self.settings.editor.tab_size)
+                }
+                // Nothing tells where the blocks of the code are
+                _ => return,
+            },
+        };
+        if formatted == self.contents {
+            return;
+        }
+
+        let id = egui::Id::new(EDITOR_ID);
+        if let Some(mut state) = egui::TextEdit::load_state(ctx, id) {
+            if let Some(range) = state.ccursor_range() {
+                let index = format::moved_cursor(&self.contents, &formatted, range.primary.index);
+                state.set_ccursor_range(Some(egui::text::CCursorRange::one(
+                    egui::text::CCursor::new(index),
+                )));
+                egui::TextEdit::store_state(ctx, id, state);
+            }
+        }
+        self.contents = formatted;
+        self.saved = false;
+        self.last_edit = Instant::now();
+    }
+
+    /// Type the recorded keystrokes again in the editor, at the next frame
+    fn play_key_macro(&mut self, ctx: &egui::Context) {
+        if !self.key_macro.can_play() {
+            return;
+        }
+        self.play_macro = true;
+        ctx.memory().request_focus(egui::Id::new(EDITOR_ID));
+        ctx.request_repaint();
+    }
+
+    /// Run the script in 'path' on the code and its selection
+    fn run_script(&mut self, ctx: &egui::Context, path: &Path) {
+        let name = macros::script_name(path);
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(err) => {
+                msgbox(
+                    &format!("Error in reading the script '{}'", name),
+                    err.to_string().as_str(),
+                    rfd::MessageLevel::Error,
+                );
+                return;
+            }
+        };
+
+        let id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+        let selection = state.ccursor_range().map_or(0..0, |range| {
+            let (start, end) = (range.primary.index, range.secondary.index);
+            start.min(end)..start.max(end)
+        });
+        let buffer = Buffer {
+            text: self.contents.clone(),
+            selection,
+        };
+        let buffer = match macros::run_script(&script, buffer) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                msgbox(
+                    &format!("Error in the script '{}'", name),
+                    &err,
+                    rfd::MessageLevel::Error,
+                );
+                return;
+            }
+        };
+        log::info!(format!("Ran the script '{}'", name));
+
+        if buffer.text != self.contents {
+            self.contents = buffer.text;
+            self.saved = false;
+            self.last_edit = Instant::now();
+        }
+        state.set_ccursor_range(Some(egui::text::CCursorRange::two(
+            egui::text::CCursor::new(buffer.selection.start),
+            egui::text::CCursor::new(buffer.selection.end),
+        )));
+        egui::TextEdit::store_state(ctx, id, state);
+        ctx.memory().request_focus(id);
+    }
+
+    /// The code highlighted as in the editor
+    fn highlighted_job(&mut self) -> egui::text::LayoutJob {
+        let code_color = self.settings.code_color;
+        let font_size = self.settings.code_font_size;
+        let make_job = |text: &str, tokens| highlight_tokens(text, tokens, code_color, font_size);
+        match self
+            .syntaxes
+            .highlighting_for(self.path.as_deref(), &self.settings)
+        {
+            Highlighting::Tokens(config) => {
+                let (job, _) =
+                    self.highlight_cache
+                        .layout(&self.contents, config, cache::ALL_LINES, make_job);
+                job
+            }
+            #[cfg(feature = "syntect")]
+            Highlighting::Grammar(grammars, syntax) => {
+                self.grammar_cache
+                    .layout(&self.contents, grammars, syntax, make_job)
+            }
+            Highlighting::Plain => make_job(
+                &self.contents,
+                vec![Token(
+                    TokenType::Other,
+                    0..self.contents.len(),
+                    Position::default(),
+                )],
+            ),
+        }
+    }
+
+    /// Write the highlighted code, on 'background', to an HTML file chosen by the user
+    fn export_html(&mut self, background: egui::Color32) {
+        let name = self.path.as_deref().and_then(Path::file_stem).map_or_else(
+            || "untitled".to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("HTML file", &["html"])
+            .set_title("Export as HTML")
+            .set_file_name(&format!("{}.html", name))
+            .save_file()
+        else {
+            return;
+        };
+
+        let title = self.path.as_deref().map_or(name, path_name_as_string);
+        let html = html::export(&self.highlighted_job(), &title, background);
+        match fs::write(&path, html) {
+            Ok(()) => log::info!(format!("Exported the code to '{}'", path.display())),
+            Err(err) => msgbox(
+                &format!("Error in writing to file '{}'", path_name_as_string(&path)),
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            ),
+        }
+    }
+
+    /// Print the highlighted code, on 'background', with its line numbers and a header
+    /// naming the file. The page is opened in the browser, whose print dialog prints it
+    /// or saves it as PDF.
+    fn print_file(&mut self, ctx: &egui::Context, background: egui::Color32) {
+        let name = self
+            .path
+            .as_deref()
+            .map_or_else(|| "untitled".to_string(), path_name_as_string);
+        let header = format!(
+            "{} \u{2014} {}",
+            name,
+            chrono::Local::now().format("%Y-%m-%d %H:%M")
+        );
+        let html = html::print_page(&self.highlighted_job(), &header, background);
+        let path = env::temp_dir().join(PRINT_FILE);
+        match fs::write(&path, html) {
+            Ok(()) => {
+                ctx.output().open_url = Some(egui::output::OpenUrl::new_tab(path.to_string_lossy()))
+            }
+            Err(err) => msgbox(
+                &format!("Error in writing to file '{}'", path_name_as_string(&path)),
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            ),
+        }
+    }
+
+    /// Upload the selection, or the whole code if nothing is selected, to the paste
+    /// service of the settings. Its URL is copied once the upload ends.
+    fn share_code(&mut self, ctx: &egui::Context) {
+        let token = share::load_token();
+        if token.is_empty() {
+            msgbox(
+                "No token for sharing",
+                "Set the token of the paste service in the Editor page of the settings.",
+                rfd::MessageLevel::Warning,
+            );
+            return;
+        }
+
+        let selection = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.ccursor_range())
+            .map(|range| {
+                let [start, end] = range.sorted();
+                char_to_byte_index(&self.contents, start.index)
+                    ..char_to_byte_index(&self.contents, end.index)
+            })
+            .filter(|range| !range.is_empty());
+        let code = match selection {
+            Some(range) => &self.contents[range],
+            None => &self.contents,
+        };
+        let name = self
+            .path
+            .as_deref()
+            .map_or_else(|| "untitled".to_string(), path_name_as_string);
+        self.share = Some(Share::start(&self.settings.share, &token, &name, code));
+    }
+
+    /// Copy the URL of the shared code once its upload ends, or show why it failed
+    fn poll_share(&mut self, ctx: &egui::Context) {
+        let Some(ref share) = self.share else {
+            return;
+        };
+        let Some(result) = share.poll() else {
+            ctx.request_repaint_after(RUN_POLL_INTERVAL);
+            return;
+        };
+        self.share = None;
+        match result {
+            Ok(url) => {
+                log::info!(format!("Shared the code at '{}'", url));
+                ctx.output().copied_text = url.clone();
+                msgbox(
+                    "Code shared",
+                    &format!("The link {} was copied to the clipboard.", url),
+                    rfd::MessageLevel::Info,
+                );
+            }
+            Err(err) => msgbox(
+                "Error in sharing the code",
+                err.as_str(),
+                rfd::MessageLevel::Error,
+            ),
+        }
+    }
+
+    /// Handler for saving the current contents
+    fn save_file(&mut self) {
+        // A file being saved for the first time has no path until it is done
+        self.wait_file_io();
+        let path = match self.path {
+            Some(ref path) if fileio::is_read_only(path) => {
+                let save_as = rfd::MessageDialog::new()
+                    .set_title("Read-only file")
+                    .set_description(&format!(
+                        "The file '{}' is read-only. Do you want to save it as another file?",
+                        path_name_as_string(path)
+                    ))
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+                if !save_as {
+                    return;
+                }
+                match ask_save_path("Save as") {
+                    Some(path) => path,
+                    None => return,
+                }
+            }
+            Some(ref path) => path.clone(),
+            None => match ask_save_path("Create file") {
+                Some(path) => path,
+                None => return,
+            },
+        };
+
+        self.save_file_contents(path);
+    }
+
+    /// The run configuration chosen for the project of the open file, if any
+    fn selected_run_config(&self) -> Option<&RunConfig> {
+        let name = self.run_config.as_deref()?;
+        self.run_configs.as_ref()?.get(name)
+    }
+
+    /// Load the run configurations of the project of the open file, if they are not
+    /// loaded yet
+    fn load_run_configs(&mut self) {
+        let dir = self.path.as_deref().map(runconfig::project_dir);
+        if dir.as_deref() == self.run_configs.as_ref().map(RunConfigs::dir) {
+            return;
+        }
+        self.run_config = None;
+        self.run_configs_window = None;
+        self.run_configs = dir.map(|dir| match RunConfigs::load(&dir) {
+            Ok(configs) => configs,
+            Err(err) => {
+                msgbox(
+                    "Error in loading the run configurations",
+                    &err,
+                    rfd::MessageLevel::Warning,
+                );
+                RunConfigs::empty(&dir)
+            }
+        });
+    }
+
+    /// Draw the list of the run configurations of the project, to choose one
+    fn draw_run_config(&mut self, ui: &mut egui::Ui) {
+        self.load_run_configs();
+        let Some(ref configs) = self.run_configs else {
+            return;
+        };
+
+        let selected = self
+            .selected_run_config()
+            .map_or("Settings", |c| c.name.as_str());
+        let mut choice = None;
+        egui::ComboBox::from_id_source("run_config")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(self.run_config.is_none(), "Settings")
+                    .clicked()
+                {
+                    choice = Some(None);
+                }
+                for config in &configs.configs {
+                    let current = self.run_config.as_ref() == Some(&config.name);
+                    if ui.selectable_label(current, &config.name).clicked() {
+                        choice = Some(Some(config.name.clone()));
+                    }
+                }
+                ui.separator();
+                if ui.button("Edit configurations...").clicked() {
+                    self.run_configs_window = Some(RunConfigsWindow::new());
+                }
+            });
+        if let Some(choice) = choice {
+            self.run_config = choice;
+        }
+    }
+
+    /// Make sure that the betty interpreter in the settings exists. If it does not, it
+    /// is looked for, else the user is asked where it is, and the settings are updated.
+    /// Return whether it was found.
+    fn locate_betty(&mut self) -> bool {
+        if paths::find_program(&self.settings.betty_exe_path).is_some() {
+            return true;
+        }
+
+        let found = paths::find_betty().or_else(|| {
+            let locate = rfd::MessageDialog::new()
+                .set_title("betty not found")
+                .set_description(&format!(
+                    "The betty interpreter '{}' does not exist. Do you want to locate it?",
+                    self.settings.betty_exe_path
+                ))
+                .set_level(rfd::MessageLevel::Warning)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if !locate {
+                return None;
+            }
+            rfd::FileDialog::new()
+                .set_title("Locate the betty interpreter")
+                .add_filter("Executable", &["exe"])
+                .pick_file()
+        });
+
+        let Some(path) = found else {
+            return false;
+        };
+        log::warning!(format!(
+            "The betty interpreter '{}' does not exist, '{}' is used instead",
+            self.settings.betty_exe_path,
+            path.display()
+        ));
+        self.settings.betty_exe_path = path.to_string_lossy().into();
+        self.save_settings();
+        true
+    }
+
+    /// Run the current file
+    fn run_file(&mut self, ctx: &egui::Context) {
+        if self.settings.save_and_run {
+            self.save_file();
+        }
+        // The program must read the file as saved
+        self.wait_file_io();
+        // The file is run now, even if it was just saved in watch mode
+        self.rerun = false;
+
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+
+        let template = self.settings.file_type(Some(&path)).run;
+        let Some(template) = template.filter(|template| !template.trim().is_empty()) else {
+            msgbox(
+                "Program execution error",
+                "No run command is configured for this type of file",
+                rfd::MessageLevel::Warning,
+            );
+            return;
+        };
+
+        // The interpreter of the run configuration is the user's business
+        let uses_settings_betty = self
+            .selected_run_config()
+            .map_or(true, |config| config.betty_exe_path.is_none());
+        if template.contains("{betty}") && uses_settings_betty && !self.locate_betty() {
+            return;
+        }
+
+        let mut command;
+        match self.selected_run_config() {
+            Some(config) => {
+                let betty_exe_path = config
+                    .betty_exe_path
+                    .as_ref()
+                    .unwrap_or(&self.settings.betty_exe_path);
+                command = betty_command(&path, betty_exe_path, &template);
+                command.args(runner::split_args(&config.args));
+                command.envs(&self.settings.env).envs(&config.env);
+                if let Some(cwd) = self.run_configs.as_ref().and_then(|c| c.cwd(config)) {
+                    command.current_dir(cwd);
+                }
+            }
+            None => {
+                command = betty_command(&path, &self.settings.betty_exe_path, &template);
+                command.args(runner::split_args(self.settings.run_args(&path)));
+                command.envs(&self.settings.env);
+            }
+        }
+        let program = command.get_program().to_string_lossy().into_owned();
+        match Run::start(command, self.settings.stderr_color, ctx) {
+            Ok(run) => {
+                self.start_console_output();
+                log::info!(format!("Started '{}'", run.command_line()));
+                self.run = Some(run);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => msgbox(
+                "Program execution error",
+                &format!(
+                    "The program '{}' does not exist. Check the path of the betty interpreter \
+                    (betty_exe_path) in the settings or in the run configuration, and the run \
+                    command of this type of file.",
+                    program
+                ),
+                rfd::MessageLevel::Error,
+            ),
+            Err(err) => msgbox(
+                "Program execution error",
+                &format!("'{}' could not be started: {}", program, err),
+                rfd::MessageLevel::Error,
+            ),
+        }
+    }
+
+    /// Add the new output of the running program to the console. Once the program
+    /// exits, its whole output is shown as usual.
+    fn poll_run(&mut self, ctx: &egui::Context) {
+        let Some(ref mut run) = self.run else {
+            return;
+        };
+        match run.poll() {
+            Ok(Progress {
+                output,
+                status: None,
+            }) => {
+                self.append_console_output(&output);
+
+                let timeout = self.settings.run_timeout_secs;
+                let Some(ref mut run) = self.run else {
+                    return;
+                };
+                if run.is_closed() {
+                    ctx.request_repaint_after(RUN_POLL_INTERVAL);
+                }
+                if timeout > 0 {
+                    let limit = Duration::from_secs(timeout);
+                    // Woken up in time to stop the program, if it prints nothing
+                    if !run.is_timed_out() {
+                        ctx.request_repaint_after(limit.saturating_sub(run.elapsed()));
+                    }
+                    if let Err(err) = run.stop_after(limit) {
+                        msgbox(
+                            "Error in stopping the program",
+                            err.to_string().as_str(),
+                            rfd::MessageLevel::Error,
+                        );
+                    }
+                }
+            }
+            Ok(Progress {
+                output,
+                status: Some(status),
+            }) => {
+                log::debug!(format!(
+                    "'{}' exited ({}) after {:.2}s",
+                    run.command_line(),
+                    status,
+                    run.elapsed().as_secs_f32()
+                ));
+                let footer = run_footer(status, run);
+                let stopped = run.is_cancelled() || run.is_timed_out();
+                let failed = !status.success() && !stopped;
+                let mut record = RunRecord {
+                    command: run.command_line().to_string(),
+                    started: run.started_at(),
+                    exit_code: status.code().filter(|_| !stopped),
+                    elapsed: run.elapsed(),
+                    output: String::new(),
+                };
+                self.run = None;
+                let mut all_output = mem::take(&mut self.run_output);
+                all_output.push_str(&output);
+                self.mark_run_errors(failed, &all_output);
+                let previous = self.console[..self.console_start].to_string();
+                self.set_console_output(all_output);
+                record.output = format!("{}{}", self.console, footer);
+                self.history.push(record);
+                self.console.insert_str(0, &previous);
+                if !self.console.is_empty() && !self.console.ends_with('\n') {
+                    self.console.push('\n');
+                }
+                self.console.push_str(&footer);
+                self.limit_console_lines();
+            }
+            Err(err) => {
+                self.run = None;
+                msgbox(
+                    "Program execution error",
+                    err.to_string().as_str(),
+                    rfd::MessageLevel::Error,
+                );
+            }
+        }
+    }
+
+    /// Run the file again if it was saved in watch mode, replacing the output of the
+    /// previous run. A program that is still running is stopped first.
+    fn rerun_watched(&mut self, ctx: &egui::Context) {
+        if !self.rerun {
+            return;
+        }
+        if !self.watch {
+            self.rerun = false;
+            return;
+        }
+        match self.run {
+            // The file runs again once the program has exited
+            Some(ref run) if run.is_cancelled() => {}
+            Some(_) => self.stop_run(),
+            None => {
+                self.clear_console();
+                self.run_file(ctx);
+            }
+        }
+    }
+
+    /// Lint the saved file in 'path' with the linter of its file type, if it has one.
+    /// A linter still running is left to finish, but its output is ignored.
+    fn start_lint(&mut self, path: &Path) {
+        let Some(template) = self.settings.file_type(Some(path)).linter else {
+            return;
+        };
+        if template.trim().is_empty() {
+            return;
+        }
+        let mut command = betty_command(path, &self.settings.betty_exe_path, &template);
+        command.envs(&self.settings.env);
+        if let Some(dir) = path.parent() {
+            command.current_dir(dir);
+        }
+        self.lint = Some(Lint::start(command));
+    }
+
+    /// Mark the problems found by the linter, once it exits
+    fn poll_lint(&mut self, ctx: &egui::Context) {
+        let Some(ref lint) = self.lint else {
+            return;
+        };
+        let Some(output) = lint.poll() else {
+            ctx.request_repaint_after(RUN_POLL_INTERVAL);
+            return;
+        };
+        match (output, &self.path) {
+            // The output is about the saved code, which may have been edited meanwhile
+            (Ok(output), Some(path)) if self.saved => {
+                self.lint_diagnostics =
+                    lint::parse(&output, &path_name_as_string(path), &self.contents);
+            }
+            (Ok(_), _) => (),
+            (Err(err), _) => {
+                log::warning!(format!(
+                    "'{}' could not lint the file: {}",
+                    lint.program(),
+                    err
+                ));
+            }
+        }
+        self.lint = None;
+    }
+
+    /// Mark in the editor the lines where the run failed, according to its 'output',
+    /// or clear the marks if it did not fail
+    fn mark_run_errors(&mut self, failed: bool, output: &str) {
+        self.run_errors = match (failed, &self.path) {
+            (true, Some(path)) => {
+                let (output, _) = ansi::parse(output);
+                traceback::parse(&output, &path_name_as_string(path), &self.contents)
+            }
+            _ => Vec::new(),
+        };
+    }
+
+    /// Make room in the console for the output of a program starting
+    fn start_console_output(&mut self) {
+        if self.settings.clear_console_on_run {
+            self.set_console_output(String::new());
+        } else if !self.console.is_empty() && !self.console.ends_with('\n') {
+            self.console.push('\n');
+        }
+        self.console_start = self.console.len();
+        self.run_output.clear();
+    }
+
+    /// Debug the open file with the debugger of its file type
+    fn start_debugging(&mut self, ctx: &egui::Context) {
+        if self.settings.save_and_run {
+            self.save_file();
+        }
+        self.wait_file_io();
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        let command = self.settings.file_type(Some(&path)).debugger;
+        let Some(command) = command.filter(|command| !command.trim().is_empty()) else {
+            msgbox(
+                "Debugging error",
+                "No debugger is configured for this type of file",
+                rfd::MessageLevel::Warning,
+            );
+            return;
+        };
+
+        let args = runner::split_args(self.settings.run_args(&path));
+        let breakpoints = self.breakpoints.iter().map(|line| line + 1).collect();
+        let cwd = runconfig::project_dir(&path);
+        match Debugger::start(&command, &path, args, &cwd, breakpoints, ctx.clone()) {
+            Ok(debugger) => {
+                self.start_console_output();
+                log::info!(format!("Started debugging '{}'", path.display()));
+                self.debugger = Some(debugger);
+            }
+            Err(err) => msgbox(
+                "Debugging error",
+                &format!("The debugger '{}' could not be started: {}", command, err),
+                rfd::MessageLevel::Error,
+            ),
+        }
+    }
+
+    /// Show the output of the program being debugged, and forget the debugger once
+    /// the program ended
+    fn poll_debugger(&mut self) {
+        let Some(ref mut debugger) = self.debugger else {
+            return;
+        };
+        let output = debugger.poll();
+        let ended = debugger.state() == DebugState::Ended;
+        self.append_console_output(&output);
+        if ended {
+            self.debugger = None;
+            if !self.console.is_empty() && !self.console.ends_with('\n') {
+                self.append_console_output("\n");
+            }
+            self.append_console_output("The debugging ended\n");
+        }
+    }
+
+    /// Continue or step the program being debugged with 'step'
+    fn debug_step(&mut self, step: DebugStep) {
+        let Some(ref mut debugger) = self.debugger else {
+            return;
+        };
+        if let Err(err) = step(debugger) {
+            log::warning!(format!("The debugger could not be told: {}", err));
+        }
+    }
+
+    /// F5 starts debugging or continues, F10, F11 and Shift+F11 step, Shift+F5 stops
+    fn handle_debug_keys(&mut self, ctx: &egui::Context) {
+        let (shift, f5, f10, f11) = {
+            let input = ctx.input();
+            (
+                input.modifiers.shift,
+                input.key_pressed(egui::Key::F5),
+                input.key_pressed(egui::Key::F10),
+                input.key_pressed(egui::Key::F11),
+            )
+        };
+        match (f5, f10, f11, shift) {
+            (true, _, _, true) => self.debugger = None,
+            (true, _, _, false) if self.debugger.is_none() => self.start_debugging(ctx),
+            (true, _, _, false) => self.debug_step(Debugger::resume),
+            (_, true, _, _) => self.debug_step(Debugger::step_over),
+            (_, _, true, false) => self.debug_step(Debugger::step_into),
+            (_, _, true, true) => self.debug_step(Debugger::step_out),
+            _ => (),
+        }
+    }
+
+    /// The buttons controlling the program being debugged
+    fn draw_debug_controls(&mut self, ui: &mut egui::Ui) {
+        let Some(state) = self.debugger.as_ref().map(Debugger::state) else {
+            return;
+        };
+        if ui
+            .button(egui::RichText::new("Stop debugging").monospace())
+            .clicked()
+        {
+            self.debugger = None;
+            return;
+        }
+        // The layout is right to left
+        for (text, shortcut, step) in DEBUG_STEPS.into_iter().rev() {
+            let button = egui::Button::new(egui::RichText::new(text).monospace());
+            if ui
+                .add_enabled(state == DebugState::Stopped, button)
+                .on_hover_text(shortcut)
+                .clicked()
+            {
+                self.debug_step(step);
+            }
+        }
+    }
+
+    /// Toggle the breakpoint of the line clicked in 'gutter', mark the breakpoints
+    /// there and highlight the line where the program being debugged stopped, in the
+    /// editor showing 'galley' at 'text_pos'
+    fn draw_breakpoints(
+        &mut self,
+        ui: &egui::Ui,
+        gutter: &egui::Response,
+        galley: &egui::Galley,
+        text_pos: egui::Pos2,
+    ) {
+        if let (true, Some(pointer)) = (gutter.clicked(), gutter.interact_pointer_pos()) {
+            let cursor = galley.cursor_from_pos(egui::vec2(0.0, pointer.y - text_pos.y));
+            let line = cursor.pcursor.paragraph;
+            if !self.breakpoints.remove(&line) {
+                self.breakpoints.insert(line);
+            }
+            if let Some(ref mut debugger) = self.debugger {
+                let lines = self.breakpoints.iter().map(|line| line + 1).collect();
+                if let Err(err) = debugger.set_breakpoints(lines) {
+                    log::warning!(format!("The debugger could not be told: {}", err));
+                }
+            }
+        }
+
+        let row_rect = |line: usize| {
+            galley
+                .pos_from_pcursor(egui::epaint::text::cursor::PCursor {
+                    paragraph: line,
+                    offset: 0,
+                    prefer_next_row: false,
+                })
+                .translate(text_pos.to_vec2())
+        };
+        let painter = ui.painter();
+        let radius = self.settings.code_font_size * 0.3;
+        for &line in &self.breakpoints {
+            let center = egui::pos2(gutter.rect.left() + radius, row_rect(line).center().y);
+            painter.circle_filled(center, radius, egui::Color32::RED);
+        }
+
+        let stopped = self
+            .debugger
+            .as_ref()
+            .and_then(Debugger::location)
+            .filter(|(path, _)| self.path.as_deref() == Some(*path));
+        if let Some((_, line)) = stopped {
+            let row = row_rect(line.saturating_sub(1));
+            let rect = egui::Rect::from_x_y_ranges(
+                gutter.rect.left()..=ui.max_rect().right(),
+                row.y_range(),
+            );
+            painter.rect_filled(rect, 0.0, egui::Color32::YELLOW.linear_multiply(0.15));
+        }
+    }
+
+    /// Diff the code with its last commit again if it was edited since
+    fn update_git_changes(&mut self) {
+        let Some(ref head) = self.git_head else {
+            self.git_changes.clear();
+            return;
+        };
+        if self
+            .git_diffed
+            .map_or(false, |diffed| diffed >= self.last_edit)
+        {
+            return;
+        }
+        self.git_changes = git::line_changes(head, &self.contents);
+        self.git_diffed = Some(Instant::now());
+    }
+
+    /// Check the spelling of the comments and strings of the code, once it changed
+    fn update_misspellings(&mut self) {
+        if !self.settings.editor.spell_check {
+            self.misspellings.clear();
+            self.spell_checked = None;
+            return;
+        }
+        let dictionary = self.settings.spell_dictionary.as_deref();
+        if self
+            .spell_checker
+            .as_ref()
+            .map_or(true, |checker| checker.dictionary() != dictionary)
+        {
+            self.spell_checker = Some(SpellChecker::load(dictionary));
+            self.spell_checked = None;
+        }
+        if self
+            .spell_checked
+            .map_or(false, |checked| checked >= self.last_edit)
+        {
+            return;
+        }
+        let Some(ref checker) = self.spell_checker else {
+            return;
+        };
+        self.misspellings = match self
+            .syntaxes
+            .highlighting_for(self.path.as_deref(), &self.settings)
+        {
+            Highlighting::Tokens(config) => checker.check(&self.contents, config),
+            // Only the tokens tell the comments and strings apart
+            _ => Vec::new(),
+        };
+        self.spell_checked = Some(Instant::now());
     }
-}
-
-impl CodeEditor {
-    /// Return the numbers of the lines on the top left of the editor
-    fn lines(&self) -> String {
-        // + 1 because we add one newline at least
-        let row_count = self.contents.chars().filter(|ch| ch == &'\n').count() + 1;
-        let mut lines = (1..=row_count).fold(String::new(), |acc, n| format!("{}\n{}", acc, n));
-        lines.remove(0); // Remove the first newline caused by `fold`
 
-        // If we don't do this shitty thing, the label gets pushed in the middle.
-        // Therefore, we add as many newlines as we need to fill the ui (empirical count)
-        // It looks weird but at least it works :(
-        if row_count < 35 {
-            let delta = 35 - row_count;
-            for _ in 0..delta {
-                lines.push('\n');
+    /// Draw a wavy line under the misspelled words of 'galley', shown at 'text_pos'
+    fn draw_misspellings(&self, ui: &egui::Ui, galley: &egui::Galley, text_pos: egui::Pos2) {
+        let painter = ui.painter();
+        let stroke = egui::Stroke::new(1.0, SPELL_COLOR);
+        for misspelling in &self.misspellings {
+            let rect = |index| {
+                let cursor = galley.from_ccursor(egui::text::CCursor::new(index));
+                galley
+                    .pos_from_cursor(&cursor)
+                    .translate(text_pos.to_vec2())
+            };
+            let (start, end) = (rect(misspelling.chars.start), rect(misspelling.chars.end));
+            // Words wrapped on two rows are left as they are
+            if start.bottom() != end.bottom() || !painter.clip_rect().intersects(start) {
+                continue;
             }
+            let y = start.bottom() - 1.0;
+            let points: Vec<egui::Pos2> = (0..)
+                .map(|step| start.left() + step as f32 * SPELL_WAVE_WIDTH)
+                .take_while(|x| *x <= end.left())
+                .enumerate()
+                .map(|(step, x)| egui::pos2(x, y - (step % 2) as f32 * SPELL_WAVE_WIDTH))
+                .collect();
+            painter.add(egui::Shape::line(points, stroke));
         }
-        lines
     }
 
-    /// If there is a file loaded, we want to show whether the path was saved or not.
-    /// Add a '+' if the file has been saved or '-' if not.
-    fn set_title(&self) -> String {
-        match self.path {
-            Some(ref path) if self.saved => format!("+ {}", path_name_as_string(path)),
-            Some(ref path) if !self.saved => format!("- {}", path_name_as_string(path)),
-            _ => "No file loaded".into(),
+    /// The context menu of the editor, with the suggestions for the misspelled word
+    /// that was clicked
+    fn draw_spell_menu(&mut self, ui: &mut egui::Ui) {
+        // The code may have changed since the word was clicked
+        let span = self
+            .spell_menu
+            .clone()
+            .filter(|span| self.misspellings.iter().any(|m| m.span == *span));
+        let (Some(span), Some(checker)) = (span, self.spell_checker.as_mut()) else {
+            ui.label("No misspelled word here");
+            return;
+        };
+        let word = self.contents[span.clone()].to_string();
+
+        let suggestions = checker.suggestions(&word);
+        if suggestions.is_empty() {
+            ui.label("No suggestions");
+        }
+        for suggestion in suggestions {
+            if ui.button(&suggestion).clicked() {
+                ui.close_menu();
+                self.contents.replace_range(span.clone(), &suggestion);
+                self.saved = false;
+                self.last_edit = Instant::now();
+                self.spell_menu = None;
+                return;
+            }
+        }
+        ui.separator();
+        if ui
+            .button(format!("Add '{}' to the dictionary", word))
+            .clicked()
+        {
+            ui.close_menu();
+            if let Err(err) = checker.add_word(&word) {
+                msgbox(
+                    "Error in adding the word to the dictionary",
+                    err.to_string().as_str(),
+                    rfd::MessageLevel::Error,
+                );
+            }
+            self.spell_checked = None;
+            self.spell_menu = None;
         }
     }
 
-    /// A Ctrl+S event is accepted if:
-    ///     - Ctrl is pressed
-    ///     - S is pressed
-    ///     - The current file is not saved
-    fn handle_ctrl_s(&mut self, events: std::slice::Iter<'_, egui::Event>) {
-        for event in events {
-            if matches!(event, egui::Event::Key { key, pressed, modifiers }
-            if *pressed
-                && matches!(key, egui::Key::S)
-                && modifiers.ctrl
-                && !self.saved
-            ) {
-                self.save_file();
-            }
+    /// Mark the lines changed since the last commit at the right of 'gutter', next to
+    /// the editor showing 'galley' at 'text_pos'
+    fn draw_git_changes(
+        &self,
+        ui: &egui::Ui,
+        gutter: &egui::Response,
+        galley: &egui::Galley,
+        text_pos: egui::Pos2,
+    ) {
+        let painter = ui.painter();
+        let x = gutter.rect.right() - GIT_MARK_WIDTH;
+        for &(line, change) in &self.git_changes {
+            let row = galley
+                .pos_from_pcursor(egui::epaint::text::cursor::PCursor {
+                    paragraph: line,
+                    offset: 0,
+                    prefer_next_row: false,
+                })
+                .translate(text_pos.to_vec2());
+            let (y_range, color) = match change {
+                LineChange::Added => (row.y_range(), egui::Color32::from_rgb(80, 160, 80)),
+                LineChange::Modified => (row.y_range(), egui::Color32::from_rgb(80, 130, 200)),
+                // Between this line and the one above
+                LineChange::Removed => (
+                    row.top() - GIT_MARK_WIDTH..=row.top() + GIT_MARK_WIDTH,
+                    egui::Color32::from_rgb(200, 80, 80),
+                ),
+            };
+            let rect = egui::Rect::from_x_y_ranges(x..=x + GIT_MARK_WIDTH, y_range);
+            painter.rect_filled(rect, 0.0, color);
         }
     }
-    /// A Ctrl+R event is accepted if:
-    ///     - Ctrl is pressed
-    ///     - R is pressed
-    ///     - The current file is not saved
-    fn handle_ctrl_r(&mut self, events: std::slice::Iter<'_, egui::Event>) {
-        for event in events {
-            if matches!(event, egui::Event::Key { key, pressed, modifiers }
-            if *pressed
-                && matches!(key, egui::Key::R)
-                && modifiers.ctrl
-                && !self.saved
-            ) {
-                self.run_file();
-            }
+
+    /// Open the command palette, listing the tasks of the project of the open file
+    fn open_palette(&mut self) {
+        let Some(ref path) = self.path else {
+            msgbox(
+                "No project",
+                "The tasks belong to the folder of the open file: open or save a file first.",
+                rfd::MessageLevel::Info,
+            );
+            return;
+        };
+        match Tasks::load(&runconfig::project_dir(path)) {
+            Ok(tasks) => self.palette = Some((CommandPalette::default(), tasks)),
+            Err(err) => msgbox(
+                "Error in loading the tasks",
+                &err,
+                rfd::MessageLevel::Warning,
+            ),
         }
     }
 
-    /// Handler for saving the current contents
-    fn save_file(&mut self) {
-        let path = match self.path {
-            Some(ref path) => path.clone(),
+    /// Run 'command', the task called 'label', in its page of the console. If the task
+    /// is still running from the last time, it is stopped and its page reused.
+    fn start_task(&mut self, ctx: &egui::Context, label: String, command: process::Command) {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let run = match Run::start(command, self.settings.stderr_color, ctx) {
+            Ok(run) => run,
+            Err(err) => {
+                msgbox(
+                    "Error in running the task",
+                    &format!("'{}' could not be started: {}", program, err),
+                    rfd::MessageLevel::Error,
+                );
+                return;
+            }
+        };
+        log::info!(format!(
+            "Started the task '{}': '{}'",
+            label,
+            run.command_line()
+        ));
+
+        let idx = match self
+            .task_runs
+            .iter()
+            .position(|task_run| task_run.label == label)
+        {
+            Some(idx) => {
+                if let Some(ref mut previous) = self.task_runs[idx].run {
+                    let _ = previous.stop();
+                }
+                idx
+            }
             None => {
-                // The following only gets the path, does not actually create the file
-                let path = rfd::FileDialog::new()
-                    .add_filter("betty file", &["betty"])
-                    .add_filter("Other files", &["*"])
-                    .set_title("Create file")
-                    .save_file();
-                match path {
-                    // Otherwise we cannot live long enough
-                    Some(path) => {
-                        self.path = Some(path.clone());
-                        path
+                self.task_runs.push(TaskRun {
+                    label,
+                    run: None,
+                    output: String::new(),
+                });
+                self.task_runs.len() - 1
+            }
+        };
+        let task_run = &mut self.task_runs[idx];
+        task_run.output = format!("> {}\n", run.command_line());
+        task_run.run = Some(run);
+        self.console_tab = ConsoleTab::Task(idx);
+    }
+
+    /// Add the new output of the running tasks to their pages
+    fn poll_tasks(&mut self, ctx: &egui::Context) {
+        for task_run in &mut self.task_runs {
+            let Some(ref mut run) = task_run.run else {
+                continue;
+            };
+            match run.poll() {
+                Ok(Progress {
+                    output,
+                    status: None,
+                }) => {
+                    task_run.output.push_str(&output);
+                    if run.is_closed() {
+                        ctx.request_repaint_after(RUN_POLL_INTERVAL);
                     }
-                    // The user exited the file dialog
-                    None => return,
+                }
+                Ok(Progress {
+                    output,
+                    status: Some(status),
+                }) => {
+                    task_run.output.push_str(&output);
+                    if !task_run.output.is_empty() && !task_run.output.ends_with('\n') {
+                        task_run.output.push('\n');
+                    }
+                    task_run.output.push_str(&run_footer(status, run));
+                    task_run.run = None;
+                }
+                Err(err) => {
+                    log::warning!(format!(
+                        "The output of the task '{}' could not be read: {}",
+                        task_run.label, err
+                    ));
+                    task_run.run = None;
                 }
             }
+        }
+    }
+
+    /// Terminate the running program, if any
+    fn stop_run(&mut self) {
+        let Some(ref mut run) = self.run else {
+            return;
         };
+        if let Err(err) = run.stop() {
+            msgbox(
+                "Error in stopping the program",
+                err.to_string().as_str(),
+                rfd::MessageLevel::Error,
+            );
+        }
+    }
 
-        self.save_file_contents(path);
+    /// Show 'output' in the console. If it is larger than the console allows, only its
+    /// end is kept and the whole output is written to a temporary file, which can be
+    /// opened from the console.
+    fn set_console_output(&mut self, output: String) {
+        let max_bytes = self.settings.console_max_bytes;
+        if output.len() <= max_bytes {
+            self.console = output;
+            self.full_output = None;
+            return;
+        }
+
+        self.console = console_tail(&output, max_bytes);
+        let path = env::temp_dir().join(FULL_OUTPUT_FILE);
+        self.full_output = match fs::write(&path, &output) {
+            Ok(()) => Some(path),
+            Err(err) => {
+                log::warning!(format!(
+                    "The full output could not be written to '{}': {}",
+                    path.display(),
+                    err
+                ));
+                None
+            }
+        };
     }
 
-    /// Run the current file
-    fn run_file(&mut self) {
-        if self.settings.save_and_run {
-            self.save_file();
+    /// Add 'output' of the running program to the console, keeping only the end of
+    /// the output of the run if it gets larger than the console allows
+    fn append_console_output(&mut self, output: &str) {
+        if output.is_empty() {
+            return;
+        }
+        self.run_output.push_str(output);
+        let max_bytes = self.settings.console_max_bytes;
+        if self.run_output.len() <= max_bytes {
+            self.console.push_str(output);
+        } else {
+            self.console.truncate(self.console_start);
+            self.console
+                .push_str(&console_tail(&self.run_output, max_bytes));
         }
+        self.limit_console_lines();
+    }
 
-        let Some(ref path) = self.path else {
+    /// Drop the oldest lines of the console beyond `console_max_lines`
+    fn limit_console_lines(&mut self) {
+        let max_lines = self.settings.console_max_lines;
+        if max_lines == 0 {
+            return;
+        }
+        let text = self.console.strip_suffix('\n').unwrap_or(&self.console);
+        let Some((idx, _)) = text.rmatch_indices('\n').nth(max_lines - 1) else {
             return;
         };
+        self.console.drain(..=idx);
+        self.console_start = self.console_start.saturating_sub(idx + 1);
+    }
 
-        match run_betty(path, &self.settings.betty_exe_path) {
-            Ok(output) => {
-                // Combine stdout and stderr as one output
-                let contents = format!(
-                    "{}{}",
-                    String::from_utf8_lossy(&output.stdout).into_owned(),
-                    String::from_utf8_lossy(&output.stderr).into_owned()
-                );
-                self.console = contents
-            }
+    /// Empty the console, including the output so far of the running program
+    fn clear_console(&mut self) {
+        self.console.clear();
+        self.console_start = 0;
+        self.run_output.clear();
+        self.full_output = None;
+    }
+
+    /// Open file handler
+    fn open_file(&mut self) {
+        let Some(path) = rfd::FileDialog::new().pick_file() else {
+            // The user exited the file dialog
+            return;
+        };
+        self.open_path(path);
+    }
+
+    /// Start loading the file in 'path' in the editor
+    fn open_path(&mut self, path: PathBuf) {
+        let _span = tracing::info_span!("open", path = %path.display()).entered();
+
+        // The file being saved is written before another one replaces it
+        self.wait_file_io();
+        let max_bytes = self
+            .settings
+            .editor
+            .max_file_size_mb
+            .saturating_mul(1024 * 1024);
+        let file = fs::File::open(&path).and_then(|file| Ok((file.metadata()?.len(), file)));
+        match file {
+            Ok((len, _)) if max_bytes > 0 && len > max_bytes => msgbox(
+                "File too large",
+                &format!(
+                    "The file '{}' is {} MB, more than the {} MB the editor opens. The limit \
+                     can be raised in the Editor page of the settings.",
+                    path_name_as_string(&path),
+                    len / (1024 * 1024),
+                    self.settings.editor.max_file_size_mb
+                ),
+                rfd::MessageLevel::Warning,
+            ),
+            Ok((_, file)) => self.file_io = Some(FileIo::open(file, path)),
             Err(err) => msgbox(
-                "Program execution error",
+                &format!("Error in opening file '{}'", path_name_as_string(&path)),
                 err.to_string().as_str(),
                 rfd::MessageLevel::Error,
             ),
         }
     }
 
-    /// Open file handler
-    fn open_file(&mut self) {
-        let Some(path) = rfd::FileDialog::new().pick_file() else {
-            // The user exited the file dialog
+    /// Apply the result of the file being opened or saved, once it is done
+    fn poll_file_io(&mut self, ctx: &egui::Context) {
+        let Some(ref mut file_io) = self.file_io else {
+            return;
+        };
+        let Some(result) = file_io.poll() else {
+            ctx.request_repaint_after(RUN_POLL_INTERVAL);
             return;
         };
+        let operation = file_io.operation();
+        let path = file_io.path().to_path_buf();
+        self.file_io = None;
+        self.finish_file_io(operation, path, result);
+    }
 
-        match fs::read_to_string(&path) {
-            Ok(contents) => {
+    /// Block until the file being opened or saved, if any, is done
+    fn wait_file_io(&mut self) {
+        let Some(file_io) = self.file_io.take() else {
+            return;
+        };
+        let operation = file_io.operation();
+        let path = file_io.path().to_path_buf();
+        let result = file_io.wait();
+        self.finish_file_io(operation, path, result);
+    }
+
+    fn finish_file_io(
+        &mut self,
+        operation: Operation,
+        path: PathBuf,
+        result: io::Result<Contents>,
+    ) {
+        match operation {
+            Operation::Open => self.finish_open(path, result),
+            Operation::Save => self.finish_save(path, result.map(Contents::into_text)),
+        }
+    }
+
+    /// Show the 'contents' read from 'path' in the editor, or in the hex view if they are
+    /// not text
+    fn finish_open(&mut self, path: PathBuf, contents: io::Result<Contents>) {
+        let hex = self.settings.file_type(Some(&path)).open_as == OpenAs::Hex;
+        match contents {
+            Ok(Contents::Text(text)) if hex => {
+                self.hex_view = Some(HexView::new(path, text.into_bytes()));
+            }
+            Ok(Contents::Binary(bytes)) if hex => {
+                self.hex_view = Some(HexView::new(path, bytes));
+            }
+            Ok(Contents::Binary(bytes)) => {
+                let view = rfd::MessageDialog::new()
+                    .set_title("Binary file")
+                    .set_description(&format!(
+                        "The file '{}' is not a text file. Do you want to see its bytes in \
+                         the read-only hex view instead?",
+                        path_name_as_string(&path)
+                    ))
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+                if view {
+                    self.hex_view = Some(HexView::new(path, bytes));
+                }
+            }
+            Ok(Contents::Text(contents)) => {
+                // The mark would be shown as a stray character in the editor
+                let (contents, bom) = fileio::strip_bom(contents);
+                self.bom = Some(bom);
                 // As the file has just been loaded, it is unmodified
                 // and therefore it is considered saved
                 self.saved = true;
+                log::info!(format!("Opened '{}'", path.display()));
+                self.add_recent(&path);
+                self.clear_recovery();
+                self.breakpoints.clear();
+                self.git_head = git::head_contents(&path);
+                self.git_diffed = None;
+                self.git_window = None;
+                self.spell_checked = None;
+                self.welcome = None;
                 self.path = Some(path);
                 self.contents = contents;
+                self.run_errors.clear();
+                self.lint = None;
+                self.lint_diagnostics.clear();
             }
             Err(err) => msgbox(
                 &format!("Error in opening file '{}'", path_name_as_string(&path)),
@@ -342,27 +3946,46 @@ impl CodeEditor {
         }
     }
 
-    /// Save self.contents into 'path
+    /// Put 'path' first in the recent files
+    fn add_recent(&mut self, path: &Path) {
+        // The other windows may have opened files meanwhile
+        self.recent = RecentFiles::load();
+        self.recent.add(path);
+        if let Err(err) = self.recent.save() {
+            log::warning!(format!("The recent files could not be saved: {}", err));
+        }
+    }
+
+    /// Start saving self.contents into 'path
     fn save_file_contents(&mut self, path: PathBuf) {
-        match fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            // .truncate(true)  This is not needed imho
-            .open(&path)
-        {
-            Ok(mut file) => {
-                if let Err(err) = file.write_all(self.contents.as_bytes()) {
-                    msgbox(
-                        &format!("Error in writing to file '{}'", path_name_as_string(&path)),
-                        err.to_string().as_str(),
-                        rfd::MessageLevel::Error,
-                    );
-                } else {
-                    self.saved = true;
+        let _span = tracing::info_span!("save", path = %path.display()).entered();
+        let bom = self.bom.unwrap_or(self.settings.editor.bom);
+        self.file_io = Some(FileIo::save(path, self.contents.clone(), bom));
+    }
+
+    /// Mark the 'contents' written to 'path' as saved, unless they have been edited since
+    fn finish_save(&mut self, path: PathBuf, contents: io::Result<String>) {
+        match contents {
+            Ok(contents) => {
+                self.saved = self.contents == contents;
+                self.path = Some(path.clone());
+                self.rerun = self.watch;
+                self.clear_recovery();
+                // The file may have just been created
+                if self.git_head.is_none() {
+                    self.git_head = git::head_contents(&path);
                 }
+                if let Some(ref mut server) = self.language_server {
+                    if let Err(err) = server.save() {
+                        log::warning!(format!("The language server could not be told: {}", err));
+                    }
+                }
+                self.add_recent(&path);
+                self.welcome = None;
+                self.start_lint(&path);
             }
             Err(err) => msgbox(
-                "Error in opening file",
+                &format!("Error in writing to file '{}'", path_name_as_string(&path)),
                 err.to_string().as_str(),
                 rfd::MessageLevel::Error,
             ),
@@ -370,59 +3993,432 @@ impl CodeEditor {
     }
 }
 
-/// Highlighter of the source code
+/// Ask where to save the file, with a dialog titled 'title'. This only gets the path,
+/// and does not actually create the file. None if the user exited the dialog.
+fn ask_save_path(title: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("betty file", &["betty"])
+        .add_filter("Other files", &["*"])
+        .set_title(title)
+        .save_file()
+}
+
+/// The lines of the editor to color, around the ones the last 'galley' showed in
+/// 'viewport', from 0. Before the first frame, these are the first lines.
+fn shown_lines(galley: Option<&egui::Galley>, viewport: egui::Rect) -> Range<usize> {
+    let Some(galley) = galley else {
+        return 0..2 * HIGHLIGHT_BLOCK_LINES;
+    };
+    let block_at = |y: f32| {
+        galley.cursor_from_pos(egui::vec2(0.0, y)).pcursor.paragraph / HIGHLIGHT_BLOCK_LINES
+    };
+    let first = block_at(viewport.min.y).saturating_sub(1);
+    let last = block_at(viewport.max.y) + 1;
+    first * HIGHLIGHT_BLOCK_LINES..(last + 1) * HIGHLIGHT_BLOCK_LINES
+}
+
+/// Turn the tokens of 'text', given by the syntax highligher, into colored text
 #[inline]
-fn highlight_text(text: &str, code_color: CodeColor, font_size: f32) -> egui::text::LayoutJob {
+fn highlight_tokens(
+    text: &str,
+    tokens: Vec<Token>,
+    code_color: CodeColor,
+    font_size: f32,
+) -> egui::text::LayoutJob {
     let mut job = egui::text::LayoutJob::default();
-    if text.is_empty() {
-        return job;
-    }
 
-    // Get the tokens from the syntax highligher
-    let highlighter = Highligher::new(text.chars().collect());
-    let tokens = highlighter.make_tokens();
-
-    // For each token, convert the type into a color
+    // For each token, convert the type into a color and a font style
+    let style = code_color.style;
     for token in tokens {
-        let Token(typ, literal) = token;
-        let color = match typ {
-            TokenType::Num => egui::Color32::from_code_color(code_color.number),
-            TokenType::Ident => egui::Color32::from_code_color(code_color.ident),
-            TokenType::Str => egui::Color32::from_code_color(code_color.string),
-            TokenType::Sym => egui::Color32::from_code_color(code_color.symbol),
-            TokenType::Kw => egui::Color32::from_code_color(code_color.keyword),
-            TokenType::BuiltinFun => egui::Color32::from_code_color(code_color.builtin_fn),
+        let Token(typ, span, _) = token;
+        let (color, font_style) = match typ {
+            TokenType::Num => (
+                egui::Color32::from_code_color(code_color.number),
+                style.number,
+            ),
+            TokenType::Ident => (
+                egui::Color32::from_code_color(code_color.ident),
+                style.ident,
+            ),
+            TokenType::Str => (
+                egui::Color32::from_code_color(code_color.string),
+                style.string,
+            ),
+            TokenType::Sym => (
+                egui::Color32::from_code_color(code_color.symbol),
+                style.symbol,
+            ),
+            TokenType::Op => (
+                egui::Color32::from_code_color(code_color.operator),
+                style.operator,
+            ),
+            TokenType::Kw => (
+                egui::Color32::from_code_color(code_color.keyword),
+                style.keyword,
+            ),
+            TokenType::BuiltinFun => (
+                egui::Color32::from_code_color(code_color.builtin_fn),
+                style.builtin_fn,
+            ),
             TokenType::Fun => {
                 let [r, g, b, a] = code_color.fun;
-                egui::Color32::from_rgba_premultiplied(r, g, b, a)
+                (
+                    egui::Color32::from_rgba_premultiplied(r, g, b, a),
+                    style.fun,
+                )
+            }
+            TokenType::Comment => (
+                egui::Color32::from_code_color(code_color.comment),
+                style.comment,
+            ),
+            TokenType::Error => (
+                egui::Color32::from_code_color(code_color.error),
+                style.error,
+            ),
+            TokenType::Undefined => (
+                egui::Color32::from_code_color(code_color.undefined),
+                style.undefined,
+            ),
+            TokenType::Other => (
+                egui::Color32::from_code_color(code_color.other),
+                style.other,
+            ),
+        };
+
+        // Lines have the same color as the text
+        let line = |enabled: bool, color: egui::Color32| {
+            if enabled {
+                egui::Stroke::new(1.0, color)
+            } else {
+                egui::Stroke::NONE
             }
-            TokenType::Comment => egui::Color32::from_code_color(code_color.comment),
-            TokenType::Error => egui::Color32::from_code_color(code_color.error),
-            TokenType::Other => egui::Color32::from_code_color(code_color.other),
         };
 
+        let format = egui::text::TextFormat {
+            color,
+            font_id: egui::FontId::new(font_size, fonts::monospace(font_style.bold)),
+            italics: font_style.italic,
+            underline: line(font_style.underline, color),
+            strikethrough: line(font_style.strikethrough, color),
+            ..Default::default()
+        };
+
+        // Give the tags in comments their own color
+        let mut start = span.start;
+        if typ == TokenType::Comment {
+            let todo_color = egui::Color32::from_code_color(code_color.todo);
+            let todo_format = egui::text::TextFormat {
+                color: todo_color,
+                font_id: egui::FontId::new(font_size, fonts::monospace(style.todo.bold)),
+                italics: style.todo.italic,
+                underline: line(style.todo.underline, todo_color),
+                strikethrough: line(style.todo.strikethrough, todo_color),
+                ..format.clone()
+            };
+            for (_, tag) in todo::find_tags(&text[span.clone()]) {
+                let tag = span.start + tag.start..span.start + tag.end;
+                if start < tag.start {
+                    job.append(&text[start..tag.start], 0.0, format.clone());
+                }
+                job.append(&text[tag.clone()], 0.0, todo_format.clone());
+                start = tag.end;
+            }
+        }
+
         // Push the color into the buffer
-        job.append(
-            &literal,
-            0.0,
-            egui::text::TextFormat {
-                color,
-                font_id: egui::FontId::new(font_size, egui::FontFamily::Monospace),
-                ..Default::default()
-            },
-        );
+        if start < span.end {
+            job.append(&text[start..span.end], 0.0, format);
+        }
     }
 
     job
 }
 
+/// Open the betty files with the IDE if 'associate', or stop opening them with it,
+/// telling the user how it went
+fn associate_files(associate: bool) {
+    let result = if associate {
+        association::register()
+    } else {
+        association::unregister()
+    };
+    match (result, associate) {
+        (Ok(()), true) => msgbox(
+            "betty files",
+            "The betty files now open with Colors, which is also in the menu of every file",
+            rfd::MessageLevel::Info,
+        ),
+        (Ok(()), false) => msgbox(
+            "betty files",
+            "The betty files no longer open with Colors",
+            rfd::MessageLevel::Info,
+        ),
+        (Err(err), _) => msgbox(
+            "Error in associating the betty files",
+            err.to_string().as_str(),
+            rfd::MessageLevel::Error,
+        ),
+    }
+}
+
+/// List the variables where the program being debugged stopped, by scope
+fn draw_variables(ui: &mut egui::Ui, debugger: &Debugger, font_size: f32) {
+    ui.heading("Variables");
+    if debugger.state() != DebugState::Stopped {
+        ui.label("The program is running");
+        return;
+    }
+    egui::ScrollArea::both()
+        .id_source("variables")
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for scope in debugger.scopes() {
+                egui::CollapsingHeader::new(&scope.name)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new(&scope.name).striped(true).show(ui, |ui| {
+                            for (name, value) in &scope.variables {
+                                ui.label(egui::RichText::new(name).size(font_size).monospace());
+                                ui.label(egui::RichText::new(value).size(font_size).monospace());
+                                ui.end_row();
+                            }
+                        });
+                    });
+            }
+        });
+}
+
+/// Add a button to the open menu, closing it when clicked. Return whether it was.
+fn menu_item(ui: &mut egui::Ui, text: &str, shortcut: &str) -> bool {
+    let clicked = ui
+        .add(egui::Button::new(text).shortcut_text(shortcut))
+        .clicked();
+    if clicked {
+        ui.close_menu();
+    }
+    clicked
+}
+
+/// The line shown in the console after the output of a run, e.g.
+/// `--- exited with code 1 in 2.34s ---`. It is red (with an ANSI sequence) when the
+/// program failed or was stopped.
+fn run_footer(status: ExitStatus, run: &Run) -> String {
+    let elapsed = run.elapsed().as_secs_f32();
+    let (text, failed) = match status.code() {
+        _ if run.is_cancelled() => (format!("--- cancelled after {:.2}s ---", elapsed), true),
+        _ if run.is_timed_out() => (
+            format!(
+                "--- stopped after {:.2}s: the time limit (run_timeout_secs) was reached ---",
+                elapsed
+            ),
+            true,
+        ),
+        Some(code) => (
+            format!("--- exited with code {} in {:.2}s ---", code, elapsed),
+            code != 0,
+        ),
+        // Killed by a signal
+        None => (format!("--- terminated after {:.2}s ---", elapsed), true),
+    };
+    if failed {
+        format!("\x1b[1;31m{}\x1b[0m\n", text)
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+/// The last 'max_bytes' of 'output' (or a little less, to cut it between two
+/// characters), after a line telling that it was truncated
+fn console_tail(output: &str, max_bytes: usize) -> String {
+    let mut start = output.len().saturating_sub(max_bytes);
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!(
+        "--- Output truncated: showing the last {} of {} bytes ---\n{}",
+        output.len() - start,
+        output.len(),
+        &output[start..]
+    )
+}
+
+/// The command running the file in 'path' as given by 'template', where `{betty}` is
+/// replaced by 'betty_exe_path' and `{file}` by 'path'. The template is split on
+/// whitespace before the replacement, so paths containing spaces stay a single argument.
+/// The first word is the program, which is started directly rather than through
+/// `cmd`, so the arguments are quoted as needed.
 #[inline]
-fn run_betty(path: &Path, betty_exe_path: &str) -> io::Result<process::Output> {
-    process::Command::new("cmd")
-        .arg("/C")
-        .arg(betty_exe_path)
-        .arg(ffi::OsString::from(path))
-        .output()
+fn betty_command(path: &Path, betty_exe_path: &str, template: &str) -> process::Command {
+    let mut args = template.split_whitespace().map(|arg| match arg {
+        "{betty}" => ffi::OsString::from(betty_exe_path),
+        "{file}" => ffi::OsString::from(path),
+        _ => ffi::OsString::from(arg),
+    });
+
+    // The template is not empty
+    let mut command = process::Command::new(args.next().unwrap_or_default());
+    command.args(args);
+    command
+}
+
+/// A Ctrl+Shift+C event, which stops the running program like Ctrl+C in a terminal,
+/// is accepted if:
+///     - Ctrl and Shift are pressed
+///     - C is pressed
+fn is_ctrl_shift_c(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::C)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+L event, which clears the console, is accepted if:
+///     - Ctrl is pressed
+///     - L is pressed
+fn is_ctrl_l(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::L)
+            && modifiers.ctrl
+        )
+    })
+}
+
+/// A Ctrl+P event, which prints the file, is accepted if:
+///     - Ctrl is pressed, but not Shift
+///     - P is pressed
+fn is_ctrl_p(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::P)
+            && modifiers.ctrl
+            && !modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+Shift+P event, which opens the command palette, is accepted if:
+///     - Ctrl and Shift are pressed
+///     - P is pressed
+fn is_ctrl_shift_p(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::P)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// Remove the presses of Ctrl+Enter from 'events', so that they do not add a new line
+/// to the editor, and return whether there was any
+fn take_ctrl_enter(events: &mut Vec<egui::Event>) -> bool {
+    let count = events.len();
+    events.retain(|event| {
+        !matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::Enter)
+            && modifiers.ctrl
+        )
+    });
+    events.len() < count
+}
+
+/// A Ctrl+Shift+D event is accepted if:
+///     - Ctrl and Shift are pressed
+///     - D is pressed
+fn is_ctrl_shift_d(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::D)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+Shift+F12 event, which toggles the performance overlay, is accepted if:
+///     - Ctrl and Shift are pressed
+///     - F12 is pressed
+fn is_ctrl_shift_f12(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::F12)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+Shift+F event, which formats the document, is accepted if:
+///     - Ctrl and Shift are pressed
+///     - F is pressed
+fn is_ctrl_shift_f(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::F)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+Shift+M event starts or stops recording a macro
+fn is_ctrl_shift_m(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::M)
+            && modifiers.ctrl
+            && modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+M event (without Shift) plays the macro
+fn is_ctrl_m(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::M)
+            && modifiers.ctrl
+            && !modifiers.shift
+        )
+    })
+}
+
+/// A Ctrl+Space event asks for the completions at the cursor
+fn is_ctrl_space(mut events: std::slice::Iter<'_, egui::Event>) -> bool {
+    events.any(|event| {
+        matches!(event, egui::Event::Key { key, pressed, modifiers }
+        if *pressed
+            && matches!(key, egui::Key::Space)
+            && modifiers.ctrl
+        )
+    })
+}
+
+/// Convert an index expressed in characters into an index expressed in bytes
+fn char_to_byte_index(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(idx, _)| idx)
+}
+
+/// Convert an index expressed in bytes into an index expressed in characters. Return
+/// [`None`] if the index is past the end of 'text' or inside a character.
+fn byte_to_char_index(text: &str, byte_index: usize) -> Option<usize> {
+    text.get(..byte_index).map(|before| before.chars().count())
 }
 
 /// Spawn a MessageBox with the given title, description and level
@@ -454,3 +4450,19 @@ impl FromCodeColor for egui::Color32 {
         Self::from_rgb(rgb[0], rgb[1], rgb[2])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_byte_indexes_have_no_char_index() {
+        let text = "é = 1";
+        assert_eq!(byte_to_char_index(text, 0), Some(0));
+        assert_eq!(byte_to_char_index(text, 2), Some(1));
+        assert_eq!(byte_to_char_index(text, text.len()), Some(5));
+        // Inside 'é', and past the end after a deletion
+        assert_eq!(byte_to_char_index(text, 1), None);
+        assert_eq!(byte_to_char_index(text, 42), None);
+    }
+}
@@ -0,0 +1,60 @@
+//! Read-only viewer for the full output of a program, used when the output is too
+//! large to be kept in the console. Only the visible rows are laid out, so even
+//! huge outputs can be scrolled smoothly.
+
+use eframe::egui;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub struct OutputViewer {
+    /// Lines of the output file
+    lines: Vec<String>,
+
+    /// Is the window still open?
+    open: bool,
+}
+
+impl OutputViewer {
+    /// Load the output stored in 'path'
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        Ok(Self {
+            lines: String::from_utf8_lossy(&contents)
+                .lines()
+                .map(String::from)
+                .collect(),
+            open: true,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draw the viewer as a separate window
+    pub fn show(&mut self, ctx: &egui::Context, font_size: f32) {
+        let font_id = egui::FontId::new(font_size, egui::FontFamily::Monospace);
+        let row_height = ctx.fonts().row_height(&font_id);
+        let lines = &self.lines;
+
+        egui::Window::new("Full output")
+            .open(&mut self.open)
+            .default_size((800.0, 600.0))
+            .vscroll(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::both()
+                    .id_source("full_output")
+                    .auto_shrink([false, false])
+                    .show_rows(ui, row_height, lines.len(), |ui, rows| {
+                        for line in &lines[rows] {
+                            ui.label(
+                                egui::RichText::new(line)
+                                    .font(font_id.clone())
+                                    .color(ui.visuals().strong_text_color()),
+                            );
+                        }
+                    });
+            });
+    }
+}
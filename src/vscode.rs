@@ -0,0 +1,257 @@
+//! Import of VS Code color themes. The colors of the TextMate scopes of the theme
+//! are mapped to the [`CodeColor`] fields, and the fields whose scopes the theme does
+//! not color keep the colors of the default theme.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::jsonedit;
+use super::settings::{CodeColor, FontStyle};
+use super::theme::theme_name;
+
+/// Themes can include other themes, e.g. to extend the default ones of VS Code
+const MAX_INCLUDES: usize = 8;
+
+/// Color and font style given by the theme to a scope
+#[derive(Clone, Copy)]
+struct ScopeStyle {
+    color: Option<[u8; 4]>,
+    font_style: Option<FontStyle>,
+}
+
+/// The rules of the theme, as pairs of scope selector and style, in order
+type Rules = Vec<(String, ScopeStyle)>;
+
+/// Read the VS Code theme in 'path' and the themes it includes. Return the name of
+/// the theme (or of the file, if it has none) in lowercase, and its colors.
+pub fn import(path: &Path) -> Result<(String, CodeColor), String> {
+    let mut rules = Rules::new();
+    let mut foreground = None;
+    let theme = read_theme(path, &mut rules, &mut foreground, 0)?;
+
+    let name = theme
+        .get("name")
+        .and_then(Value::as_str)
+        .map(theme_name)
+        .or_else(|| {
+            path.file_stem()
+                .map(|stem| theme_name(&stem.to_string_lossy()))
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "vscode".into());
+
+    let mut color = CodeColor::default();
+    let mut style = color.style;
+    let fields: [(&[&str], &mut [u8; 3], &mut FontStyle); 11] = [
+        (
+            &["variable.other", "variable"],
+            &mut color.ident,
+            &mut style.ident,
+        ),
+        (&["constant.numeric"], &mut color.number, &mut style.number),
+        (
+            &["string.quoted", "string"],
+            &mut color.string,
+            &mut style.string,
+        ),
+        (
+            &["punctuation.section", "punctuation"],
+            &mut color.symbol,
+            &mut style.symbol,
+        ),
+        (
+            &["keyword.operator"],
+            &mut color.operator,
+            &mut style.operator,
+        ),
+        (
+            &["keyword.control", "keyword", "storage"],
+            &mut color.keyword,
+            &mut style.keyword,
+        ),
+        (
+            &["support.function"],
+            &mut color.builtin_fn,
+            &mut style.builtin_fn,
+        ),
+        (&["comment"], &mut color.comment, &mut style.comment),
+        (
+            &["keyword.codetag", "comment.todo", "markup.bold"],
+            &mut color.todo,
+            &mut style.todo,
+        ),
+        (
+            &[
+                "support.type.exception",
+                "entity.name.type.exception",
+                "support.class",
+            ],
+            &mut color.error,
+            &mut style.error,
+        ),
+        (
+            &["invalid.illegal", "invalid"],
+            &mut color.undefined,
+            &mut style.undefined,
+        ),
+    ];
+    for (scopes, rgb, font_style) in fields {
+        let scope_style = find_style(&rules, scopes);
+        if let Some([r, g, b, _]) = scope_style.color {
+            *rgb = [r, g, b];
+        }
+        if let Some(new_style) = scope_style.font_style {
+            *font_style = new_style;
+        }
+    }
+
+    // Functions have an alpha channel
+    let fun = find_style(&rules, &["entity.name.function", "support.function"]);
+    if let Some(rgba) = fun.color {
+        color.fun = rgba;
+    }
+    if let Some(font_style) = fun.font_style {
+        style.fun = font_style;
+    }
+
+    // The text that has no scope
+    if let Some([r, g, b, _]) = foreground {
+        color.other = [r, g, b];
+    }
+
+    color.style = style;
+    Ok((name, color))
+}
+
+/// Read the theme in 'path', after the theme it includes, adding its rules to
+/// 'rules' and its editor foreground to 'foreground'
+fn read_theme(
+    path: &Path,
+    rules: &mut Rules,
+    foreground: &mut Option<[u8; 4]>,
+    depth: usize,
+) -> Result<Value, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|err| format!("'{}' could not be read: {}", path.display(), err))?;
+    let theme: Value = serde_json::from_str(&jsonedit::strip_comments(&json))
+        .map_err(|err| format!("'{}' is not a valid theme: {}", path.display(), err))?;
+
+    // The included theme comes first, so that this one overrides it
+    if let Some(include) = theme.get("include").and_then(Value::as_str) {
+        if depth == MAX_INCLUDES {
+            return Err(format!("'{}' includes too many themes", path.display()));
+        }
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        read_theme(&dir.join(include), rules, foreground, depth + 1)?;
+    }
+
+    if let Some(color) = theme
+        .pointer("/colors/editor.foreground")
+        .and_then(Value::as_str)
+        .and_then(parse_color)
+    {
+        *foreground = Some(color);
+    }
+
+    let token_colors = theme.get("tokenColors").and_then(Value::as_array);
+    for rule in token_colors.into_iter().flatten() {
+        let Some(settings) = rule.get("settings") else {
+            continue;
+        };
+        let style = ScopeStyle {
+            color: settings
+                .get("foreground")
+                .and_then(Value::as_str)
+                .and_then(parse_color),
+            font_style: settings
+                .get("fontStyle")
+                .and_then(Value::as_str)
+                .map(|font_style| FontStyle {
+                    bold: font_style.contains("bold"),
+                    italic: font_style.contains("italic"),
+                    underline: font_style.contains("underline"),
+                    strikethrough: font_style.contains("strikethrough"),
+                }),
+        };
+
+        // The scopes are a list, or a string with the scopes separated by commas
+        let scopes: Vec<&str> = match rule.get("scope") {
+            Some(Value::String(scopes)) => scopes.split(',').collect(),
+            Some(Value::Array(scopes)) => scopes.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        for scope in scopes {
+            rules.push((scope.trim().to_string(), style));
+        }
+    }
+    Ok(theme)
+}
+
+/// Find the style of the first of 'scopes' that the rules match. As in VS Code, a
+/// selector matches the scopes that start with it, and the most specific selector
+/// wins, then the last one. Selectors with many scopes (e.g. `source.python string`)
+/// only apply in other languages, and are ignored.
+fn find_style(rules: &Rules, scopes: &[&str]) -> ScopeStyle {
+    let mut found = ScopeStyle {
+        color: None,
+        font_style: None,
+    };
+    for scope in scopes {
+        let mut best_color: Option<(usize, [u8; 4])> = None;
+        let mut best_font_style: Option<(usize, FontStyle)> = None;
+
+        for (selector, style) in rules {
+            let matches = *scope == selector
+                || scope
+                    .strip_prefix(selector.as_str())
+                    .map_or(false, |rest| rest.starts_with('.'));
+            if !matches {
+                continue;
+            }
+            if let Some(color) = style.color {
+                if best_color.map_or(true, |(len, _)| selector.len() >= len) {
+                    best_color = Some((selector.len(), color));
+                }
+            }
+            if let Some(font_style) = style.font_style {
+                if best_font_style.map_or(true, |(len, _)| selector.len() >= len) {
+                    best_font_style = Some((selector.len(), font_style));
+                }
+            }
+        }
+
+        found.color = found.color.or(best_color.map(|(_, color)| color));
+        found.font_style = found
+            .font_style
+            .or(best_font_style.map(|(_, font_style)| font_style));
+        if found.color.is_some() {
+            break;
+        }
+    }
+    found
+}
+
+/// Parse a color written as `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.strip_prefix('#')?;
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|ch| ch.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<_>>()?;
+    let channels: Vec<u8> = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit * 17).collect(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|pair| pair[0] * 16 + pair[1])
+            .collect(),
+        _ => return None,
+    };
+    Some([
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).copied().unwrap_or(255),
+    ])
+}
@@ -0,0 +1,152 @@
+//! Welcome page, shown in place of the editor while no file is loaded. It lists the
+//! files opened lately, which are kept in `recent.json` in the user folder, and the
+//! betty files of a folder chosen with "Open folder".
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+
+use super::paths;
+
+/// Files kept in the list of the recent ones, the oldest ones being forgotten first
+const MAX_RECENT: usize = 10;
+
+/// Links shown under the commands, with their text
+const LINKS: [(&str, &str); 2] = [
+    ("betty documentation", "https://github.com/lowfrank/betty"),
+    ("colors on GitHub", "https://github.com/lowfrank/colors"),
+];
+
+/// The files opened lately, the latest first
+#[derive(Default)]
+pub struct RecentFiles {
+    files: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    /// The files saved when the IDE last changed them, if any
+    pub fn load() -> Self {
+        let files = fs::read_to_string(paths::recent_file())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { files }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(paths::recent_file(), serde_json::to_string(&self.files)?)
+    }
+
+    /// Put 'path' first in the list
+    pub fn add(&mut self, path: &Path) {
+        self.files.retain(|file| file != path);
+        self.files.insert(0, path.to_path_buf());
+        self.files.truncate(MAX_RECENT);
+    }
+}
+
+/// What the user chose in the welcome page
+pub enum WelcomeAction {
+    NewFile,
+    OpenFile,
+    Open(PathBuf),
+}
+
+#[derive(Default)]
+pub struct Welcome {
+    /// Folder chosen with "Open folder", and its betty files
+    folder: Option<(PathBuf, Vec<PathBuf>)>,
+}
+
+impl Welcome {
+    /// Draw the page in 'ui', in place of the editor
+    pub fn show(&mut self, ui: &mut egui::Ui, recent: &RecentFiles) -> Option<WelcomeAction> {
+        let mut action = None;
+        let strong = ui.visuals().strong_text_color();
+
+        egui::ScrollArea::vertical()
+            .id_source("welcome")
+            .max_height(ui.available_height() * 0.85)
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.add_space(20.0);
+                ui.heading(egui::RichText::new("Colors").color(strong).size(28.0));
+                ui.label("An IDE for the betty programming language");
+                ui.add_space(15.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("New file").clicked() {
+                        action = Some(WelcomeAction::NewFile);
+                    }
+                    if ui.button("Open file...").clicked() {
+                        action = Some(WelcomeAction::OpenFile);
+                    }
+                    if ui.button("Open folder...").clicked() {
+                        self.open_folder();
+                    }
+                });
+                ui.add_space(15.0);
+
+                ui.label(egui::RichText::new("Recent files").color(strong).strong());
+                if recent.files.is_empty() {
+                    ui.weak("No file opened yet");
+                }
+                for file in &recent.files {
+                    if file_link(ui, file) {
+                        action = Some(WelcomeAction::Open(file.clone()));
+                    }
+                }
+
+                if let Some((folder, files)) = &self.folder {
+                    ui.add_space(15.0);
+                    let title = format!("Files in {}", folder.display());
+                    ui.label(egui::RichText::new(title).color(strong).strong());
+                    if files.is_empty() {
+                        ui.weak("No betty file in this folder");
+                    }
+                    for file in files {
+                        if file_link(ui, file) {
+                            action = Some(WelcomeAction::Open(file.clone()));
+                        }
+                    }
+                }
+
+                ui.add_space(15.0);
+                for (text, url) in LINKS {
+                    ui.hyperlink_to(text, url);
+                }
+            });
+        action
+    }
+
+    /// Choose a folder and list its betty files
+    fn open_folder(&mut self) {
+        let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+            // The user exited the file dialog
+            return;
+        };
+        let mut files: Vec<PathBuf> = fs::read_dir(&folder)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "betty"))
+            .collect();
+        files.sort();
+        self.folder = Some((folder, files));
+    }
+}
+
+/// Show 'file' as a link with its name, and its folder on hover. Return whether it
+/// was clicked.
+fn file_link(ui: &mut egui::Ui, file: &Path) -> bool {
+    let name = file.file_name().map_or_else(
+        || file.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    ui.link(name)
+        .on_hover_text(file.display().to_string())
+        .clicked()
+}